@@ -3,9 +3,9 @@ extern crate polydraw;
 use std::usize;
 
 use polydraw::{Application, Renderer, Frame};
-use polydraw::draw::RGB;
+use polydraw::draw::RGBA;
 use polydraw::geom::point::Point;
-use polydraw::raster::{Scene, Segment, Circle, EdgeType, EdgeSrc, Poly, Rasterizer};
+use polydraw::raster::{Scene, Segment, Circle, EdgeType, EdgeSrc, Poly, Paint, Rasterizer};
 
 struct SceneRenderer {
    rasterizer: Rasterizer,
@@ -73,19 +73,20 @@ impl SceneRenderer {
          Poly::new(9, 12, 2),
       ];
 
-      let colors = vec![
-         RGB::new(194, 243, 137),
-         RGB::new(154, 222, 76),
-         RGB::new(172, 58, 162),
+      let paints = vec![
+         Paint::Solid(RGBA::new(194, 243, 137, 255)),
+         Paint::Solid(RGBA::new(154, 222, 76, 255)),
+         Paint::Solid(RGBA::new(172, 58, 162, 255)),
       ];
 
       Scene {
          points: points,
          segments: segments,
          circles: circles,
+         curves: vec![],
          edges: edges,
          polys: polys,
-         colors: colors,
+         paints: paints,
       }
    }
 
@@ -110,7 +111,7 @@ impl Renderer for SceneRenderer {
       let mut scene = self.create_scene();
       self.scale_scene(&mut scene);
 
-      self.rasterizer.render(&scene, frame, self.div_per_pixel);
+      self.rasterizer.render(&scene, frame, self.div_per_pixel).unwrap();
    }
 }
 
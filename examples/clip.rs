@@ -1,124 +1,67 @@
 extern crate polydraw;
 
-use polydraw::geom::point::Point;
 use polydraw::{Application, Renderer, Frame};
-use polydraw::raster::{Scene, Rasterizer, EdgeType, Poly};
-
-
-#[derive(Debug, Clone, Copy)]
-pub struct Edge {
-   pub edge_type: EdgeType,
-   pub p1: Point,
-   pub p2: Point,
-}
-
-impl Edge {
-   #[inline]
-   pub fn new(edge_type: EdgeType, p1: Point, p2: Point) -> Self {
-      Edge {
-         edge_type: edge_type,
-         p1: p1,
-         p2: p2,
-      }
-   }
-
-   #[inline]
-   pub fn vert_top(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LVT, p1, p2)
-   }
-
-   #[inline]
-   pub fn vert_bottom(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LVB, p1, p2)
-   }
-
-   #[inline]
-   pub fn hori_right(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LHR, p1, p2)
-   }
-
-   #[inline]
-   pub fn hori_left(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LHL, p1, p2)
-   }
-
-   #[inline]
-   pub fn top_right(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LTR, p1, p2)
-   }
-
-   #[inline]
-   pub fn top_left(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LTL, p1, p2)
-   }
-
-   #[inline]
-   pub fn bottom_right(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LBR, p1, p2)
-   }
-
-   #[inline]
-   pub fn bottom_left(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LBL, p1, p2)
-   }
-}
-
+use polydraw::draw::RGBA;
+use polydraw::raster::{Scene, SceneCompiler, Path, Rasterizer, FillRule};
 
 struct ClipRenderer {
    rasterizer: Rasterizer,
    div_per_pixel: i64,
-
-   edges: Vec<Edge>,
-   polys: Vec<Poly>,
 }
 
 impl ClipRenderer {
    fn new() -> Self {
-      let edges = vec![
-         Edge::top_left(Point::new(5, 1), Point::new(1, 6)),       // 0
-         Edge::top_right(Point::new(1, 6), Point::new(8, 9)),      // 1
-         Edge::bottom_right(Point::new(8, 9), Point::new(9, 6)),   // 2
-         Edge::bottom_left(Point::new(9, 6), Point::new(5, 1)),    // 3
-         Edge::top_right(Point::new(1, 1), Point::new(3, 8)),      // 4
-         Edge::bottom_right(Point::new(3, 8), Point::new(10, 4)),  // 5
-         Edge::bottom_left(Point::new(10, 4), Point::new(1, 1)),   // 6
-      ];
-
-      let polys = vec![
-         Poly::new(0, 4, 0),    // 0
-         Poly::new(4, 7, 1),    // 1
-      ];
-
       ClipRenderer {
          rasterizer: Rasterizer::new(),
          div_per_pixel: 1000,
-
-         edges: edges,
-         polys: polys,
       }
    }
 
-   fn create_scene(&self) -> Scene {
-      let points = vec![];
+   fn create_scene(&self, frame: &Frame) -> Scene {
+      let s = self.div_per_pixel * frame.width.min(frame.height) as i64 / 12;
+
+      // Rounded viewport with a round window cut out of it
+      let mut viewport = Path::new();
+      viewport.move_to(s, 3 * s);
+      viewport.line_to(s, 9 * s);
+      viewport.arc_to(3 * s, 11 * s, 3 * s, 9 * s, true);
+      viewport.line_to(9 * s, 11 * s);
+      viewport.arc_to(11 * s, 9 * s, 9 * s, 9 * s, true);
+      viewport.line_to(11 * s, 3 * s);
+      viewport.arc_to(9 * s, s, 9 * s, 3 * s, true);
+      viewport.line_to(3 * s, s);
+      viewport.arc_to(s, 3 * s, 3 * s, 3 * s, true);
+      viewport.close();
+      viewport.move_to(8 * s, 6 * s);
+      viewport.arc_to(8 * s, 6 * s, 6 * s, 6 * s, true);
+      viewport.fill_rule = FillRule::EvenOdd;
+
+      let mut stripes = Vec::new();
+      for i in 0..6 {
+         let mut stripe = Path::new();
+         stripe.move_to(2 * i * s, 0);
+         stripe.line_to(2 * i * s - 4 * s, 12 * s);
+         stripe.line_to(2 * i * s - 3 * s, 12 * s);
+         stripe.line_to(2 * i * s + s, 0);
+         stripe.close();
+         stripes.push(stripe);
+      }
 
-      let segments = vec![];
+      let mut sun = Path::new();
+      sun.move_to(11 * s, 11 * s);
+      sun.arc_to(11 * s, 11 * s, 8 * s, 11 * s, true);
 
-      let circles = vec![];
+      let mut compiler = SceneCompiler::new();
 
-      let edges = vec![];
+      compiler.set_clip(Some(&viewport));
 
-      let polys = vec![];
+      for stripe in &stripes {
+         compiler.add_path(stripe, RGBA::new(194, 243, 137, 255));
+      }
 
-      let colors = vec![];
+      compiler.add_path(&sun, RGBA::new(172, 58, 162, 255));
 
-      Scene {
-         points: points,
-         segments: segments,
-         circles: circles,
-         edges: edges,
-         polys: polys,
-         colors: colors,
-      }
+      compiler.compile().unwrap()
    }
 }
 
@@ -126,9 +69,9 @@ impl Renderer for ClipRenderer {
    fn render(&mut self, frame: &mut Frame) {
       frame.clear();
 
-      let scene = self.create_scene();
+      let scene = self.create_scene(frame);
 
-      self.rasterizer.render(&scene, frame, self.div_per_pixel);
+      self.rasterizer.render(&scene, frame, self.div_per_pixel).unwrap();
    }
 }
 
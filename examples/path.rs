@@ -0,0 +1,68 @@
+extern crate polydraw;
+
+use polydraw::{Application, Renderer, Frame};
+use polydraw::draw::RGBA;
+use polydraw::raster::{Scene, SceneBuilder, Path, Rasterizer};
+
+struct PathRenderer {
+   rasterizer: Rasterizer,
+   div_per_pixel: i64,
+}
+
+impl PathRenderer {
+   fn new() -> Self {
+      PathRenderer {
+         rasterizer: Rasterizer::new(),
+         div_per_pixel: 1000,
+      }
+   }
+
+   fn create_scene(&self, frame: &Frame) -> Scene {
+      let s = self.div_per_pixel * frame.width.min(frame.height) as i64 / 12;
+
+      let mut leaf = Path::new();
+      leaf.move_to(0, 0);
+      leaf.line_to(0, 8 * s);
+      leaf.arc_to(4 * s, 12 * s, 4 * s, 8 * s, true);
+      leaf.line_to(12 * s, 12 * s);
+      leaf.line_to(12 * s, 4 * s);
+      leaf.quad_to(12 * s, 0, 8 * s, 0);
+      leaf.close();
+
+      let mut corner = Path::new();
+      corner.move_to(8 * s, 0);
+      corner.quad_to(12 * s, 0, 12 * s, 4 * s);
+      corner.line_to(12 * s, 0);
+
+      let mut notch = Path::new();
+      notch.move_to(4 * s, 12 * s);
+      notch.arc_to(0, 8 * s, 4 * s, 8 * s, false);
+      notch.line_to(0, 12 * s);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&leaf, RGBA::new(194, 243, 137, 255)).unwrap();
+      builder.add_path(&corner, RGBA::new(154, 222, 76, 255)).unwrap();
+      builder.add_path(&notch, RGBA::new(172, 58, 162, 255)).unwrap();
+
+      builder.build()
+   }
+}
+
+impl Renderer for PathRenderer {
+   fn render(&mut self, frame: &mut Frame) {
+      frame.clear();
+
+      let scene = self.create_scene(frame);
+
+      self.rasterizer.render(&scene, frame, self.div_per_pixel).unwrap();
+   }
+}
+
+fn main() {
+   let mut renderer = PathRenderer::new();
+
+   Application::new()
+      .renderer(&mut renderer)
+      .title("Path")
+      .run();
+}
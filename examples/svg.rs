@@ -0,0 +1,53 @@
+extern crate polydraw;
+
+use std::env;
+
+use polydraw::{Application, Renderer, Frame};
+use polydraw::svg::Document;
+
+const ICON: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="96" viewBox="0 0 128 96">
+   <rect x="4" y="4" width="120" height="88" rx="12" fill="#f0f0e8" stroke="#333" stroke-width="3"/>
+   <g transform="rotate(-15 40 48)" fill="orange" opacity="0.8">
+      <ellipse cx="40" cy="48" rx="26" ry="14"/>
+   </g>
+   <circle cx="92" cy="36" r="18" fill="rgba(0, 128, 255, 0.6)" stroke="navy" stroke-width="2" stroke-dasharray="6 3"/>
+   <polyline points="70,80 82,62 94,76 110,56" fill="none" stroke="green" stroke-width="5" stroke-linejoin="round" stroke-linecap="round"/>
+   <path d="M20 84a10 6 30 1 1 20 0z" fill="purple"/>
+   <line x1="10" y1="10" x2="118" y2="86" stroke="red" stroke-opacity="0.5"/>
+</svg>"##;
+
+struct SvgRenderer {
+   document: Document,
+   div_per_pixel: i64,
+}
+
+impl SvgRenderer {
+   fn new(document: Document) -> Self {
+      SvgRenderer {
+         document,
+         div_per_pixel: 1000,
+      }
+   }
+}
+
+impl Renderer for SvgRenderer {
+   fn render(&mut self, frame: &mut Frame) {
+      frame.clear();
+
+      self.document.render(frame, self.div_per_pixel).unwrap();
+   }
+}
+
+fn main() {
+   let document = match env::args().nth(1) {
+      Some(path) => Document::from_file(&path).unwrap(),
+      None => Document::parse(ICON).unwrap(),
+   };
+
+   let mut renderer = SvgRenderer::new(document);
+
+   Application::new()
+      .renderer(&mut renderer)
+      .title("SVG")
+      .run();
+}
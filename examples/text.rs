@@ -0,0 +1,74 @@
+extern crate polydraw;
+
+use std::env;
+
+use polydraw::{Application, Renderer, Frame};
+use polydraw::draw::RGBA;
+use polydraw::raster::{Scene, SceneCompiler, Rasterizer};
+use polydraw::font::{Font, GlyphCache, TextLayout, Align};
+
+const DEFAULT_FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+const TEXT: &str = "Typography: the quick brown fox jumps over the lazy dog. AVATAR, Towel, Wave.";
+
+struct TextRenderer {
+   rasterizer: Rasterizer,
+   cache: GlyphCache,
+   div_per_pixel: i64,
+}
+
+impl TextRenderer {
+   fn new(font: Font) -> Self {
+      TextRenderer {
+         rasterizer: Rasterizer::new(),
+         cache: GlyphCache::new(font),
+         div_per_pixel: 1000,
+      }
+   }
+
+   fn create_scene(&mut self, frame: &Frame) -> Scene {
+      let dpp = self.div_per_pixel as f64;
+      let margin = 10. * dpp;
+
+      let mut compiler = SceneCompiler::new();
+
+      let mut y = frame.height as f64 * dpp - margin;
+      let aligns = [Align::Left, Align::Center, Align::Right];
+
+      for (i, size) in [10., 14., 20., 28.].iter().enumerate() {
+         let mut layout = TextLayout::new(size * dpp);
+         layout.max_width = Some(frame.width as f64 * dpp - 2. * margin);
+         layout.align = aligns[i % aligns.len()];
+
+         let (_, height) = layout.measure(self.cache.font(), TEXT);
+
+         layout.add_text(&mut compiler, &mut self.cache, TEXT, margin, y, RGBA::new(235, 235, 220, 255)).unwrap();
+
+         y -= height + margin;
+      }
+
+      compiler.compile().unwrap()
+   }
+}
+
+impl Renderer for TextRenderer {
+   fn render(&mut self, frame: &mut Frame) {
+      frame.clear();
+
+      let scene = self.create_scene(frame);
+
+      self.rasterizer.render(&scene, frame, self.div_per_pixel).unwrap();
+   }
+}
+
+fn main() {
+   let path = env::args().nth(1).unwrap_or_else(|| DEFAULT_FONT.to_string());
+   let font = Font::from_file(&path).unwrap();
+
+   let mut renderer = TextRenderer::new(font);
+
+   Application::new()
+      .renderer(&mut renderer)
+      .title("Text")
+      .run();
+}
@@ -0,0 +1,30 @@
+extern crate polydraw;
+
+use std::env;
+
+use polydraw::Frame;
+use polydraw::svg::Document;
+use polydraw::png::{PngEncoder, ColorType};
+
+fn main() {
+   let args: Vec<String> = env::args().collect();
+
+   if args.len() < 3 {
+      println!("Usage: {} input.svg output.png [size]", args[0]);
+      return;
+   }
+
+   let document = Document::from_file(&args[1]).unwrap();
+
+   // Longest side of the thumbnail, keeping the document's aspect ratio
+   let size: f64 = args.get(3).map_or(128., |size| size.parse().unwrap());
+   let scale = size / document.width.max(document.height);
+
+   let width = (document.width * scale).round().max(1.) as u32;
+   let height = (document.height * scale).round().max(1.) as u32;
+
+   let mut frame = Frame::new(width, height);
+   document.render(&mut frame, 1000).unwrap();
+
+   PngEncoder::new(ColorType::Rgb).save_frame(&frame, &args[2]).unwrap();
+}
@@ -1,3 +1,6 @@
+use std::sync::OnceLock;
+
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct RGB {
    pub r: u8,
@@ -20,3 +23,155 @@ impl Default for RGB {
       RGB::new(0, 0, 0)
    }
 }
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RGBA {
+   pub r: u8,
+   pub g: u8,
+   pub b: u8,
+   pub a: u8
+}
+
+impl RGBA {
+   pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+      RGBA {
+         r,
+         g,
+         b,
+         a
+      }
+   }
+
+   #[inline]
+   pub fn rgb(&self) -> RGB {
+      RGB::new(self.r, self.g, self.b)
+   }
+
+   /// Source-over compositing of this color on top of an opaque destination.
+   #[inline]
+   pub fn over(&self, dst: &RGB) -> RGB {
+      let a = self.a as u32;
+      let inv_a = 255 - a;
+
+      RGB::new(
+         ((self.r as u32 * a + dst.r as u32 * inv_a) / 255) as u8,
+         ((self.g as u32 * a + dst.g as u32 * inv_a) / 255) as u8,
+         ((self.b as u32 * a + dst.b as u32 * inv_a) / 255) as u8,
+      )
+   }
+
+   /// Source-over compositing like `over`, but mixing in linear light.
+   #[inline]
+   pub fn over_linear(&self, dst: &RGB) -> RGB {
+      let gamma = GammaTable::get();
+
+      let a = self.a as u32;
+      let inv_a = 255 - a;
+
+      let blend = |src: u8, dst: u8| {
+         gamma.srgb((gamma.linear(src) * a + gamma.linear(dst) * inv_a + 127) / 255)
+      };
+
+      RGB::new(
+         blend(self.r, dst.r),
+         blend(self.g, dst.g),
+         blend(self.b, dst.b),
+      )
+   }
+}
+
+impl Default for RGBA {
+   fn default() -> RGBA {
+      RGBA::new(0, 0, 0, 255)
+   }
+}
+
+impl From<RGB> for RGBA {
+   fn from(color: RGB) -> RGBA {
+      RGBA::new(color.r, color.g, color.b, 255)
+   }
+}
+
+/// Largest linear light value used by `GammaTable`.
+pub const LINEAR_MAX: u32 = (1 << 12) - 1;
+
+/// Lookup tables converting 8-bit sRGB channels to 12-bit linear light and
+/// back, so colors can be mixed by area without darkening edges.
+pub struct GammaTable {
+   to_linear: Vec<u16>,
+   to_srgb: Vec<u8>,
+}
+
+impl GammaTable {
+   pub fn new() -> Self {
+      let to_linear = (0..256).map(|c| {
+         (srgb_to_linear(c as f64 / 255.) * LINEAR_MAX as f64).round() as u16
+      }).collect();
+
+      let to_srgb = (0..LINEAR_MAX + 1).map(|l| {
+         (linear_to_srgb(l as f64 / LINEAR_MAX as f64) * 255.).round() as u8
+      }).collect();
+
+      GammaTable {
+         to_linear,
+         to_srgb,
+      }
+   }
+
+   /// Shared table, computed on first use.
+   #[inline]
+   pub fn get() -> &'static GammaTable {
+      static TABLE: OnceLock<GammaTable> = OnceLock::new();
+
+      TABLE.get_or_init(GammaTable::new)
+   }
+
+   #[inline]
+   pub fn linear(&self, c: u8) -> u32 {
+      self.to_linear[c as usize] as u32
+   }
+
+   #[inline]
+   pub fn srgb(&self, l: u32) -> u8 {
+      self.to_srgb[l.min(LINEAR_MAX) as usize]
+   }
+}
+
+impl Default for GammaTable {
+   fn default() -> GammaTable {
+      GammaTable::new()
+   }
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+   if c <= 0.04045 {
+      c / 12.92
+   } else {
+      ((c + 0.055) / 1.055).powf(2.4)
+   }
+}
+
+fn linear_to_srgb(l: f64) -> f64 {
+   if l <= 0.0031308 {
+      l * 12.92
+   } else {
+      1.055 * l.powf(1. / 2.4) - 0.055
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::{RGB, RGBA, GammaTable};
+
+   #[test]
+   fn test_gamma_table() {
+      let gamma = GammaTable::get();
+
+      for c in 0..256 {
+         assert_eq!(gamma.srgb(gamma.linear(c as u8)), c as u8);
+      }
+
+      let half = RGBA::new(255, 255, 255, 128).over_linear(&RGB::new(0, 0, 0));
+      assert_eq!(half, RGB::new(188, 188, 188));
+   }
+}
@@ -18,7 +18,7 @@ impl<'a> EventLoop<'a> {
    pub fn run(&self, renderer: &mut Renderer, width: u32, height: u32) -> Result<(), RuntimeError> {
       renderer.init(width, height);
 
-      let mut frame = Frame::new(width, height);
+      let mut frame = Frame::new_gl(width, height);
 
       let mut quit = false;
 
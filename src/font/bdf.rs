@@ -0,0 +1,212 @@
+use std::str::FromStr;
+
+use error::RuntimeError;
+
+use super::bitmap::{BitmapFont, BitmapGlyph, check_metric};
+use super::reader::invalid_font;
+
+
+/// Glyphs wider or taller than this are treated as broken.
+const MAX_GLYPH_SIZE: i32 = 4096;
+
+/// Glyph being read between `STARTCHAR` and `ENDCHAR`.
+struct BdfChar {
+   code: Option<u32>,
+   glyph: BitmapGlyph,
+}
+
+/// Parses a font in the Glyph Bitmap Distribution Format. Glyphs without a
+/// standard encoding are left out.
+pub fn parse_bdf(data: &[u8]) -> Result<BitmapFont, RuntimeError> {
+   let text = String::from_utf8_lossy(data);
+   let mut lines = text.lines();
+
+   match lines.next() {
+      Some(line) if line.starts_with("STARTFONT") => {},
+      _ => return Err(invalid_font("Not a BDF font".to_string())),
+   }
+
+   let mut bounding_box = [0i32; 4];
+   let mut default_advance = None;
+   let mut ascent = None;
+   let mut descent = None;
+   let mut default_char = None;
+
+   let mut glyphs = Vec::new();
+   let mut current: Option<BdfChar> = None;
+
+   while let Some(line) = lines.next() {
+      let mut words = line.split_whitespace();
+
+      let keyword = match words.next() {
+         Some(keyword) => keyword,
+         None => continue,
+      };
+
+      let values: Vec<&str> = words.collect();
+
+      match (keyword, current.as_mut()) {
+         ("FONTBOUNDINGBOX", None) => {
+            numbers(&values, &mut bounding_box)?;
+            check_size(bounding_box[0], bounding_box[1], MAX_GLYPH_SIZE, MAX_GLYPH_SIZE)?;
+            check_metric(bounding_box[2])?;
+            check_metric(bounding_box[3])?;
+         },
+         ("FONT_ASCENT", None) => ascent = Some(check_metric(number(&values, 0)?)?),
+         ("FONT_DESCENT", None) => descent = Some(check_metric(number(&values, 0)?)?),
+         ("DEFAULT_CHAR", None) => default_char = Some(number(&values, 0)?),
+         ("DWIDTH", None) => default_advance = Some(check_metric(number(&values, 0)?)?),
+         ("STARTCHAR", None) => {
+            let [width, height, x_offset, y_offset] = bounding_box;
+
+            current = Some(BdfChar {
+               code: None,
+               glyph: BitmapGlyph {
+                  width: width.max(0) as u32,
+                  height: height.max(0) as u32,
+                  x_offset,
+                  y_offset,
+                  advance: default_advance.unwrap_or(width),
+                  bitmap: Vec::new(),
+               },
+            });
+         },
+         ("ENCODING", Some(ch)) => {
+            let code: i64 = number(&values, 0)?;
+            ch.code = if code >= 0 { Some(code as u32) } else { None };
+         },
+         ("DWIDTH", Some(ch)) => ch.glyph.advance = check_metric(number(&values, 0)?)?,
+         ("BBX", Some(ch)) => {
+            let mut bbx = [0i32; 4];
+            numbers(&values, &mut bbx)?;
+
+            // Glyphs must fit the font bounding box when there is one
+            let (max_width, max_height) = match bounding_box {
+               [0, 0, _, _] => (MAX_GLYPH_SIZE, MAX_GLYPH_SIZE),
+               [width, height, _, _] => (width, height),
+            };
+            check_size(bbx[0], bbx[1], max_width, max_height)?;
+
+            ch.glyph.width = bbx[0].max(0) as u32;
+            ch.glyph.height = bbx[1].max(0) as u32;
+            ch.glyph.x_offset = check_metric(bbx[2])?;
+            ch.glyph.y_offset = check_metric(bbx[3])?;
+         },
+         ("BITMAP", Some(ch)) => {
+            let stride = ch.glyph.stride();
+
+            for _ in 0..ch.glyph.height {
+               let row = lines.next().ok_or_else(|| invalid_font("BDF bitmap truncated".to_string()))?;
+               hex_row(row.trim(), stride, &mut ch.glyph.bitmap)?;
+            }
+         },
+         ("ENDCHAR", Some(_)) => {
+            if let Some(ch) = current.take() {
+               if let Some(code) = ch.code {
+                  glyphs.push((code, ch.glyph));
+               }
+            }
+         },
+         ("ENDFONT", None) => break,
+         _ => {},
+      }
+   }
+
+   let ascent = ascent.unwrap_or(bounding_box[1] + bounding_box[3]);
+   let descent = descent.unwrap_or(-bounding_box[3]);
+
+   let mut font = BitmapFont::new(ascent, descent);
+
+   for (code, glyph) in glyphs {
+      font.add_glyph(code, glyph);
+   }
+
+   if let Some(code) = default_char {
+      font.set_default(code);
+   }
+
+   Ok(font)
+}
+
+fn number<T: FromStr>(values: &[&str], index: usize) -> Result<T, RuntimeError> {
+   match values.get(index).and_then(|value| value.parse().ok()) {
+      Some(value) => Ok(value),
+      None => Err(invalid_font(format!("Invalid BDF value {:?}", values.get(index)))),
+   }
+}
+
+fn check_size(width: i32, height: i32, max_width: i32, max_height: i32) -> Result<(), RuntimeError> {
+   if width > max_width || height > max_height {
+      return Err(invalid_font(format!("BDF glyph size {}x{} exceeds {}x{}", width, height, max_width, max_height)));
+   }
+   Ok(())
+}
+
+fn numbers(values: &[&str], target: &mut [i32]) -> Result<(), RuntimeError> {
+   for (i, value) in target.iter_mut().enumerate() {
+      *value = number(values, i)?;
+   }
+   Ok(())
+}
+
+/// Appends `stride` bytes of a hex bitmap row, ignoring extra padding
+/// digits and filling short rows with zeros.
+fn hex_row(row: &str, stride: usize, bitmap: &mut Vec<u8>) -> Result<(), RuntimeError> {
+   let digits = row.as_bytes();
+
+   for i in 0..stride {
+      let byte = match digits.get(2 * i..2 * i + 2) {
+         Some(pair) => {
+            let pair = String::from_utf8_lossy(pair);
+            u8::from_str_radix(&pair, 16).map_err(|_| invalid_font(format!("Invalid BDF bitmap row {}", row)))?
+         },
+         None => 0,
+      };
+
+      bitmap.push(byte);
+   }
+
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use font::bitmap::tests::TEST_BDF;
+
+   use super::parse_bdf;
+
+   #[test]
+   fn test_bdf_malformed() {
+      assert!(parse_bdf(TEST_BDF.as_bytes()).is_ok());
+      assert!(parse_bdf(b"STARTCHAR A\nENDCHAR\n").is_err());
+
+      let invalid = [
+         // Bitmap cut short by the end of the data or of the glyph
+         TEST_BDF[..TEST_BDF.find("80\n").unwrap() + 3].to_string(),
+         TEST_BDF.replace("80\n80\nENDCHAR", "80\nENDCHAR"),
+         // Bad hex row
+         TEST_BDF.replace("A0\nE0", "A0\nG0"),
+         // Bad number
+         TEST_BDF.replace("BBX 3 4 0 0", "BBX 3 x 0 0"),
+         // Larger than the font bounding box or any sane glyph
+         TEST_BDF.replace("BBX 3 4 0 0", "BBX 3 2000000000 0 0"),
+         TEST_BDF.replace("BBX 3 4 0 0", "BBX 5 4 0 0"),
+         TEST_BDF.replace("FONTBOUNDINGBOX 4 6 0 -2", "FONTBOUNDINGBOX 2000000000 6 0 -2"),
+         // Offsets, advances and extents that overflow when drawn
+         TEST_BDF.replace("BBX 3 4 0 0", "BBX 3 4 2147483647 0"),
+         TEST_BDF.replace("BBX 3 4 0 0", "BBX 3 4 0 -2147483648"),
+         TEST_BDF.replace("FONTBOUNDINGBOX 4 6 0 -2", "FONTBOUNDINGBOX 4 6 0 -40000"),
+         TEST_BDF.replace("DWIDTH 4 0", "DWIDTH 2147483647 0"),
+         TEST_BDF.replace("FONT_ASCENT 5", "FONT_ASCENT 2147483647"),
+      ];
+
+      for data in &invalid {
+         assert!(parse_bdf(data.as_bytes()).is_err(), "{}", data);
+      }
+
+      // Without a font bounding box only the sane limit applies
+      let unbounded = TEST_BDF.replace("FONTBOUNDINGBOX 4 6 0 -2\n", "");
+      assert!(parse_bdf(unbounded.replace("BBX 3 4 0 0", "BBX 40 4 0 0").as_bytes()).is_ok());
+      assert!(parse_bdf(unbounded.replace("BBX 3 4 0 0", "BBX 3 5000 0 0").as_bytes()).is_err());
+   }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+
+use error::{RuntimeError, ErrorKind};
+use frame::Frame;
+use draw::RGBA;
+
+use super::bdf::parse_bdf;
+use super::pcf::{parse_pcf, PCF_MAGIC};
+use super::reader::invalid_font;
+
+
+/// Glyph offsets, advances and font extents beyond this many pixels are
+/// treated as broken, PCF storing glyph metrics in 16 bits.
+pub const MAX_METRIC: i32 = i16::MAX as i32;
+
+/// One bit per pixel glyph image with rows from top to bottom, each row
+/// starting on a byte boundary with the leftmost pixel in the most
+/// significant bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitmapGlyph {
+   pub width: u32,
+   pub height: u32,
+   /// Position of the bottom left corner relative to the origin, y up.
+   pub x_offset: i32,
+   pub y_offset: i32,
+   pub advance: i32,
+   pub bitmap: Vec<u8>,
+}
+
+impl BitmapGlyph {
+   #[inline]
+   pub fn stride(&self) -> usize {
+      (self.width as usize).div_ceil(8)
+   }
+
+   /// Whether pixel `(x, y)` is set, with `y` counting rows from the top.
+   #[inline]
+   pub fn pixel(&self, x: u32, y: u32) -> bool {
+      let byte = self.bitmap.get(y as usize * self.stride() + x as usize / 8).cloned().unwrap_or(0);
+      byte & (0x80 >> (x % 8)) != 0
+   }
+}
+
+/// BDF or PCF bitmap font drawn pixel by pixel without anti-aliasing.
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+   glyphs: Vec<BitmapGlyph>,
+   index: HashMap<u32, usize>,
+   default_glyph: usize,
+   pub ascent: i32,
+   pub descent: i32,
+}
+
+impl BitmapFont {
+   #[inline]
+   pub fn new(ascent: i32, descent: i32) -> Self {
+      BitmapFont {
+         glyphs: Vec::new(),
+         index: HashMap::new(),
+         default_glyph: usize::MAX,
+         ascent,
+         descent,
+      }
+   }
+
+   /// Loads a BDF or PCF font, telling them apart by the PCF signature.
+   pub fn from_data(data: &[u8]) -> Result<Self, RuntimeError> {
+      if data.starts_with(PCF_MAGIC) {
+         parse_pcf(data)
+      } else {
+         parse_bdf(data)
+      }
+   }
+
+   pub fn from_file(path: &str) -> Result<Self, RuntimeError> {
+      match fs::read(path) {
+         Ok(data) => BitmapFont::from_data(&data),
+         Err(err) => Err(RuntimeError::new(
+            ErrorKind::Other,
+            format!("Cannot read font {}: {}", path, err)
+         )),
+      }
+   }
+
+   pub fn add_glyph(&mut self, code: u32, glyph: BitmapGlyph) {
+      self.index.insert(code, self.glyphs.len());
+      self.glyphs.push(glyph);
+   }
+
+   /// Glyph drawn for characters the font does not have, none by default.
+   pub fn set_default(&mut self, code: u32) {
+      self.default_glyph = self.index.get(&code).cloned().unwrap_or(usize::MAX);
+   }
+
+   #[inline]
+   pub fn len(&self) -> usize {
+      self.glyphs.len()
+   }
+
+   #[inline]
+   pub fn is_empty(&self) -> bool {
+      self.glyphs.is_empty()
+   }
+
+   /// Glyph of `ch`, or the default glyph when missing.
+   pub fn glyph(&self, ch: char) -> Option<&BitmapGlyph> {
+      match self.index.get(&(ch as u32)) {
+         Some(index) => Some(&self.glyphs[*index]),
+         None => self.glyphs.get(self.default_glyph),
+      }
+   }
+
+   #[inline]
+   pub fn line_height(&self) -> i32 {
+      self.ascent.saturating_add(self.descent)
+   }
+
+   /// Advance of the longest line of `text` in pixels.
+   pub fn text_width(&self, text: &str) -> i32 {
+      text.split('\n')
+         .map(|line| line.chars().filter_map(|ch| self.glyph(ch)).fold(0, |width: i32, glyph| width.saturating_add(glyph.advance)))
+         .max()
+         .unwrap_or(0)
+   }
+
+   /// Draws `text` with the first baseline starting at pixel `(x, y)`.
+   /// Newlines move one line height down. Returns the pen position after
+   /// the last glyph.
+   pub fn draw_text(&self, frame: &mut Frame, text: &str, x: i32, y: i32, color: &RGBA) -> (i32, i32) {
+      let (mut pen_x, mut pen_y) = (x, y);
+
+      for ch in text.chars() {
+         if ch == '\n' {
+            pen_x = x;
+            pen_y = pen_y.saturating_sub(self.line_height());
+            continue;
+         }
+
+         if let Some(glyph) = self.glyph(ch) {
+            self.draw_glyph(frame, glyph, pen_x, pen_y, color);
+            pen_x = pen_x.saturating_add(glyph.advance);
+         }
+      }
+
+      (pen_x, pen_y)
+   }
+
+   pub fn draw_glyph(&self, frame: &mut Frame, glyph: &BitmapGlyph, x: i32, y: i32, color: &RGBA) {
+      // Pixels pushed past the i32 range land outside the frame either way
+      let left = x.saturating_add(glyph.x_offset);
+      let top = y.saturating_add(glyph.y_offset).saturating_add(glyph.height as i32 - 1);
+
+      for row in 0..glyph.height {
+         for column in 0..glyph.width {
+            if glyph.pixel(column, row) {
+               frame.blend_pixel(left.saturating_add(column as i32), top.saturating_sub(row as i32), color);
+            }
+         }
+      }
+   }
+}
+
+/// Rejects a glyph offset or advance, or a font extent, beyond `MAX_METRIC`.
+pub fn check_metric(value: i32) -> Result<i32, RuntimeError> {
+   if value.unsigned_abs() > MAX_METRIC as u32 {
+      return Err(invalid_font(format!("Bitmap font metric {} exceeds {}", value, MAX_METRIC)));
+   }
+   Ok(value)
+}
+
+#[cfg(test)]
+pub mod tests {
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+
+   use super::BitmapFont;
+
+   /// Font with a 3x4 'A' above the baseline, a 1x2 'j' dipping below it
+   /// and '?' as the default glyph.
+   pub const TEST_BDF: &str = "STARTFONT 2.1
+FONT -test-fixed
+SIZE 6 75 75
+FONTBOUNDINGBOX 4 6 0 -2
+STARTPROPERTIES 3
+FONT_ASCENT 5
+FONT_DESCENT 2
+DEFAULT_CHAR 63
+ENDPROPERTIES
+CHARS 3
+STARTCHAR A
+ENCODING 65
+SWIDTH 666 0
+DWIDTH 4 0
+BBX 3 4 0 0
+BITMAP
+40
+A0
+E0
+A0
+ENDCHAR
+STARTCHAR j
+ENCODING 106
+DWIDTH 2 0
+BBX 1 2 1 -2
+BITMAP
+80
+80
+ENDCHAR
+STARTCHAR question
+ENCODING 63
+DWIDTH 4 0
+BBX 2 1 1 3
+BITMAP
+C0
+ENDCHAR
+ENDFONT
+";
+
+   #[test]
+   fn test_draw_bitmap_text() {
+      let font = BitmapFont::from_data(TEST_BDF.as_bytes()).unwrap();
+
+      assert_eq!(font.len(), 3);
+      assert_eq!((font.ascent, font.descent), (5, 2));
+      assert_eq!(font.text_width("Aj\nAAA"), 12);
+      assert_eq!(font.glyph('x'), font.glyph('?'));
+
+      let mut frame = Frame::new(12, 8);
+      let end = font.draw_text(&mut frame, "Aj", 1, 3, &RGBA::new(255, 0, 0, 255));
+      assert_eq!(end, (7, 3));
+
+      let on = RGB::new(255, 0, 0);
+      let off = RGB::new(0, 0, 0);
+
+      // Apex and crossbar of the 'A', then the descender of the 'j'
+      assert_eq!(frame.get_pixel(2, 6), on);
+      assert_eq!(frame.get_pixel(1, 6), off);
+      assert_eq!(frame.get_pixel(1, 4), on);
+      assert_eq!(frame.get_pixel(2, 4), on);
+      assert_eq!(frame.get_pixel(2, 3), off);
+      assert_eq!(frame.get_pixel(6, 2), on);
+      assert_eq!(frame.get_pixel(6, 1), on);
+      assert_eq!(frame.get_pixel(6, 3), off);
+
+      // Pens at the ends of the i32 range stop there instead of overflowing
+      let end = font.draw_text(&mut frame, "AA\nj", i32::MAX - 5, i32::MIN + 3, &RGBA::new(0, 0, 255, 255));
+      assert_eq!(end, (i32::MAX - 3, i32::MIN));
+      assert_eq!(font.draw_text(&mut frame, "AAA", i32::MAX - 5, 0, &RGBA::new(0, 0, 255, 255)), (i32::MAX, 0));
+      assert_eq!(frame.get_pixel(2, 6), on);
+   }
+}
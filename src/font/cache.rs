@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use error::RuntimeError;
+use geom::affine::Affine;
+use raster::Path;
+
+use super::Font;
+
+
+/// Default number of paths a `GlyphCache` keeps.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Glyph paths of one font, kept per glyph and size so that repeated text
+/// skips outline parsing and scaling. Paths are stored with the glyph origin
+/// at `(0, 0)`. Once `capacity` paths are cached the cache is cleared
+/// before adding another one.
+#[derive(Debug, Clone)]
+pub struct GlyphCache {
+   font: Font,
+   paths: HashMap<(u16, u64), Path>,
+   capacity: usize,
+}
+
+impl GlyphCache {
+   #[inline]
+   pub fn new(font: Font) -> Self {
+      GlyphCache::with_capacity(font, DEFAULT_CAPACITY)
+   }
+
+   /// Cache keeping at most `capacity` paths, but at least one.
+   #[inline]
+   pub fn with_capacity(font: Font, capacity: usize) -> Self {
+      GlyphCache {
+         font,
+         paths: HashMap::new(),
+         capacity: capacity.max(1),
+      }
+   }
+
+   #[inline]
+   pub fn font(&self) -> &Font {
+      &self.font
+   }
+
+   /// Path of `glyph` at `size` with its origin at `(0, 0)`.
+   pub fn glyph_path(&mut self, glyph: u16, size: f64) -> Result<&Path, RuntimeError> {
+      let key = (glyph, size.to_bits());
+
+      if !self.paths.contains_key(&key) {
+         if self.paths.len() >= self.capacity {
+            self.paths.clear();
+         }
+
+         let path = self.font.glyph_path(glyph, size, 0., 0.)?;
+         self.paths.insert(key, path);
+      }
+
+      Ok(&self.paths[&key])
+   }
+
+   /// Appends the contours of `glyph` at `size` placed with its origin at
+   /// `(x, y)`, rounded to the integer grid.
+   pub fn append_glyph(
+      &mut self, path: &mut Path, glyph: u16, size: f64, x: f64, y: f64
+   ) -> Result<(), RuntimeError> {
+      let transform = Affine::translate(x.round(), y.round());
+
+      let glyph_path = self.glyph_path(glyph, size)?;
+      path.contours.extend(glyph_path.contours.iter().map(|contour| contour.transform(&transform)));
+
+      Ok(())
+   }
+
+   #[inline]
+   pub fn len(&self) -> usize {
+      self.paths.len()
+   }
+
+   #[inline]
+   pub fn capacity(&self) -> usize {
+      self.capacity
+   }
+
+   #[inline]
+   pub fn is_empty(&self) -> bool {
+      self.paths.is_empty()
+   }
+
+   #[inline]
+   pub fn clear(&mut self) {
+      self.paths.clear();
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use font::tests::test_font;
+
+   use super::GlyphCache;
+
+   #[test]
+   fn test_glyph_cache() {
+      let mut cache = GlyphCache::new(test_font());
+      assert!(cache.is_empty());
+
+      let path = cache.glyph_path(1, 1000.).unwrap().clone();
+      assert_eq!(path.contours.len(), 1);
+
+      // Hits return the stored path
+      assert_eq!(cache.glyph_path(1, 1000.).unwrap().contours[0].start, path.contours[0].start);
+      assert_eq!(cache.len(), 1);
+
+      cache.glyph_path(1, 500.).unwrap();
+      cache.glyph_path(2, 1000.).unwrap();
+      assert_eq!(cache.len(), 3);
+
+      cache.clear();
+      assert!(cache.is_empty());
+
+      let mut cache = GlyphCache::with_capacity(test_font(), 2);
+      for size in 1..10 {
+         cache.glyph_path(1, size as f64 * 100.).unwrap();
+         assert!(cache.len() <= 2);
+      }
+      assert_eq!(cache.glyph_path(1, 900.).unwrap().contours.len(), 1);
+      assert!(cache.glyph_path(3, 1000.).is_err());
+   }
+}
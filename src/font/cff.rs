@@ -0,0 +1,799 @@
+use std::ops::Range;
+
+use error::RuntimeError;
+
+use super::outline::Outline;
+use super::reader::{Reader, invalid_font};
+
+
+const OP_CHAR_STRINGS: u16 = 17;
+const OP_PRIVATE: u16 = 18;
+const OP_SUBRS: u16 = 19;
+const OP_CHARSTRING_TYPE: u16 = 1206;
+const OP_FD_ARRAY: u16 = 1236;
+const OP_FD_SELECT: u16 = 1237;
+
+/// Type 2 charstrings may nest subroutine calls up to this depth.
+const MAX_SUBR_DEPTH: usize = 10;
+
+/// Operand stack limit of Type 2 charstrings.
+const MAX_STACK: usize = 48;
+
+/// Glyphs whose charstrings take more operands and operators than this,
+/// counting every subroutine call, are treated as broken.
+const MAX_OPERATIONS: usize = 65536;
+
+/// Cubic outlines from a `CFF ` table, including CID-keyed fonts with
+/// per glyph local subroutines.
+#[derive(Debug, Clone)]
+pub struct Cff {
+   table: Range<usize>,
+   char_strings: Vec<Range<usize>>,
+   global_subrs: Vec<Range<usize>>,
+   /// Local subroutines of each font DICT, a single entry unless CID-keyed.
+   local_subrs: Vec<Vec<Range<usize>>>,
+   /// Font DICT index of each glyph, empty unless CID-keyed.
+   fd_select: Vec<u8>,
+}
+
+impl Cff {
+   /// Parses the CFF table at `table` within `data`. Ranges kept by the
+   /// parser are relative to the table start.
+   pub fn new(data: &[u8], table: Range<usize>) -> Result<Self, RuntimeError> {
+      let cff = data.get(table.clone()).ok_or_else(|| invalid_font("CFF table out of range".to_string()))?;
+
+      let mut reader = Reader::new(cff);
+
+      let major = reader.u8()?;
+      if major != 1 {
+         return Err(invalid_font(format!("Unsupported CFF version {}", major)));
+      }
+
+      reader.skip(1);
+      let header_size = reader.u8()? as usize;
+
+      let (_, end) = read_index(cff, header_size)?;
+      let (top_dicts, end) = read_index(cff, end)?;
+      let (_, end) = read_index(cff, end)?;
+      let (global_subrs, _) = read_index(cff, end)?;
+
+      let top = match top_dicts.first() {
+         Some(range) => read_dict(&cff[range.clone()])?,
+         None => return Err(invalid_font("CFF without fonts".to_string())),
+      };
+
+      if let Some(operands) = dict_entry(&top, OP_CHARSTRING_TYPE) {
+         if operands.first() != Some(&2.) {
+            return Err(invalid_font("Only Type 2 charstrings are supported".to_string()));
+         }
+      }
+
+      let char_strings = match dict_entry(&top, OP_CHAR_STRINGS) {
+         Some(operands) if !operands.is_empty() => read_index(cff, dict_offset(operands[0])?)?.0,
+         _ => return Err(invalid_font("CFF without charstrings".to_string())),
+      };
+
+      let mut local_subrs = Vec::new();
+      let mut fd_select = Vec::new();
+
+      match dict_entry(&top, OP_FD_ARRAY) {
+         Some(operands) if !operands.is_empty() => {
+            for range in read_index(cff, dict_offset(operands[0])?)?.0 {
+               let font_dict = read_dict(&cff[range])?;
+               local_subrs.push(read_private(cff, &font_dict)?);
+            }
+
+            if let Some(operands) = dict_entry(&top, OP_FD_SELECT) {
+               if let Some(offset) = operands.first() {
+                  fd_select = read_fd_select(cff, dict_offset(*offset)?, char_strings.len())?;
+               }
+            }
+         },
+         _ => local_subrs.push(read_private(cff, &top)?),
+      }
+
+      Ok(Cff {
+         table,
+         char_strings,
+         global_subrs,
+         local_subrs,
+         fd_select,
+      })
+   }
+
+   #[inline]
+   pub fn glyph_count(&self) -> usize {
+      self.char_strings.len()
+   }
+
+   pub fn outline(&self, data: &[u8], glyph: u16) -> Result<Outline, RuntimeError> {
+      let cff = &data[self.table.clone()];
+
+      let code = match self.char_strings.get(glyph as usize) {
+         Some(range) => &cff[range.clone()],
+         None => return Err(invalid_font(format!("Glyph {} out of range", glyph))),
+      };
+
+      let fd = self.fd_select.get(glyph as usize).cloned().unwrap_or(0) as usize;
+      let local_subrs = match self.local_subrs.get(fd) {
+         Some(subrs) => subrs,
+         None => return Err(invalid_font(format!("Glyph {} has invalid font DICT", glyph))),
+      };
+
+      let mut interpreter = Interpreter::new(cff, &self.global_subrs, local_subrs);
+      interpreter.run(code, 0)?;
+      interpreter.close_contour();
+
+      Ok(interpreter.outline)
+   }
+}
+
+/// Reads an INDEX at `pos`, returning the ranges of its entries and the
+/// position after it.
+fn read_index(cff: &[u8], pos: usize) -> Result<(Vec<Range<usize>>, usize), RuntimeError> {
+   let mut reader = Reader::at(cff, pos);
+
+   let count = reader.u16()? as usize;
+   if count == 0 {
+      return Ok((Vec::new(), reader.pos()));
+   }
+
+   let offset_size = reader.u8()? as usize;
+   if offset_size == 0 || offset_size > 4 {
+      return Err(invalid_font("Invalid CFF offset size".to_string()));
+   }
+
+   let mut offsets = Vec::with_capacity(count + 1);
+   for _ in 0..count + 1 {
+      offsets.push(reader.uint(offset_size)? as usize);
+   }
+
+   // Offsets count from the byte before the data
+   let base = reader.pos() - 1;
+
+   let mut ranges = Vec::with_capacity(count);
+   for pair in offsets.windows(2) {
+      if pair[0] == 0 || pair[0] > pair[1] || base + pair[1] > cff.len() {
+         return Err(invalid_font("Invalid CFF INDEX".to_string()));
+      }
+
+      ranges.push(base + pair[0]..base + pair[1]);
+   }
+
+   Ok((ranges, base + offsets[count]))
+}
+
+/// DICT entries as operator and operands, two byte operators are stored as
+/// `1200 + second byte`.
+fn read_dict(dict: &[u8]) -> Result<Vec<(u16, Vec<f64>)>, RuntimeError> {
+   let mut reader = Reader::new(dict);
+
+   let mut entries = Vec::new();
+   let mut operands = Vec::new();
+
+   while !reader.at_end() {
+      let b0 = reader.u8()?;
+
+      match b0 {
+         0..=21 => {
+            let operator = if b0 == 12 {
+               1200 + reader.u8()? as u16
+            } else {
+               b0 as u16
+            };
+
+            entries.push((operator, operands));
+            operands = Vec::new();
+         },
+         28 => operands.push(reader.i16()? as f64),
+         29 => operands.push(reader.i32()? as f64),
+         30 => operands.push(read_real(&mut reader)?),
+         32..=246 => operands.push(b0 as f64 - 139.),
+         247..=250 => operands.push(((b0 as f64 - 247.) * 256.) + reader.u8()? as f64 + 108.),
+         251..=254 => operands.push(-((b0 as f64 - 251.) * 256.) - reader.u8()? as f64 - 108.),
+         _ => return Err(invalid_font(format!("Invalid CFF DICT byte {}", b0))),
+      }
+   }
+
+   Ok(entries)
+}
+
+#[inline]
+fn dict_entry(dict: &[(u16, Vec<f64>)], operator: u16) -> Option<&Vec<f64>> {
+   dict.iter().find(|entry| entry.0 == operator).map(|entry| &entry.1)
+}
+
+/// Real number operand stored as a nibble string.
+fn read_real(reader: &mut Reader) -> Result<f64, RuntimeError> {
+   let mut text = String::new();
+
+   loop {
+      let byte = reader.u8()?;
+
+      for nibble in [byte >> 4, byte & 0xf].iter() {
+         match *nibble {
+            0..=9 => text.push((b'0' + nibble) as char),
+            0xa => text.push('.'),
+            0xb => text.push('e'),
+            0xc => text.push_str("e-"),
+            0xe => text.push('-'),
+            0xf => return text.parse().map_err(|_| invalid_font("Invalid CFF real".to_string())),
+            _ => {},
+         }
+      }
+   }
+}
+
+/// Local subroutines referenced from the Private DICT of `dict`.
+fn read_private(cff: &[u8], dict: &[(u16, Vec<f64>)]) -> Result<Vec<Range<usize>>, RuntimeError> {
+   let (size, offset) = match dict_entry(dict, OP_PRIVATE) {
+      Some(operands) if operands.len() >= 2 => (dict_offset(operands[0])?, dict_offset(operands[1])?),
+      _ => return Ok(Vec::new()),
+   };
+
+   let private = match offset.checked_add(size).and_then(|end| cff.get(offset..end)) {
+      Some(private) => read_dict(private)?,
+      None => return Err(invalid_font("Private DICT out of range".to_string())),
+   };
+
+   match dict_entry(&private, OP_SUBRS) {
+      Some(operands) if !operands.is_empty() => {
+         let subrs = offset.checked_add(dict_offset(operands[0])?).ok_or_else(|| {
+            invalid_font("Local subroutines out of range".to_string())
+         })?;
+         Ok(read_index(cff, subrs)?.0)
+      },
+      _ => Ok(Vec::new()),
+   }
+}
+
+/// DICT operand used as an offset or size, which must be a whole number
+/// within the range of table offsets.
+fn dict_offset(value: f64) -> Result<usize, RuntimeError> {
+   if !(value >= 0. && value <= u32::MAX as f64) || value.fract() != 0. {
+      return Err(invalid_font(format!("Invalid CFF offset {}", value)));
+   }
+
+   Ok(value as usize)
+}
+
+fn read_fd_select(cff: &[u8], pos: usize, glyphs: usize) -> Result<Vec<u8>, RuntimeError> {
+   let mut reader = Reader::at(cff, pos);
+
+   match reader.u8()? {
+      0 => Ok(reader.bytes(glyphs)?.to_vec()),
+      3 => {
+         let mut fd_select = vec![0; glyphs];
+
+         let ranges = reader.u16()?;
+         let mut first = reader.u16()? as usize;
+
+         for _ in 0..ranges {
+            let fd = reader.u8()?;
+            let next = reader.u16()? as usize;
+
+            for entry in fd_select.iter_mut().take(next).skip(first) {
+               *entry = fd;
+            }
+
+            first = next;
+         }
+
+         Ok(fd_select)
+      },
+      format => Err(invalid_font(format!("Unsupported FDSelect format {}", format))),
+   }
+}
+
+#[inline]
+fn subr_bias(count: usize) -> usize {
+   if count < 1240 {
+      107
+   } else if count < 33900 {
+      1131
+   } else {
+      32768
+   }
+}
+
+/// Type 2 charstring interpreter. Hints are skipped, only the outline is
+/// kept.
+struct Interpreter<'a> {
+   cff: &'a [u8],
+   global_subrs: &'a [Range<usize>],
+   local_subrs: &'a [Range<usize>],
+   stack: Vec<f64>,
+   x: f64,
+   y: f64,
+   stems: usize,
+   width_parsed: bool,
+   open: bool,
+   ended: bool,
+   operations: usize,
+   outline: Outline,
+}
+
+impl<'a> Interpreter<'a> {
+   fn new(cff: &'a [u8], global_subrs: &'a [Range<usize>], local_subrs: &'a [Range<usize>]) -> Self {
+      Interpreter {
+         cff,
+         global_subrs,
+         local_subrs,
+         stack: Vec::new(),
+         x: 0.,
+         y: 0.,
+         stems: 0,
+         width_parsed: false,
+         open: false,
+         ended: false,
+         operations: 0,
+         outline: Outline::new(),
+      }
+   }
+
+   fn run(&mut self, code: &[u8], depth: usize) -> Result<(), RuntimeError> {
+      if depth > MAX_SUBR_DEPTH {
+         return Err(invalid_font("Charstring subroutines nested too deep".to_string()));
+      }
+
+      let mut reader = Reader::new(code);
+
+      while !reader.at_end() && !self.ended {
+         self.operations += 1;
+         if self.operations > MAX_OPERATIONS {
+            return Err(invalid_font("Charstring takes too many operations".to_string()));
+         }
+
+         let b0 = reader.u8()?;
+
+         match b0 {
+            28 => self.push(reader.i16()? as f64)?,
+            32..=246 => self.push(b0 as f64 - 139.)?,
+            247..=250 => {
+               let value = (b0 as f64 - 247.) * 256. + reader.u8()? as f64 + 108.;
+               self.push(value)?;
+            },
+            251..=254 => {
+               let value = -(b0 as f64 - 251.) * 256. - reader.u8()? as f64 - 108.;
+               self.push(value)?;
+            },
+            255 => self.push(reader.i32()? as f64 / 65536.)?,
+
+            // hstem, vstem, hstemhm, vstemhm
+            1 | 3 | 18 | 23 => self.stem_hints(),
+
+            // hintmask, cntrmask
+            19 | 20 => {
+               self.stem_hints();
+               reader.skip(self.stems.div_ceil(8));
+            },
+
+            // rmoveto
+            21 => {
+               self.parse_width(2);
+               let dx = self.arg(0)?;
+               let dy = self.arg(1)?;
+               self.move_to(dx, dy);
+            },
+
+            // hmoveto
+            22 => {
+               self.parse_width(1);
+               let dx = self.arg(0)?;
+               self.move_to(dx, 0.);
+            },
+
+            // vmoveto
+            4 => {
+               self.parse_width(1);
+               let dy = self.arg(0)?;
+               self.move_to(0., dy);
+            },
+
+            // rlineto
+            5 => {
+               let mut i = 0;
+               while i + 2 <= self.stack.len() {
+                  let (dx, dy) = (self.stack[i], self.stack[i + 1]);
+                  self.line_to(dx, dy);
+                  i += 2;
+               }
+            },
+
+            // hlineto, vlineto
+            6 | 7 => {
+               let mut horizontal = b0 == 6;
+               for delta in self.stack.clone() {
+                  if horizontal {
+                     self.line_to(delta, 0.);
+                  } else {
+                     self.line_to(0., delta);
+                  }
+                  horizontal = !horizontal;
+               }
+            },
+
+            // rrcurveto
+            8 => {
+               let mut i = 0;
+               while i + 6 <= self.stack.len() {
+                  self.curve_from_stack(i);
+                  i += 6;
+               }
+            },
+
+            // rcurveline
+            24 => {
+               let mut i = 0;
+               while i + 8 <= self.stack.len() {
+                  self.curve_from_stack(i);
+                  i += 6;
+               }
+               let dx = self.arg(i)?;
+               let dy = self.arg(i + 1)?;
+               self.line_to(dx, dy);
+            },
+
+            // rlinecurve
+            25 => {
+               let mut i = 0;
+               while i + 8 <= self.stack.len() {
+                  let (dx, dy) = (self.stack[i], self.stack[i + 1]);
+                  self.line_to(dx, dy);
+                  i += 2;
+               }
+               if i + 6 > self.stack.len() {
+                  return Err(stack_underflow());
+               }
+               self.curve_from_stack(i);
+            },
+
+            // vvcurveto
+            26 => {
+               let mut i = self.stack.len() % 2;
+               let mut dx1 = if i == 1 { self.stack[0] } else { 0. };
+               while i + 4 <= self.stack.len() {
+                  let s = &self.stack[i..i + 4];
+                  let (dya, dxb, dyb, dyc) = (s[0], s[1], s[2], s[3]);
+                  self.curve_to(dx1, dya, dxb, dyb, 0., dyc);
+                  dx1 = 0.;
+                  i += 4;
+               }
+            },
+
+            // hhcurveto
+            27 => {
+               let mut i = self.stack.len() % 2;
+               let mut dy1 = if i == 1 { self.stack[0] } else { 0. };
+               while i + 4 <= self.stack.len() {
+                  let s = &self.stack[i..i + 4];
+                  let (dxa, dxb, dyb, dxc) = (s[0], s[1], s[2], s[3]);
+                  self.curve_to(dxa, dy1, dxb, dyb, dxc, 0.);
+                  dy1 = 0.;
+                  i += 4;
+               }
+            },
+
+            // vhcurveto, hvcurveto
+            30 | 31 => {
+               let mut horizontal = b0 == 31;
+               let mut i = 0;
+               while i + 4 <= self.stack.len() {
+                  let s = &self.stack[i..i + 4];
+                  let (a, b, c, d) = (s[0], s[1], s[2], s[3]);
+                  let last = if self.stack.len() - i == 5 { self.stack[i + 4] } else { 0. };
+
+                  if horizontal {
+                     self.curve_to(a, 0., b, c, last, d);
+                  } else {
+                     self.curve_to(0., a, b, c, d, last);
+                  }
+
+                  horizontal = !horizontal;
+                  i += 4;
+               }
+            },
+
+            // callsubr, callgsubr
+            10 | 29 => {
+               let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+               let index = self.stack.pop().ok_or_else(stack_underflow)? as i64 + subr_bias(subrs.len()) as i64;
+
+               let range = match subrs.get(index as usize) {
+                  Some(range) if index >= 0 => range.clone(),
+                  _ => return Err(invalid_font(format!("Invalid charstring subroutine {}", index))),
+               };
+
+               let cff = self.cff;
+               self.run(&cff[range], depth + 1)?;
+               continue;
+            },
+
+            // return
+            11 => return Ok(()),
+
+            // endchar
+            14 => {
+               self.parse_width(0);
+               self.close_contour();
+               self.ended = true;
+            },
+
+            12 => {
+               let b1 = reader.u8()?;
+               self.flex(b1)?;
+            },
+
+            _ => {},
+         }
+
+         // Every operator except the subroutine calls clears the stack
+         if b0 < 32 && b0 != 28 {
+            self.stack.clear();
+         }
+      }
+
+      Ok(())
+   }
+
+   #[inline]
+   fn push(&mut self, value: f64) -> Result<(), RuntimeError> {
+      if self.stack.len() >= MAX_STACK {
+         return Err(invalid_font("Charstring stack overflow".to_string()));
+      }
+
+      self.stack.push(value);
+      Ok(())
+   }
+
+   #[inline]
+   fn arg(&self, i: usize) -> Result<f64, RuntimeError> {
+      self.stack.get(i).cloned().ok_or_else(stack_underflow)
+   }
+
+   /// Drops the advance width preceding the operands of the first stack
+   /// clearing operator, found by the operand count being larger than
+   /// `expected`.
+   fn parse_width(&mut self, expected: usize) {
+      if !self.width_parsed {
+         if self.stack.len() > expected {
+            self.stack.remove(0);
+         }
+         self.width_parsed = true;
+      }
+   }
+
+   fn stem_hints(&mut self) {
+      if !self.width_parsed {
+         let odd = self.stack.len() % 2;
+         self.parse_width(self.stack.len() - odd);
+      }
+
+      self.stems += self.stack.len() / 2;
+   }
+
+   fn close_contour(&mut self) {
+      if self.open {
+         self.outline.close();
+         self.open = false;
+      }
+   }
+
+   fn move_to(&mut self, dx: f64, dy: f64) {
+      self.close_contour();
+
+      self.x += dx;
+      self.y += dy;
+      self.outline.move_to(self.x, self.y);
+
+      self.open = true;
+   }
+
+   fn line_to(&mut self, dx: f64, dy: f64) {
+      self.x += dx;
+      self.y += dy;
+      self.outline.line_to(self.x, self.y);
+   }
+
+   /// Cubic curve given as three deltas, each relative to the point before.
+   fn curve_to(&mut self, dx1: f64, dy1: f64, dx2: f64, dy2: f64, dx3: f64, dy3: f64) {
+      let (c1x, c1y) = (self.x + dx1, self.y + dy1);
+      let (c2x, c2y) = (c1x + dx2, c1y + dy2);
+
+      self.x = c2x + dx3;
+      self.y = c2y + dy3;
+
+      self.outline.cubic_to(c1x, c1y, c2x, c2y, self.x, self.y);
+   }
+
+   #[inline]
+   fn curve_from_stack(&mut self, i: usize) {
+      let s = &self.stack[i..i + 6];
+      let (dx1, dy1, dx2, dy2, dx3, dy3) = (s[0], s[1], s[2], s[3], s[4], s[5]);
+      self.curve_to(dx1, dy1, dx2, dy2, dx3, dy3);
+   }
+
+   /// Flex operators drawing two joined curves. Other escaped operators are
+   /// deprecated arithmetic and ignored.
+   fn flex(&mut self, operator: u8) -> Result<(), RuntimeError> {
+      let required = match operator {
+         34 => 7,
+         35 => 13,
+         36 => 9,
+         37 => 11,
+         _ => return Ok(()),
+      };
+
+      if self.stack.len() < required {
+         return Err(stack_underflow());
+      }
+
+      let s = self.stack.clone();
+      let (x0, y0) = (self.x, self.y);
+
+      match operator {
+         // hflex
+         34 => {
+            self.curve_to(s[0], 0., s[1], s[2], s[3], 0.);
+            self.curve_to(s[4], 0., s[5], y0 - self.y, s[6], 0.);
+         },
+         // flex
+         35 => {
+            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+            self.curve_to(s[6], s[7], s[8], s[9], s[10], s[11]);
+         },
+         // hflex1
+         36 => {
+            self.curve_to(s[0], s[1], s[2], s[3], s[4], 0.);
+            let dy = y0 - (self.y + s[7]);
+            self.curve_to(s[5], 0., s[6], s[7], s[8], dy);
+         },
+         // flex1
+         _ => {
+            let dx = s[0] + s[2] + s[4] + s[6] + s[8];
+            let dy = s[1] + s[3] + s[5] + s[7] + s[9];
+
+            self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+
+            let (last_x, last_y) = if dx.abs() > dy.abs() {
+               (s[10], y0 - (self.y + s[7] + s[9]))
+            } else {
+               (x0 - (self.x + s[6] + s[8]), s[10])
+            };
+
+            self.curve_to(s[6], s[7], s[8], s[9], last_x, last_y);
+         },
+      }
+
+      Ok(())
+   }
+}
+
+#[inline]
+fn stack_underflow() -> RuntimeError {
+   invalid_font("Charstring stack underflow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+   use font::OutlineCommand;
+
+   use super::Cff;
+
+   fn num(value: i32) -> Vec<u8> {
+      vec![28, (value >> 8) as u8, value as u8]
+   }
+
+   fn long(value: usize) -> Vec<u8> {
+      vec![29, (value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+   }
+
+   fn index(items: &[Vec<u8>]) -> Vec<u8> {
+      if items.is_empty() {
+         return vec![0, 0];
+      }
+
+      let mut data = vec![0, items.len() as u8, 2, 0, 1];
+
+      let mut offset = 1;
+      for item in items {
+         offset += item.len();
+         data.extend_from_slice(&[(offset >> 8) as u8, offset as u8]);
+      }
+
+      for item in items {
+         data.extend_from_slice(item);
+      }
+
+      data
+   }
+
+   fn charstring(parts: &[&[i32]], operators: &[&[u8]]) -> Vec<u8> {
+      let mut code = Vec::new();
+      for (operands, operator) in parts.iter().zip(operators) {
+         for operand in operands.iter() {
+            code.extend(num(*operand));
+         }
+         code.extend_from_slice(operator);
+      }
+      code
+   }
+
+   /// CFF of one glyph with its Private DICT size encoded as `private_size`,
+   /// or the actual size when it is empty.
+   fn test_cff(private_size: Vec<u8>) -> Vec<u8> {
+      // Width and hints with a mask, a line from a local subroutine and a
+      // curve from a global one
+      let glyph = charstring(
+         &[&[500, 0, 10, 20, 10], &[100, 0], &[], &[-107], &[-107], &[]],
+         &[&[18], &[21], &[19, 0xc0], &[10], &[29], &[14]],
+      );
+      let local = charstring(&[&[300, 400], &[]], &[&[5], &[11]]);
+      let global = charstring(&[&[-100, -100, -100, -200], &[]], &[&[31], &[11]]);
+
+      cff_data(glyph, &[global], &[local], private_size)
+   }
+
+   /// CFF of one glyph with the given subroutines.
+   fn cff_data(glyph: Vec<u8>, global: &[Vec<u8>], local: &[Vec<u8>], private_size: Vec<u8>) -> Vec<u8> {
+      let header = vec![1, 0, 4, 1];
+      let names = index(&[b"A".to_vec()]);
+      let strings = index(&[]);
+      let global_subrs = index(global);
+      let char_strings = index(&[glyph]);
+
+      let private = [long(6), vec![19]].concat();
+      let private_size = if private_size.is_empty() { long(private.len()) } else { private_size };
+
+      let top_len = 12 + private_size.len();
+      let top_index_len = index(&[vec![0; top_len]]).len();
+
+      let char_strings_offset = header.len() + names.len() + top_index_len + strings.len() + global_subrs.len();
+      let private_offset = char_strings_offset + char_strings.len();
+
+      let top = [long(char_strings_offset), vec![17], private_size, long(private_offset), vec![18]].concat();
+      assert_eq!(top.len(), top_len);
+
+      [header, names, index(&[top]), strings, global_subrs, char_strings, private, index(local)].concat()
+   }
+
+   #[test]
+   fn test_cff_charstrings() {
+      let data = test_cff(Vec::new());
+
+      let cff = Cff::new(&data, 0..data.len()).unwrap();
+      assert_eq!(cff.glyph_count(), 1);
+
+      assert_eq!(cff.outline(&data, 0).unwrap().commands, vec![
+         OutlineCommand::MoveTo(100., 0.),
+         OutlineCommand::LineTo(400., 400.),
+         OutlineCommand::CubicTo(300., 400., 200., 300., 200., 100.),
+         OutlineCommand::Close,
+      ]);
+
+      assert!(cff.outline(&data, 1).is_err());
+   }
+   #[test]
+   fn test_cff_invalid_offsets() {
+      // Real operands 1e30, -1 and 2.5 as the Private DICT size
+      for size in &[vec![30, 0x1b, 0x30, 0xff], vec![30, 0xe1, 0xff], vec![30, 0x2a, 0x5f]] {
+         let data = test_cff(size.clone());
+         assert!(Cff::new(&data, 0..data.len()).is_err());
+      }
+
+      let data = test_cff(long(1000));
+      assert!(Cff::new(&data, 0..data.len()).is_err());
+   }
+   #[test]
+   fn test_cff_operation_budget() {
+      // Each level calls the next one eight times, which would end up running
+      // the innermost subroutine 8^6 times
+      let call = |subr: i32| [num(subr - 107), vec![10]].concat().repeat(8);
+      let mut local: Vec<Vec<u8>> = (1..6).map(|subr| [call(subr), vec![11]].concat()).collect();
+      local.push(vec![11]);
+
+      let data = cff_data([call(0), vec![14]].concat(), &[], &local, Vec::new());
+      let cff = Cff::new(&data, 0..data.len()).unwrap();
+      assert!(cff.outline(&data, 0).is_err());
+   }
+}
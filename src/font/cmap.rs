@@ -0,0 +1,242 @@
+use std::ops::Range;
+
+use error::RuntimeError;
+
+use super::reader::{Reader, invalid_font};
+
+
+/// Unicode to glyph mapping from one `cmap` subtable.
+#[derive(Debug, Clone)]
+pub struct Cmap {
+   subtable: Range<usize>,
+   format: u16,
+}
+
+impl Cmap {
+   /// Picks the best Unicode subtable of the `cmap` table at `table`,
+   /// preferring full repertoire ones over BMP only ones.
+   pub fn new(data: &[u8], table: Range<usize>) -> Result<Self, RuntimeError> {
+      let mut reader = Reader::at(data, table.start + 2);
+      let count = reader.u16()?;
+
+      let mut best: Option<(u32, Cmap)> = None;
+
+      for _ in 0..count {
+         let platform = reader.u16()?;
+         let encoding = reader.u16()?;
+         let offset = reader.u32()? as usize;
+
+         let start = table.start + offset;
+         let format = Reader::at(data, start).u16()?;
+
+         let rank = match (platform, encoding, format) {
+            (3, 10, 12) | (0, 4, 12) | (0, 6, 12) => 4,
+            (3, 1, 4) | (0, 3, 4) | (0, 1, 4) | (0, 0, 4) => 3,
+            (3, 0, 4) => 2,
+            (_, _, 6) | (_, _, 0) => 1,
+            _ => continue,
+         };
+
+         if best.as_ref().is_none_or(|best| rank > best.0) {
+            best = Some((rank, Cmap {
+               subtable: start..table.end,
+               format,
+            }));
+         }
+      }
+
+      match best {
+         Some((_, cmap)) => Ok(cmap),
+         None => Err(invalid_font("No supported cmap subtable".to_string())),
+      }
+   }
+
+   /// Glyph of `ch`, 0 for the missing glyph.
+   pub fn glyph_index(&self, data: &[u8], ch: char) -> u16 {
+      let code = ch as u32;
+
+      let glyph = match self.format {
+         0 => self.format0(data, code),
+         4 => self.format4(data, code),
+         6 => self.format6(data, code),
+         _ => self.format12(data, code),
+      };
+
+      glyph.unwrap_or(0)
+   }
+
+   fn format0(&self, data: &[u8], code: u32) -> Result<u16, RuntimeError> {
+      if code > 255 {
+         return Ok(0);
+      }
+
+      Ok(Reader::at(data, self.subtable.start + 6 + code as usize).u8()? as u16)
+   }
+
+   fn format4(&self, data: &[u8], code: u32) -> Result<u16, RuntimeError> {
+      if code > 0xffff {
+         return Ok(0);
+      }
+
+      let start = self.subtable.start;
+      let segments = Reader::at(data, start + 6).u16()? as usize / 2;
+
+      let end_codes = start + 14;
+      let start_codes = end_codes + 2 * segments + 2;
+      let deltas = start_codes + 2 * segments;
+      let range_offsets = deltas + 2 * segments;
+
+      // Binary search for the first segment ending at or after the code
+      let (mut low, mut high) = (0, segments);
+      while low < high {
+         let mid = (low + high) / 2;
+         if (Reader::at(data, end_codes + 2 * mid).u16()? as u32) < code {
+            low = mid + 1;
+         } else {
+            high = mid;
+         }
+      }
+
+      if low == segments {
+         return Ok(0);
+      }
+
+      let segment_start = Reader::at(data, start_codes + 2 * low).u16()? as u32;
+      if code < segment_start {
+         return Ok(0);
+      }
+
+      let delta = Reader::at(data, deltas + 2 * low).u16()?;
+      let range_offset_pos = range_offsets + 2 * low;
+      let range_offset = Reader::at(data, range_offset_pos).u16()? as usize;
+
+      if range_offset == 0 {
+         return Ok((code as u16).wrapping_add(delta));
+      }
+
+      let pos = range_offset_pos + range_offset + 2 * (code - segment_start) as usize;
+      match Reader::at(data, pos).u16()? {
+         0 => Ok(0),
+         glyph => Ok(glyph.wrapping_add(delta)),
+      }
+   }
+
+   fn format6(&self, data: &[u8], code: u32) -> Result<u16, RuntimeError> {
+      let mut reader = Reader::at(data, self.subtable.start + 6);
+      let first = reader.u16()? as u32;
+      let count = reader.u16()? as u32;
+
+      if code < first || code >= first + count {
+         return Ok(0);
+      }
+
+      reader.skip(2 * (code - first) as usize);
+      reader.u16()
+   }
+
+   fn format12(&self, data: &[u8], code: u32) -> Result<u16, RuntimeError> {
+      let groups_start = self.subtable.start + 16;
+      let groups = Reader::at(data, self.subtable.start + 12).u32()? as usize;
+
+      let (mut low, mut high) = (0, groups);
+      while low < high {
+         let mid = (low + high) / 2;
+
+         let mut reader = Reader::at(data, groups_start + 12 * mid);
+         let first = reader.u32()?;
+         let last = reader.u32()?;
+         let glyph = reader.u32()?;
+
+         if code < first {
+            high = mid;
+         } else if code > last {
+            low = mid + 1;
+         } else {
+            return Ok(glyph.wrapping_add(code - first) as u16);
+         }
+      }
+
+      Ok(0)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use font::tests::be16;
+
+   use super::Cmap;
+
+   /// Format 4 subtable mapping 10 to 12 by a delta of 5, and 20 and 21
+   /// through the glyph id array with a delta of 100.
+   fn format4() -> Vec<u8> {
+      let mut subtable = Vec::new();
+      be16(&mut subtable, &[4, 44, 0, 6, 4, 1, 2]);
+      be16(&mut subtable, &[12, 21, 0xffff, 0]);
+      be16(&mut subtable, &[10, 20, 0xffff]);
+      be16(&mut subtable, &[5, 100, 1]);
+      be16(&mut subtable, &[0, 4, 0]);
+      be16(&mut subtable, &[7, 0]);
+      subtable
+   }
+
+   /// Format 12 subtable mapping 'A' to 'F' from glyph 1 and three emoji
+   /// from glyph 50.
+   fn format12() -> Vec<u8> {
+      let mut subtable = Vec::new();
+      be16(&mut subtable, &[12, 0, 0, 40, 0, 0, 0, 2]);
+      be16(&mut subtable, &[0, 65, 0, 70, 0, 1]);
+      be16(&mut subtable, &[1, 0xf600, 1, 0xf602, 0, 50]);
+      subtable
+   }
+
+   /// `cmap` table of the subtables with their platform and encoding.
+   fn cmap_table(subtables: Vec<(i32, i32, Vec<u8>)>) -> Vec<u8> {
+      let mut data = Vec::new();
+      be16(&mut data, &[0, subtables.len() as i32]);
+
+      let mut offset = 4 + 8 * subtables.len() as i32;
+      for &(platform, encoding, ref subtable) in &subtables {
+         be16(&mut data, &[platform, encoding, 0, offset]);
+         offset += subtable.len() as i32;
+      }
+
+      for (_, _, subtable) in subtables {
+         data.extend(subtable);
+      }
+
+      data
+   }
+
+   #[test]
+   fn test_cmap_format4() {
+      let data = cmap_table(vec![(3, 1, format4())]);
+      let cmap = Cmap::new(&data, 0..data.len()).unwrap();
+
+      assert_eq!(cmap.glyph_index(&data, '\u{a}'), 15);
+      assert_eq!(cmap.glyph_index(&data, '\u{c}'), 17);
+      assert_eq!(cmap.glyph_index(&data, '\u{d}'), 0);
+      assert_eq!(cmap.glyph_index(&data, '\u{14}'), 107);
+      assert_eq!(cmap.glyph_index(&data, '\u{15}'), 0);
+      assert_eq!(cmap.glyph_index(&data, '\u{9}'), 0);
+      assert_eq!(cmap.glyph_index(&data, '\u{1f600}'), 0);
+   }
+
+   #[test]
+   fn test_cmap_format12() {
+      let data = cmap_table(vec![(3, 1, format4()), (3, 10, format12())]);
+      let cmap = Cmap::new(&data, 0..data.len()).unwrap();
+
+      assert_eq!(cmap.glyph_index(&data, 'A'), 1);
+      assert_eq!(cmap.glyph_index(&data, 'F'), 6);
+      assert_eq!(cmap.glyph_index(&data, 'G'), 0);
+      assert_eq!(cmap.glyph_index(&data, '@'), 0);
+      assert_eq!(cmap.glyph_index(&data, '\u{1f601}'), 51);
+      assert_eq!(cmap.glyph_index(&data, '\u{1f603}'), 0);
+
+      // The full repertoire subtable wins over the BMP one
+      assert_eq!(cmap.glyph_index(&data, '\u{a}'), 0);
+
+      let data = cmap_table(vec![(1, 0, format12())]);
+      assert!(Cmap::new(&data, 0..data.len()).is_err());
+   }
+}
@@ -0,0 +1,397 @@
+use std::ops::Range;
+
+use error::RuntimeError;
+
+use super::reader::Reader;
+
+
+const PAIR_ADJUSTMENT: u16 = 2;
+const EXTENSION: u16 = 9;
+
+const X_ADVANCE: u16 = 0x0004;
+
+/// Pair kerning from the GPOS lookups of the `kern` feature, falling back to
+/// the older `kern` table. Only horizontal advance adjustments are used.
+#[derive(Debug, Clone, Default)]
+pub struct Kerning {
+   /// Pair adjustment subtables of each GPOS lookup.
+   lookups: Vec<Vec<usize>>,
+   /// Start and count of each format 0 pair list of the `kern` table.
+   pair_lists: Vec<(usize, usize)>,
+}
+
+impl Kerning {
+   pub fn new(
+      data: &[u8], gpos: Option<Range<usize>>, kern: Option<Range<usize>>
+   ) -> Result<Self, RuntimeError> {
+      let mut kerning = Kerning::default();
+
+      if let Some(gpos) = gpos {
+         kerning.lookups = gpos_lookups(data, gpos.start)?;
+      }
+
+      if kerning.lookups.is_empty() {
+         if let Some(kern) = kern {
+            kerning.pair_lists = kern_pair_lists(data, kern)?;
+         }
+      }
+
+      Ok(kerning)
+   }
+
+   #[inline]
+   pub fn is_empty(&self) -> bool {
+      self.lookups.is_empty() && self.pair_lists.is_empty()
+   }
+
+   /// Advance adjustment in font units between `left` and `right`.
+   pub fn pair(&self, data: &[u8], left: u16, right: u16) -> i32 {
+      let mut value = 0;
+
+      for lookup in &self.lookups {
+         for subtable in lookup {
+            if let Ok(Some(adjust)) = pair_adjustment(data, *subtable, left, right) {
+               value += adjust as i32;
+               break;
+            }
+         }
+      }
+
+      for &(start, count) in &self.pair_lists {
+         value += kern_pair(data, start, count, left, right).unwrap_or(0) as i32;
+      }
+
+      value
+   }
+}
+
+/// Pair adjustment subtables of the lookups the `kern` feature refers to,
+/// in lookup order. Script and language selection is not applied.
+fn gpos_lookups(data: &[u8], gpos: usize) -> Result<Vec<Vec<usize>>, RuntimeError> {
+   let mut reader = Reader::at(data, gpos + 6);
+   let feature_list = gpos + reader.u16()? as usize;
+   let lookup_list = gpos + reader.u16()? as usize;
+
+   let mut indices = Vec::new();
+
+   let mut reader = Reader::at(data, feature_list);
+   for _ in 0..reader.u16()? {
+      let tag = reader.bytes(4)?;
+      let feature = feature_list + reader.u16()? as usize;
+
+      if tag != b"kern" {
+         continue;
+      }
+
+      let mut feature_reader = Reader::at(data, feature + 2);
+      for _ in 0..feature_reader.u16()? {
+         indices.push(feature_reader.u16()?);
+      }
+   }
+
+   indices.sort();
+   indices.dedup();
+
+   let mut lookups = Vec::new();
+
+   for index in indices {
+      let lookup = lookup_list + Reader::at(data, lookup_list + 2 + 2 * index as usize).u16()? as usize;
+
+      let mut reader = Reader::at(data, lookup);
+      let lookup_type = reader.u16()?;
+      reader.skip(2);
+
+      let mut subtables = Vec::new();
+
+      for _ in 0..reader.u16()? {
+         let subtable = lookup + reader.u16()? as usize;
+
+         match lookup_type {
+            PAIR_ADJUSTMENT => subtables.push(subtable),
+            EXTENSION => {
+               let mut extension = Reader::at(data, subtable + 2);
+               if extension.u16()? == PAIR_ADJUSTMENT {
+                  subtables.push(subtable + extension.u32()? as usize);
+               }
+            },
+            _ => {},
+         }
+      }
+
+      if !subtables.is_empty() {
+         lookups.push(subtables);
+      }
+   }
+
+   Ok(lookups)
+}
+
+/// Horizontal advance adjustment of the first glyph of the pair from a
+/// PairPos subtable, `None` when the subtable does not cover the pair.
+fn pair_adjustment(data: &[u8], subtable: usize, left: u16, right: u16) -> Result<Option<i16>, RuntimeError> {
+   let mut reader = Reader::at(data, subtable);
+
+   let format = reader.u16()?;
+   let coverage = subtable + reader.u16()? as usize;
+   let format1 = reader.u16()?;
+   let format2 = reader.u16()?;
+
+   let covered = match coverage_index(data, coverage, left)? {
+      Some(index) => index,
+      None => return Ok(None),
+   };
+
+   let size1 = value_size(format1);
+   let size2 = value_size(format2);
+
+   let value = match format {
+      1 => {
+         let pair_sets = reader.u16()? as usize;
+         if covered >= pair_sets {
+            return Ok(None);
+         }
+
+         reader.skip(2 * covered);
+         let pair_set = subtable + reader.u16()? as usize;
+
+         let count = Reader::at(data, pair_set).u16()? as usize;
+         let record_size = 2 + size1 + size2;
+
+         let (mut low, mut high) = (0, count);
+         let mut found = None;
+
+         while low < high {
+            let mid = (low + high) / 2;
+            let record = pair_set + 2 + mid * record_size;
+            let second = Reader::at(data, record).u16()?;
+
+            if second < right {
+               low = mid + 1;
+            } else if second > right {
+               high = mid;
+            } else {
+               found = Some(record + 2);
+               break;
+            }
+         }
+
+         match found {
+            Some(value) => value,
+            None => return Ok(None),
+         }
+      },
+      2 => {
+         let class_def1 = subtable + reader.u16()? as usize;
+         let class_def2 = subtable + reader.u16()? as usize;
+         let class1_count = reader.u16()? as usize;
+         let class2_count = reader.u16()? as usize;
+
+         let class1 = glyph_class(data, class_def1, left)?;
+         let class2 = glyph_class(data, class_def2, right)?;
+
+         if class1 >= class1_count || class2 >= class2_count {
+            return Ok(None);
+         }
+
+         subtable + 16 + (class1 * class2_count + class2) * (size1 + size2)
+      },
+      _ => return Ok(None),
+   };
+
+   if format1 & X_ADVANCE == 0 {
+      return Ok(Some(0));
+   }
+
+   let offset = value_size(format1 & (X_ADVANCE - 1));
+
+   Ok(Some(Reader::at(data, value + offset).i16()?))
+}
+
+/// Size in bytes of a ValueRecord, every set format bit adds a field.
+#[inline]
+fn value_size(format: u16) -> usize {
+   2 * (format & 0xff).count_ones() as usize
+}
+
+fn coverage_index(data: &[u8], coverage: usize, glyph: u16) -> Result<Option<usize>, RuntimeError> {
+   let mut reader = Reader::at(data, coverage);
+
+   let format = reader.u16()?;
+   let count = reader.u16()? as usize;
+   let start = reader.pos();
+
+   match format {
+      1 => {
+         let (mut low, mut high) = (0, count);
+         while low < high {
+            let mid = (low + high) / 2;
+            let value = Reader::at(data, start + 2 * mid).u16()?;
+
+            if value < glyph {
+               low = mid + 1;
+            } else if value > glyph {
+               high = mid;
+            } else {
+               return Ok(Some(mid));
+            }
+         }
+
+         Ok(None)
+      },
+      2 => {
+         for i in 0..count {
+            let mut range = Reader::at(data, start + 6 * i);
+            let first = range.u16()?;
+            let last = range.u16()?;
+            let index = range.u16()?;
+
+            if first <= glyph && glyph <= last {
+               return Ok(Some(index as usize + (glyph - first) as usize));
+            }
+         }
+
+         Ok(None)
+      },
+      _ => Ok(None),
+   }
+}
+
+/// Class of `glyph`, 0 for glyphs not listed.
+fn glyph_class(data: &[u8], class_def: usize, glyph: u16) -> Result<usize, RuntimeError> {
+   let mut reader = Reader::at(data, class_def);
+
+   match reader.u16()? {
+      1 => {
+         let first = reader.u16()?;
+         let count = reader.u16()?;
+
+         if glyph < first || glyph - first >= count {
+            return Ok(0);
+         }
+
+         reader.skip(2 * (glyph - first) as usize);
+         Ok(reader.u16()? as usize)
+      },
+      2 => {
+         for _ in 0..reader.u16()? {
+            let first = reader.u16()?;
+            let last = reader.u16()?;
+            let class = reader.u16()?;
+
+            if first <= glyph && glyph <= last {
+               return Ok(class as usize);
+            }
+         }
+
+         Ok(0)
+      },
+      _ => Ok(0),
+   }
+}
+
+/// Horizontal format 0 subtables of a `kern` table in either the Microsoft
+/// or the Apple layout. Subtables shorter than their header end the table.
+fn kern_pair_lists(data: &[u8], kern: Range<usize>) -> Result<Vec<(usize, usize)>, RuntimeError> {
+   let mut reader = Reader::at(data, kern.start);
+   let mut pair_lists = Vec::new();
+
+   let apple = reader.u16()? != 0;
+   let count = if apple {
+      reader.skip(2);
+      reader.u32()?
+   } else {
+      reader.u16()? as u32
+   };
+
+   for _ in 0..count {
+      let start = reader.pos();
+      if start >= kern.end {
+         break;
+      }
+
+      let (length, header, list) = if apple {
+         let length = reader.u32()? as usize;
+         let coverage = reader.u16()?;
+
+         // Format 0, neither vertical, cross-stream nor variation
+         (length, 8, coverage & 0xe0ff == 0)
+      } else {
+         reader.skip(2);
+         let length = reader.u16()? as usize;
+         let coverage = reader.u16()?;
+
+         // Format 0, horizontal kerning values that are neither minimums nor
+         // cross-stream
+         (length, 6, coverage >> 8 == 0 && coverage & 0x7 == 0x1)
+      };
+
+      if length < header {
+         break;
+      }
+
+      if list {
+         reader.seek(start + header);
+         let pairs = start + header + 8;
+
+         // Pairs past the end of the table are left out
+         let count = (reader.u16()? as usize).min(kern.end.saturating_sub(pairs) / 6);
+         pair_lists.push((pairs, count));
+      }
+
+      reader.seek(start + length);
+   }
+
+   Ok(pair_lists)
+}
+
+fn kern_pair(data: &[u8], start: usize, count: usize, left: u16, right: u16) -> Result<i16, RuntimeError> {
+   let key = (left as u32) << 16 | right as u32;
+
+   let (mut low, mut high) = (0, count);
+   while low < high {
+      let mid = (low + high) / 2;
+
+      let mut reader = Reader::at(data, start + 6 * mid);
+      let pair = reader.u32()?;
+
+      if pair < key {
+         low = mid + 1;
+      } else if pair > key {
+         high = mid;
+      } else {
+         return reader.i16();
+      }
+   }
+
+   Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+   use font::tests::{be16, kern_table};
+
+   use super::Kerning;
+
+   #[test]
+   fn test_kern_table_bounds() {
+      let kern = kern_table();
+      let kerning = Kerning::new(&kern, None, Some(0..kern.len())).unwrap();
+      assert_eq!(kerning.pair(&kern, 1, 2), -100);
+
+      // Zero length subtables, repeated as often as the header allows
+      let mut ms = Vec::new();
+      be16(&mut ms, &[0, 0xffff, 0, 0, 1, 1, 0, 0, 0]);
+      assert!(Kerning::new(&ms, None, Some(0..ms.len())).unwrap().is_empty());
+
+      let mut apple = Vec::new();
+      be16(&mut apple, &[1, 0, 0xffff, 0xffff, 0, 0, 0, 0, 0]);
+      assert!(Kerning::new(&apple, None, Some(0..apple.len())).unwrap().is_empty());
+
+      // Pair counts are limited to the table
+      let mut long = kern.clone();
+      long[11] = 0xff;
+      let kerning = Kerning::new(&long, None, Some(0..long.len())).unwrap();
+      assert_eq!(kerning.pair(&long, 1, 2), -100);
+      assert_eq!(kerning.pair(&long, 2, 3), 0);
+   }
+}
@@ -0,0 +1,378 @@
+use error::RuntimeError;
+use draw::RGBA;
+use raster::{Path, SceneCompiler};
+
+use super::Font;
+use super::cache::GlyphCache;
+
+
+/// Horizontal placement of each line within the layout width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+   #[default]
+   Left,
+   Center,
+   Right,
+}
+
+/// Glyph with its origin relative to the top left corner of the text block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+   pub glyph: u16,
+   pub x: f64,
+   pub y: f64,
+}
+
+/// Line of a `TextBlock`, covering `glyphs[start..end]`. The width leaves
+/// out trailing whitespace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+   pub start: usize,
+   pub end: usize,
+   pub width: f64,
+   pub baseline: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextBlock {
+   pub glyphs: Vec<PositionedGlyph>,
+   pub lines: Vec<TextLine>,
+   pub width: f64,
+   pub height: f64,
+}
+
+/// Lays out text in lines of at most `max_width`, breaking after whitespace
+/// and within words only when a word does not fit on a line by itself.
+/// Non-breaking spaces join the words around them. Newlines always start a
+/// new line and tabs advance to the next multiple of `tab_size` spaces.
+///
+/// Positions are relative to the top left corner of the block with y
+/// pointing up, so baselines lie below zero.
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+   pub size: f64,
+   pub max_width: Option<f64>,
+   pub align: Align,
+   /// Multiple of the font's line height between consecutive baselines.
+   pub line_spacing: f64,
+   pub kerning: bool,
+   /// Number of spaces between tab stops.
+   pub tab_size: usize,
+}
+
+impl TextLayout {
+   #[inline]
+   pub fn new(size: f64) -> Self {
+      TextLayout {
+         size,
+         max_width: None,
+         align: Align::Left,
+         line_spacing: 1.,
+         kerning: true,
+         tab_size: 4,
+      }
+   }
+
+   pub fn layout(&self, font: &Font, text: &str) -> TextBlock {
+      let scale = font.scale(self.size);
+      let max_width = self.max_width.unwrap_or(f64::INFINITY);
+
+      let space = font.glyph_index(' ');
+      let tab_stop = self.tab_size as f64 * font.advance_width(space) as f64 * scale;
+
+      let mut lines = Vec::new();
+
+      for paragraph in text.split('\n') {
+         let mut line = LineBuilder::new();
+         let mut word_start = usize::MAX;
+
+         for ch in paragraph.chars() {
+            if ch == '\t' {
+               line.push_tab(tab_stop);
+               word_start = usize::MAX;
+               continue;
+            }
+
+            if ch.is_control() {
+               continue;
+            }
+
+            // Whitespace missing from the font is laid out as a space
+            let whitespace = ch.is_whitespace();
+            let glyph = match font.glyph_index(ch) {
+               0 if whitespace => space,
+               glyph => glyph,
+            };
+
+            if whitespace && !is_non_breaking(ch) {
+               line.push(self, font, scale, glyph, true);
+               word_start = usize::MAX;
+               continue;
+            }
+
+            if word_start == usize::MAX {
+               word_start = line.glyphs.len();
+            }
+
+            line.push(self, font, scale, glyph, whitespace);
+
+            if line.width() <= max_width || line.glyphs.len() == 1 {
+               continue;
+            }
+
+            // Move the word to a new line when it follows whitespace
+            if word_start > 0 {
+               let word: Vec<(u16, bool)> = line.glyphs.drain(word_start..)
+                  .filter_map(|item| item.glyph.map(|glyph| (glyph, item.whitespace)))
+                  .collect();
+               lines.push(line);
+
+               line = LineBuilder::new();
+               for (glyph, whitespace) in word {
+                  line.push(self, font, scale, glyph, whitespace);
+               }
+
+               word_start = 0;
+            }
+
+            // Break the word if it is too wide even on its own line
+            if line.width() > max_width && line.glyphs.len() > 1 {
+               if let Some(last) = line.glyphs.pop() {
+                  lines.push(line);
+
+                  line = LineBuilder::new();
+                  if let Some(glyph) = last.glyph {
+                     line.push(self, font, scale, glyph, last.whitespace);
+                  }
+               }
+
+               word_start = 0;
+            }
+         }
+
+         lines.push(line);
+      }
+
+      let ascent = font.ascender() as f64 * scale;
+      let descent = -font.descender() as f64 * scale;
+      let line_height = (ascent + descent + font.line_gap() as f64 * scale) * self.line_spacing;
+
+      let width = lines.iter().map(|line| line.width()).fold(0., f64::max);
+      let box_width = self.max_width.unwrap_or(width);
+
+      let mut block = TextBlock {
+         glyphs: Vec::new(),
+         lines: Vec::with_capacity(lines.len()),
+         width,
+         height: ascent + descent + (lines.len() - 1) as f64 * line_height,
+      };
+
+      for (i, line) in lines.iter().enumerate() {
+         let line_width = line.width();
+         let baseline = -(ascent + i as f64 * line_height);
+
+         let offset = match self.align {
+            Align::Left => 0.,
+            Align::Center => (box_width - line_width) / 2.,
+            Align::Right => box_width - line_width,
+         };
+
+         let start = block.glyphs.len();
+
+         block.glyphs.extend(line.glyphs.iter().filter_map(|item| item.glyph.map(|glyph| PositionedGlyph {
+            glyph,
+            x: item.x + offset,
+            y: baseline,
+         })));
+
+         block.lines.push(TextLine {
+            start,
+            end: block.glyphs.len(),
+            width: line_width,
+            baseline,
+         });
+      }
+
+      block
+   }
+
+   /// Width and height of the laid out text.
+   pub fn measure(&self, font: &Font, text: &str) -> (f64, f64) {
+      let block = self.layout(font, text);
+      (block.width, block.height)
+   }
+
+   /// Path of the laid out text with the top left corner of the block at
+   /// `(x, y)`.
+   pub fn path(&self, cache: &mut GlyphCache, text: &str, x: f64, y: f64) -> Result<Path, RuntimeError> {
+      let block = self.layout(cache.font(), text);
+
+      let mut path = Path::new();
+
+      for glyph in &block.glyphs {
+         cache.append_glyph(&mut path, glyph.glyph, self.size, x + glyph.x, y + glyph.y)?;
+      }
+
+      Ok(path)
+   }
+
+   pub fn add_text(
+      &self, compiler: &mut SceneCompiler, cache: &mut GlyphCache, text: &str, x: f64, y: f64, color: RGBA
+   ) -> Result<(), RuntimeError> {
+      compiler.add_path(&self.path(cache, text, x, y)?, color);
+      Ok(())
+   }
+}
+
+/// Laid out glyph, or the gap left by a tab when `glyph` is `None`.
+struct LineItem {
+   glyph: Option<u16>,
+   x: f64,
+   end: f64,
+   whitespace: bool,
+}
+
+struct LineBuilder {
+   glyphs: Vec<LineItem>,
+}
+
+impl LineBuilder {
+   #[inline]
+   fn new() -> Self {
+      LineBuilder {
+         glyphs: Vec::new(),
+      }
+   }
+
+   fn push(&mut self, layout: &TextLayout, font: &Font, scale: f64, glyph: u16, whitespace: bool) {
+      let mut x = self.glyphs.last().map_or(0., |item| item.end);
+
+      if layout.kerning {
+         if let Some(previous) = self.glyphs.last().and_then(|item| item.glyph) {
+            x += font.kerning(previous, glyph) as f64 * scale;
+         }
+      }
+
+      self.glyphs.push(LineItem {
+         glyph: Some(glyph),
+         x,
+         end: x + font.advance_width(glyph) as f64 * scale,
+         whitespace,
+      });
+   }
+
+   /// Gap reaching to the next multiple of `tab_stop`.
+   fn push_tab(&mut self, tab_stop: f64) {
+      let x = self.glyphs.last().map_or(0., |item| item.end);
+
+      let end = if tab_stop > 0. {
+         ((x / tab_stop).floor() + 1.) * tab_stop
+      } else {
+         x
+      };
+
+      self.glyphs.push(LineItem {
+         glyph: None,
+         x,
+         end,
+         whitespace: true,
+      });
+   }
+
+   /// End of the last glyph that is not whitespace.
+   fn width(&self) -> f64 {
+      self.glyphs.iter().rev().find(|item| !item.whitespace).map_or(0., |item| item.end)
+   }
+}
+
+/// Spaces that must not be broken at.
+fn is_non_breaking(ch: char) -> bool {
+   matches!(ch, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::point::Point;
+
+   use font::GlyphCache;
+   use font::tests::{test_font, test_font_with, kern_table};
+
+   use super::{TextLayout, Align, PositionedGlyph};
+
+   #[test]
+   fn test_text_layout() {
+      let font = test_font_with(vec![(b"kern", kern_table())]);
+
+      let mut layout = TextLayout::new(1000.);
+      assert_eq!(layout.measure(&font, "AB AB"), (3200., 1000.));
+
+      layout.max_width = Some(2000.);
+      layout.align = Align::Right;
+
+      let block = layout.layout(&font, "AB AB");
+      assert_eq!(block.lines.len(), 2);
+      assert_eq!((block.lines[0].width, block.lines[1].width), (1300., 1300.));
+      assert_eq!((block.width, block.height), (1300., 2090.));
+      assert_eq!(block.glyphs[3], PositionedGlyph { glyph: 1, x: 700., y: -1890. });
+
+      // Words wider than the line are broken between glyphs
+      layout.max_width = Some(1500.);
+      assert_eq!(layout.layout(&font, "AAAA").lines.len(), 2);
+      assert_eq!(layout.layout(&font, "A\nB").lines.len(), 2);
+
+      layout.max_width = None;
+      layout.align = Align::Left;
+
+      let mut cache = GlyphCache::new(font);
+      let path = layout.path(&mut cache, "AB AB", 100., 5000.).unwrap();
+      assert_eq!(path.contours.len(), 4);
+      assert_eq!(path.contours[2].start, Point::new(2000, 4200));
+      assert_eq!(cache.len(), 3);
+   }
+
+   #[test]
+   fn test_text_layout_center() {
+      let font = test_font_with(vec![(b"kern", kern_table())]);
+
+      let mut layout = TextLayout::new(1000.);
+      layout.align = Align::Center;
+
+      // Lines are centered within the widest one without a maximum width
+      let block = layout.layout(&font, "AB\nA");
+      assert_eq!(block.width, 1300.);
+      assert_eq!((block.glyphs[0].x, block.glyphs[2].x), (0., 300.));
+
+      layout.max_width = Some(2000.);
+
+      let block = layout.layout(&font, "AB AB");
+      assert_eq!(block.lines.len(), 2);
+      assert_eq!(block.glyphs[0].x, 350.);
+      assert_eq!(block.glyphs[1].x, 950.);
+      assert_eq!(block.glyphs[3], PositionedGlyph { glyph: 1, x: 350., y: -1890. });
+   }
+
+   #[test]
+   fn test_text_layout_spaces() {
+      let font = test_font();
+
+      let mut layout = TextLayout::new(1000.);
+      layout.max_width = Some(2500.);
+
+      // Breaks at the space rather than the non-breaking space
+      let block = layout.layout(&font, "A A\u{a0}A");
+      assert_eq!(block.lines.len(), 2);
+      assert_eq!((block.lines[0].start, block.lines[0].end), (0, 2));
+      assert_eq!(block.lines[1].width, 2000.);
+
+      // Tabs advance to the next stop of four spaces and have no glyph
+      layout.max_width = None;
+
+      let block = layout.layout(&font, "A\tA");
+      assert_eq!(block.glyphs.len(), 2);
+      assert_eq!(block.glyphs[1].x, 2400.);
+      assert_eq!(layout.layout(&font, "\t\tA").glyphs[0].x, 4800.);
+
+      layout.tab_size = 2;
+      assert_eq!(layout.layout(&font, "AA\tA").glyphs[2].x, 2400.);
+   }
+}
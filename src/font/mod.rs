@@ -0,0 +1,441 @@
+pub mod reader;
+pub mod outline;
+pub mod cmap;
+pub mod truetype;
+pub mod cff;
+pub mod kerning;
+pub mod cache;
+pub mod layout;
+pub mod bitmap;
+pub mod bdf;
+pub mod pcf;
+
+pub use self::outline::{Outline, OutlineCommand};
+pub use self::cache::GlyphCache;
+pub use self::layout::{TextLayout, TextBlock, TextLine, PositionedGlyph, Align};
+pub use self::bitmap::{BitmapFont, BitmapGlyph};
+
+use std::fs;
+use std::ops::Range;
+
+use error::{RuntimeError, ErrorKind};
+use geom::affine::Affine;
+use draw::RGBA;
+use raster::{Path, SceneCompiler};
+
+use self::cmap::Cmap;
+use self::truetype::Glyf;
+use self::cff::Cff;
+use self::kerning::Kerning;
+use self::reader::{Reader, invalid_font};
+
+
+const TRUETYPE: u32 = 0x0001_0000;
+const APPLE_TRUETYPE: u32 = 0x7472_7565;
+const OPENTYPE_CFF: u32 = 0x4f54_544f;
+const COLLECTION: u32 = 0x7474_6366;
+
+#[derive(Debug, Clone)]
+enum Outlines {
+   TrueType(Glyf),
+   Cff(Cff),
+}
+
+/// TrueType or OpenType font with `glyf` or `CFF ` outlines. Collections
+/// load their first font.
+///
+/// Glyphs are scaled so that the em square is `size` world units high and
+/// placed with their origin on the baseline, y pointing up.
+#[derive(Debug, Clone)]
+pub struct Font {
+   data: Vec<u8>,
+   tables: Vec<([u8; 4], Range<usize>)>,
+   outlines: Outlines,
+   cmap: Cmap,
+   kerning: Kerning,
+   hmtx: Range<usize>,
+   h_metrics: u16,
+   glyph_count: u16,
+   units_per_em: u16,
+   ascender: i16,
+   descender: i16,
+   line_gap: i16,
+}
+
+impl Font {
+   pub fn from_data(data: Vec<u8>) -> Result<Self, RuntimeError> {
+      let mut reader = Reader::new(&data);
+
+      let mut version = reader.u32()?;
+      if version == COLLECTION {
+         reader.skip(8);
+         let offset = reader.u32()? as usize;
+
+         reader.seek(offset);
+         version = reader.u32()?;
+      }
+
+      if version != TRUETYPE && version != APPLE_TRUETYPE && version != OPENTYPE_CFF {
+         return Err(invalid_font(format!("Unknown font format {:08x}", version)));
+      }
+
+      let count = reader.u16()?;
+      reader.skip(6);
+
+      let mut tables = Vec::with_capacity(count as usize);
+      for _ in 0..count {
+         let mut tag = [0; 4];
+         tag.copy_from_slice(reader.bytes(4)?);
+         reader.skip(4);
+
+         let offset = reader.u32()? as usize;
+         let length = reader.u32()? as usize;
+
+         if offset.checked_add(length).is_none_or(|end| end > data.len()) {
+            return Err(invalid_font(format!("Font table {} out of range", String::from_utf8_lossy(&tag))));
+         }
+
+         tables.push((tag, offset..offset + length));
+      }
+
+      let optional = |tag: &[u8; 4]| {
+         tables.iter().find(|table| &table.0 == tag).map(|table| table.1.clone())
+      };
+
+      let required = |tag: &[u8; 4]| {
+         optional(tag).ok_or_else(|| invalid_font(format!("Missing font table {}", String::from_utf8_lossy(tag))))
+      };
+
+      let head = required(b"head")?;
+      let hhea = required(b"hhea")?;
+      let maxp = required(b"maxp")?;
+
+      let units_per_em = Reader::at(&data, head.start + 18).u16()?;
+      let long_offsets = Reader::at(&data, head.start + 50).i16()? != 0;
+
+      if units_per_em == 0 {
+         return Err(invalid_font("Font has zero units per em".to_string()));
+      }
+
+      let mut reader = Reader::at(&data, hhea.start + 4);
+      let ascender = reader.i16()?;
+      let descender = reader.i16()?;
+      let line_gap = reader.i16()?;
+      reader.seek(hhea.start + 34);
+      let h_metrics = reader.u16()?;
+
+      let glyph_count = Reader::at(&data, maxp.start + 4).u16()?;
+
+      let outlines = if version == OPENTYPE_CFF {
+         Outlines::Cff(Cff::new(&data, required(b"CFF ")?)?)
+      } else {
+         Outlines::TrueType(Glyf::new(required(b"loca")?, required(b"glyf")?, long_offsets))
+      };
+
+      let cmap = Cmap::new(&data, required(b"cmap")?)?;
+      let hmtx = required(b"hmtx")?;
+
+      // Broken kerning is not worth rejecting an otherwise usable font
+      let kerning = Kerning::new(&data, optional(b"GPOS"), optional(b"kern")).unwrap_or_default();
+
+      if h_metrics == 0 || 4 * h_metrics as usize > hmtx.len() {
+         return Err(invalid_font("Invalid horizontal metrics".to_string()));
+      }
+
+      Ok(Font {
+         data,
+         tables,
+         outlines,
+         cmap,
+         kerning,
+         hmtx,
+         h_metrics,
+         glyph_count,
+         units_per_em,
+         ascender,
+         descender,
+         line_gap,
+      })
+   }
+
+   pub fn from_file(path: &str) -> Result<Self, RuntimeError> {
+      match fs::read(path) {
+         Ok(data) => Font::from_data(data),
+         Err(err) => Err(RuntimeError::new(
+            ErrorKind::Other,
+            format!("Cannot read font {}: {}", path, err)
+         )),
+      }
+   }
+
+   /// Raw data of the table tagged `tag`.
+   pub fn table(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+      self.tables.iter()
+         .find(|table| &table.0 == tag)
+         .map(|table| &self.data[table.1.clone()])
+   }
+
+   #[inline]
+   pub fn units_per_em(&self) -> u16 {
+      self.units_per_em
+   }
+
+   #[inline]
+   pub fn ascender(&self) -> i16 {
+      self.ascender
+   }
+
+   /// Distance from the baseline to the lowest descent, usually negative.
+   #[inline]
+   pub fn descender(&self) -> i16 {
+      self.descender
+   }
+
+   #[inline]
+   pub fn line_gap(&self) -> i16 {
+      self.line_gap
+   }
+
+   #[inline]
+   pub fn glyph_count(&self) -> u16 {
+      self.glyph_count
+   }
+
+   /// World units per font unit when rendering at `size`.
+   #[inline]
+   pub fn scale(&self, size: f64) -> f64 {
+      size / self.units_per_em as f64
+   }
+
+   /// Glyph of `ch`, 0 being the font's missing glyph.
+   #[inline]
+   pub fn glyph_index(&self, ch: char) -> u16 {
+      self.cmap.glyph_index(&self.data, ch)
+   }
+
+   /// Advance width in font units. Glyphs past the last metric share its
+   /// advance, as in monospaced fonts.
+   pub fn advance_width(&self, glyph: u16) -> u16 {
+      let metric = glyph.min(self.h_metrics - 1) as usize;
+      Reader::at(&self.data, self.hmtx.start + 4 * metric).u16().unwrap_or(0)
+   }
+
+   /// Kerning adjustment in font units to add to the advance of `left` when
+   /// followed by `right`.
+   #[inline]
+   pub fn kerning(&self, left: u16, right: u16) -> i32 {
+      self.kerning.pair(&self.data, left, right)
+   }
+
+   #[inline]
+   pub fn has_kerning(&self) -> bool {
+      !self.kerning.is_empty()
+   }
+
+   /// Outline of `glyph` in font units.
+   pub fn outline(&self, glyph: u16) -> Result<Outline, RuntimeError> {
+      if glyph >= self.glyph_count {
+         return Err(invalid_font(format!("Glyph {} out of range", glyph)));
+      }
+
+      match self.outlines {
+         Outlines::TrueType(ref glyf) => glyf.outline(&self.data, glyph),
+         Outlines::Cff(ref cff) => cff.outline(&self.data, glyph),
+      }
+   }
+
+   /// Path of `glyph` at `size` with its origin at `(x, y)`.
+   pub fn glyph_path(&self, glyph: u16, size: f64, x: f64, y: f64) -> Result<Path, RuntimeError> {
+      let scale = self.scale(size);
+      let transform = Affine::scale(scale, scale).then(&Affine::translate(x, y));
+
+      Ok(self.outline(glyph)?.to_path(&transform))
+   }
+
+   /// Path of a single kerned line of text starting at `(x, y)` on the
+   /// baseline. See `TextLayout` for multiple lines and alignment.
+   pub fn text_path(&self, text: &str, size: f64, x: f64, y: f64) -> Result<Path, RuntimeError> {
+      let scale = self.scale(size);
+
+      let mut path = Path::new();
+      let mut pen = x;
+      let mut previous = None;
+
+      for ch in text.chars() {
+         let glyph = self.glyph_index(ch);
+
+         if let Some(previous) = previous {
+            pen += self.kerning(previous, glyph) as f64 * scale;
+         }
+         previous = Some(glyph);
+
+         let glyph_path = self.glyph_path(glyph, size, pen, y)?;
+         path.contours.extend(glyph_path.contours);
+
+         pen += self.advance_width(glyph) as f64 * scale;
+      }
+
+      Ok(path)
+   }
+
+   /// Adds a line of text to `compiler`, which resolves the overlapping
+   /// glyph contours into the anti-aliased polygons `Rasterizer` renders.
+   pub fn add_text(
+      &self, compiler: &mut SceneCompiler, text: &str, size: f64, x: f64, y: f64, color: RGBA
+   ) -> Result<(), RuntimeError> {
+      compiler.add_path(&self.text_path(text, size, x, y)?, color);
+      Ok(())
+   }
+}
+
+#[cfg(test)]
+pub mod tests {
+   use raster::path::PathEdge;
+   use geom::point::Point;
+
+   use super::{Font, OutlineCommand};
+
+   pub fn be16(data: &mut Vec<u8>, values: &[i32]) {
+      for value in values {
+         data.push((*value >> 8) as u8);
+         data.push(*value as u8);
+      }
+   }
+
+   fn sfnt(tables: Vec<(&[u8; 4], Vec<u8>)>) -> Vec<u8> {
+      let mut data = vec![0, 1, 0, 0];
+      be16(&mut data, &[tables.len() as i32, 0, 0, 0]);
+
+      let mut offset = 12 + 16 * tables.len();
+      for (tag, table) in &tables {
+         data.extend_from_slice(*tag);
+         be16(&mut data, &[0, 0, 0, offset as i32, 0, table.len() as i32]);
+         offset += table.len();
+      }
+
+      for (_, table) in tables {
+         data.extend(table);
+      }
+
+      data
+   }
+
+   /// Font with units per em 1000 mapping 'A' to a square with a curved
+   /// corner and 'B' to a composite of 'A' scaled by half.
+   pub fn test_font() -> Font {
+      test_font_with(Vec::new())
+   }
+
+   pub fn test_font_with(extra: Vec<(&'static [u8; 4], Vec<u8>)>) -> Font {
+      let mut head = vec![0; 54];
+      head[18] = (1000 >> 8) as u8;
+      head[19] = (1000 & 0xff) as u8;
+
+      let mut hhea = vec![0; 34];
+      hhea[4..10].copy_from_slice(&[3, 0x20, 0xff, 0x38, 0, 90]);
+      be16(&mut hhea, &[2]);
+
+      let mut maxp = vec![0, 0, 0x50, 0];
+      be16(&mut maxp, &[3]);
+
+      let mut hmtx = Vec::new();
+      be16(&mut hmtx, &[600, 0, 700, 0]);
+
+      let mut cmap = Vec::new();
+      be16(&mut cmap, &[0, 1, 3, 1, 0, 12]);
+      be16(&mut cmap, &[4, 32, 0, 4, 4, 1, 0]);
+      be16(&mut cmap, &[66, 0xffff, 0, 65, 0xffff, -64, 1, 0, 0]);
+
+      let mut simple = Vec::new();
+      be16(&mut simple, &[1, 0, 0, 500, 500, 3, 0]);
+      simple.extend_from_slice(&[1, 0, 1, 1]);
+      be16(&mut simple, &[0, 0, 500, 0, 0, 500, 0, -500]);
+
+      let mut composite = Vec::new();
+      be16(&mut composite, &[-1, 0, 0, 350, 250, 0x0b, 1, 100, 0, 0x2000]);
+
+      let mut loca = Vec::new();
+      be16(&mut loca, &[0, 0, simple.len() as i32 / 2, (simple.len() + composite.len()) as i32 / 2]);
+
+      let mut glyf = simple;
+      glyf.extend(composite);
+
+      let mut tables: Vec<(&[u8; 4], Vec<u8>)> = vec![
+         (b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea),
+         (b"hmtx", hmtx), (b"loca", loca), (b"maxp", maxp),
+      ];
+      tables.extend(extra);
+
+      Font::from_data(sfnt(tables)).unwrap()
+   }
+
+   /// `kern` table moving 'B' 100 units closer to a preceding 'A'.
+   pub fn kern_table() -> Vec<u8> {
+      let mut kern = Vec::new();
+      be16(&mut kern, &[0, 1, 0, 20, 1, 1, 6, 0, 0, 1, 2, -100]);
+      kern
+   }
+
+   /// GPOS `kern` feature with a single pair adjustment of 50 units for the
+   /// same pair.
+   fn gpos_table() -> Vec<u8> {
+      let mut gpos = Vec::new();
+      be16(&mut gpos, &[1, 0, 10, 12, 26, 0]);
+      be16(&mut gpos, &[1, 0x6b65, 0x726e, 8, 0, 1, 0]);
+      be16(&mut gpos, &[1, 4, 2, 0, 1, 8]);
+      be16(&mut gpos, &[1, 18, 4, 0, 1, 12, 1, 2, -50, 1, 1, 1]);
+      gpos
+   }
+
+   #[test]
+   fn test_truetype_font() {
+      let font = test_font();
+
+      assert_eq!(font.units_per_em(), 1000);
+      assert_eq!((font.ascender(), font.descender(), font.line_gap()), (800, -200, 90));
+
+      assert_eq!(font.glyph_index('A'), 1);
+      assert_eq!(font.glyph_index('B'), 2);
+      assert_eq!(font.glyph_index('C'), 0);
+      assert_eq!(font.advance_width(1), 700);
+      assert_eq!(font.advance_width(2), 700);
+
+      assert!(font.outline(0).unwrap().is_empty());
+
+      assert_eq!(font.outline(2).unwrap().commands, vec![
+         OutlineCommand::MoveTo(100., 0.),
+         OutlineCommand::QuadTo(100., 250., 350., 250.),
+         OutlineCommand::LineTo(350., 0.),
+         OutlineCommand::LineTo(100., 0.),
+         OutlineCommand::Close,
+      ]);
+
+      let path = font.text_path("AB", 2000., 10., 20.).unwrap();
+      assert_eq!(path.contours.len(), 2);
+      assert_eq!(path.contours[1].start, Point::new(1610, 20));
+      assert_eq!(path.contours[0].edges[0], PathEdge::Quad {
+         control: Point::new(10, 1020),
+         end: Point::new(1010, 1020),
+      });
+
+      assert!(Font::from_data(vec![0, 1, 0, 0, 0]).is_err());
+      assert!(font.outline(3).is_err());
+   }
+
+   #[test]
+   fn test_kerning() {
+      assert!(!test_font().has_kerning());
+
+      let font = test_font_with(vec![(b"kern", kern_table())]);
+      assert_eq!(font.kerning(1, 2), -100);
+      assert_eq!(font.kerning(2, 1), 0);
+
+      let path = font.text_path("AB", 2000., 10., 20.).unwrap();
+      assert_eq!(path.contours[1].start, Point::new(1410, 20));
+
+      // GPOS takes precedence over the kern table
+      let font = test_font_with(vec![(b"kern", kern_table()), (b"GPOS", gpos_table())]);
+      assert_eq!(font.kerning(1, 2), -50);
+      assert_eq!(font.kerning(1, 1), 0);
+   }
+}
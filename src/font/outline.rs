@@ -0,0 +1,177 @@
+use geom::affine::Affine;
+use raster::Path;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlineCommand {
+   MoveTo(f64, f64),
+   LineTo(f64, f64),
+   QuadTo(f64, f64, f64, f64),
+   CubicTo(f64, f64, f64, f64, f64, f64),
+   Close,
+}
+
+/// Glyph outline in font units with y pointing up. Quadratic curves come
+/// from TrueType outlines, cubic ones from CFF.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outline {
+   pub commands: Vec<OutlineCommand>,
+}
+
+impl Outline {
+   #[inline]
+   pub fn new() -> Self {
+      Outline {
+         commands: Vec::new(),
+      }
+   }
+
+   #[inline]
+   pub fn is_empty(&self) -> bool {
+      self.commands.is_empty()
+   }
+
+   #[inline]
+   pub fn move_to(&mut self, x: f64, y: f64) {
+      self.commands.push(OutlineCommand::MoveTo(x, y));
+   }
+
+   #[inline]
+   pub fn line_to(&mut self, x: f64, y: f64) {
+      self.commands.push(OutlineCommand::LineTo(x, y));
+   }
+
+   #[inline]
+   pub fn quad_to(&mut self, cx: f64, cy: f64, x: f64, y: f64) {
+      self.commands.push(OutlineCommand::QuadTo(cx, cy, x, y));
+   }
+
+   #[inline]
+   pub fn cubic_to(&mut self, c1x: f64, c1y: f64, c2x: f64, c2y: f64, x: f64, y: f64) {
+      self.commands.push(OutlineCommand::CubicTo(c1x, c1y, c2x, c2y, x, y));
+   }
+
+   #[inline]
+   pub fn close(&mut self) {
+      self.commands.push(OutlineCommand::Close);
+   }
+
+   /// Appends `other` mapped through `transform`, as done for the components
+   /// of composite glyphs.
+   pub fn append(&mut self, other: &Outline, transform: &Affine) {
+      for command in &other.commands {
+         self.commands.push(match *command {
+            OutlineCommand::MoveTo(x, y) => {
+               let (x, y) = transform.apply(x, y);
+               OutlineCommand::MoveTo(x, y)
+            },
+            OutlineCommand::LineTo(x, y) => {
+               let (x, y) = transform.apply(x, y);
+               OutlineCommand::LineTo(x, y)
+            },
+            OutlineCommand::QuadTo(cx, cy, x, y) => {
+               let (cx, cy) = transform.apply(cx, cy);
+               let (x, y) = transform.apply(x, y);
+               OutlineCommand::QuadTo(cx, cy, x, y)
+            },
+            OutlineCommand::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+               let (c1x, c1y) = transform.apply(c1x, c1y);
+               let (c2x, c2y) = transform.apply(c2x, c2y);
+               let (x, y) = transform.apply(x, y);
+               OutlineCommand::CubicTo(c1x, c1y, c2x, c2y, x, y)
+            },
+            OutlineCommand::Close => OutlineCommand::Close,
+         });
+      }
+   }
+
+   /// Path of the outline mapped through `transform` and rounded to the
+   /// integer grid. Glyph outlines overlap and wind freely, so the path is
+   /// meant for `SceneCompiler` with the non-zero rule.
+   pub fn to_path(&self, transform: &Affine) -> Path {
+      let mut path = Path::new();
+
+      let point = |x: f64, y: f64| {
+         let (x, y) = transform.apply(x, y);
+         (x.round() as i64, y.round() as i64)
+      };
+
+      for command in &self.commands {
+         match *command {
+            OutlineCommand::MoveTo(x, y) => {
+               let (x, y) = point(x, y);
+               path.move_to(x, y);
+            },
+            OutlineCommand::LineTo(x, y) => {
+               let (x, y) = point(x, y);
+               path.line_to(x, y);
+            },
+            OutlineCommand::QuadTo(cx, cy, x, y) => {
+               let (cx, cy) = point(cx, cy);
+               let (x, y) = point(x, y);
+               path.quad_to(cx, cy, x, y);
+            },
+            OutlineCommand::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+               let (c1x, c1y) = point(c1x, c1y);
+               let (c2x, c2y) = point(c2x, c2y);
+               let (x, y) = point(x, y);
+               path.cubic_to(c1x, c1y, c2x, c2y, x, y);
+            },
+            OutlineCommand::Close => path.close(),
+         }
+      }
+
+      path
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::affine::Affine;
+   use geom::point::Point;
+   use raster::path::PathEdge;
+
+   use super::{Outline, OutlineCommand};
+
+   fn outline() -> Outline {
+      let mut outline = Outline::new();
+      outline.move_to(0., 0.);
+      outline.quad_to(50., 100., 100., 0.);
+      outline.cubic_to(100., -50., 0., -50., 0., 0.);
+      outline.close();
+      outline
+   }
+
+   #[test]
+   fn test_outline_append() {
+      let mut composite = outline();
+      composite.append(&outline(), &Affine::scale(0.5, 2.).then(&Affine::translate(10., 20.)));
+
+      assert_eq!(composite.commands.len(), 8);
+      assert_eq!(composite.commands[4..], [
+         OutlineCommand::MoveTo(10., 20.),
+         OutlineCommand::QuadTo(35., 220., 60., 20.),
+         OutlineCommand::CubicTo(60., -80., 10., -80., 10., 20.),
+         OutlineCommand::Close,
+      ]);
+   }
+
+   #[test]
+   fn test_outline_to_path() {
+      let path = outline().to_path(&Affine::scale(0.3, -0.3));
+
+      assert_eq!(path.contours.len(), 1);
+      assert_eq!(path.contours[0].start, Point::new(0, 0));
+      assert_eq!(path.contours[0].edges, vec![
+         PathEdge::Quad { control: Point::new(15, -30), end: Point::new(30, 0) },
+         PathEdge::Cubic {
+            control1: Point::new(30, 15),
+            control2: Point::new(0, 15),
+            end: Point::new(0, 0),
+         },
+      ]);
+      assert!(path.contours[0].closed);
+
+      assert!(Outline::new().to_path(&Affine::identity()).contours.is_empty());
+   }
+}
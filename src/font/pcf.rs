@@ -0,0 +1,347 @@
+use error::RuntimeError;
+
+use super::bitmap::{BitmapFont, BitmapGlyph, check_metric};
+use super::reader::{Reader, invalid_font};
+
+
+pub const PCF_MAGIC: &[u8] = b"\x01fcp";
+
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+const PCF_BYTE_MSB_FIRST: u32 = 1 << 2;
+const PCF_BIT_MSB_FIRST: u32 = 1 << 3;
+
+const NO_GLYPH: u16 = 0xffff;
+
+struct Metric {
+   left: i32,
+   right: i32,
+   advance: i32,
+   ascent: i32,
+   descent: i32,
+}
+
+/// Parses a font in the X11 Portable Compiled Format.
+pub fn parse_pcf(data: &[u8]) -> Result<BitmapFont, RuntimeError> {
+   if !data.starts_with(PCF_MAGIC) {
+      return Err(invalid_font("Not a PCF font".to_string()));
+   }
+
+   let mut reader = Reader::at(data, 4);
+   reader.little_endian = true;
+
+   let mut tables = Vec::new();
+   for _ in 0..reader.u32()? {
+      let kind = reader.u32()?;
+      reader.skip(8);
+      tables.push((kind, reader.u32()? as usize));
+   }
+
+   let table = |kind: u32| tables.iter().find(|table| table.0 == kind).map(|table| table.1);
+   let required = |kind: u32, name: &str| table(kind).ok_or_else(|| invalid_font(format!("PCF without {}", name)));
+
+   let metrics = read_metrics(data, required(PCF_METRICS, "metrics")?)?;
+   let bitmaps = read_bitmaps(data, required(PCF_BITMAPS, "bitmaps")?, &metrics)?;
+   let (default_char, encodings) = read_encodings(data, required(PCF_BDF_ENCODINGS, "encodings")?)?;
+
+   let (ascent, descent) = match table(PCF_BDF_ACCELERATORS).or_else(|| table(PCF_ACCELERATORS)) {
+      Some(offset) => {
+         let (mut reader, _) = table_reader(data, offset)?;
+         reader.skip(8);
+         (check_metric(reader.i32()?)?, check_metric(reader.i32()?)?)
+      },
+      None => (
+         metrics.iter().map(|metric| metric.ascent).max().unwrap_or(0),
+         metrics.iter().map(|metric| metric.descent).max().unwrap_or(0),
+      ),
+   };
+
+   let mut font = BitmapFont::new(ascent, descent);
+
+   for (code, index) in encodings {
+      let (metric, bitmap) = match (metrics.get(index), bitmaps.get(index)) {
+         (Some(metric), Some(bitmap)) => (metric, bitmap),
+         _ => continue,
+      };
+
+      font.add_glyph(code, BitmapGlyph {
+         width: (metric.right - metric.left).max(0) as u32,
+         height: (metric.ascent + metric.descent).max(0) as u32,
+         x_offset: metric.left,
+         y_offset: -metric.descent,
+         advance: metric.advance,
+         bitmap: bitmap.clone(),
+      });
+   }
+
+   font.set_default(default_char);
+
+   Ok(font)
+}
+
+/// Reader positioned after the format word of the table at `offset`, using
+/// the table's byte order.
+fn table_reader(data: &[u8], offset: usize) -> Result<(Reader<'_>, u32), RuntimeError> {
+   let mut reader = Reader::at(data, offset);
+   reader.little_endian = true;
+
+   let format = reader.u32()?;
+   reader.little_endian = format & PCF_BYTE_MSB_FIRST == 0;
+
+   Ok((reader, format))
+}
+
+fn read_metrics(data: &[u8], offset: usize) -> Result<Vec<Metric>, RuntimeError> {
+   let (mut reader, format) = table_reader(data, offset)?;
+
+   let mut metrics = Vec::new();
+
+   if format & PCF_COMPRESSED_METRICS != 0 {
+      let count = reader.u16()?;
+
+      for _ in 0..count {
+         let mut byte = || reader.u8().map(|value| value as i32 - 0x80);
+
+         metrics.push(Metric {
+            left: byte()?,
+            right: byte()?,
+            advance: byte()?,
+            ascent: byte()?,
+            descent: byte()?,
+         });
+      }
+   } else {
+      let count = reader.u32()?;
+
+      for _ in 0..count {
+         metrics.push(Metric {
+            left: reader.i16()? as i32,
+            right: reader.i16()? as i32,
+            advance: reader.i16()? as i32,
+            ascent: reader.i16()? as i32,
+            descent: reader.i16()? as i32,
+         });
+         reader.skip(2);
+      }
+   }
+
+   Ok(metrics)
+}
+
+/// Glyph bitmaps converted to the `BitmapGlyph` layout of unpadded rows
+/// with the most significant bit first.
+fn read_bitmaps(data: &[u8], offset: usize, metrics: &[Metric]) -> Result<Vec<Vec<u8>>, RuntimeError> {
+   let (mut reader, format) = table_reader(data, offset)?;
+
+   let count = reader.u32()? as usize;
+   if count > data.len().saturating_sub(reader.pos()) / 4 {
+      return Err(invalid_font(format!("PCF bitmap count {} exceeds the data", count)));
+   }
+
+   let mut offsets = Vec::new();
+   for _ in 0..count {
+      offsets.push(reader.u32()? as usize);
+   }
+
+   reader.skip(16);
+   let start = reader.pos();
+
+   let pad = 1 << (format & 3);
+   let unit = 1 << ((format >> 4) & 3);
+
+   let mut bitmaps = Vec::new();
+
+   for (glyph, metric) in offsets.iter().zip(metrics) {
+      let width = (metric.right - metric.left).max(0) as usize;
+      let height = (metric.ascent + metric.descent).max(0) as usize;
+
+      let row_bytes = width.div_ceil(8 * pad) * pad;
+      let stride = width.div_ceil(8);
+
+      if row_bytes == 0 || height == 0 {
+         bitmaps.push(Vec::new());
+         continue;
+      }
+
+      let mut source = Reader::at(data, start + glyph).bytes(row_bytes * height)?.to_vec();
+
+      // Rows are stored in scan units, whose bytes follow the byte order
+      if (format & PCF_BYTE_MSB_FIRST != 0) != (format & PCF_BIT_MSB_FIRST != 0) && unit > 1 {
+         for chunk in source.chunks_mut(unit) {
+            chunk.reverse();
+         }
+      }
+
+      if format & PCF_BIT_MSB_FIRST == 0 {
+         for byte in source.iter_mut() {
+            *byte = byte.reverse_bits();
+         }
+      }
+
+      let mut bitmap = Vec::with_capacity(stride * height);
+      for row in source.chunks(row_bytes) {
+         bitmap.extend_from_slice(&row[..stride]);
+      }
+
+      bitmaps.push(bitmap);
+   }
+
+   Ok(bitmaps)
+}
+
+/// Default character and the glyph index of each encoded character.
+fn read_encodings(data: &[u8], offset: usize) -> Result<(u32, Vec<(u32, usize)>), RuntimeError> {
+   let (mut reader, _) = table_reader(data, offset)?;
+
+   let min_byte2 = reader.u16()? as u32;
+   let max_byte2 = reader.u16()? as u32;
+   let min_byte1 = reader.u16()? as u32;
+   let max_byte1 = reader.u16()? as u32;
+   let default_char = reader.u16()? as u32;
+
+   let mut encodings = Vec::new();
+
+   for byte1 in min_byte1..max_byte1 + 1 {
+      for byte2 in min_byte2..max_byte2 + 1 {
+         let index = reader.u16()?;
+
+         if index != NO_GLYPH {
+            encodings.push((byte1 << 8 | byte2, index as usize));
+         }
+      }
+   }
+
+   Ok((default_char, encodings))
+}
+
+#[cfg(test)]
+mod tests {
+   use font::bitmap::BitmapFont;
+   use font::bitmap::tests::TEST_BDF;
+
+   use super::{PCF_MAGIC, PCF_METRICS, PCF_BITMAPS, PCF_BDF_ENCODINGS, PCF_BDF_ACCELERATORS, PCF_BYTE_MSB_FIRST};
+   use super::parse_pcf;
+
+   fn le32(data: &mut Vec<u8>, values: &[u32]) {
+      for value in values {
+         data.extend_from_slice(&value.to_le_bytes());
+      }
+   }
+
+   fn le16(data: &mut Vec<u8>, values: &[u16]) {
+      for value in values {
+         data.extend_from_slice(&value.to_le_bytes());
+      }
+   }
+
+   /// The BDF test font with big-endian metrics and little-endian bitmaps
+   /// padded to four bytes with the least significant bit first.
+   fn test_pcf() -> Vec<u8> {
+      let mut metrics = Vec::new();
+      le32(&mut metrics, &[PCF_BYTE_MSB_FIRST]);
+      metrics.extend_from_slice(&3u32.to_be_bytes());
+      for metric in &[[0i16, 3, 4, 4, 0, 0], [1, 2, 2, 0, 2, 0], [1, 3, 4, 4, -3, 0]] {
+         for value in metric {
+            metrics.extend_from_slice(&value.to_be_bytes());
+         }
+      }
+
+      let rows: [&[u8]; 3] = [&[0x40, 0xa0, 0xe0, 0xa0], &[0x80, 0x80], &[0xc0]];
+      let mut data = Vec::new();
+      for glyph in &rows {
+         for row in glyph.iter() {
+            data.extend_from_slice(&[row.reverse_bits(), 0, 0, 0]);
+         }
+      }
+
+      let mut bitmaps = Vec::new();
+      le32(&mut bitmaps, &[2, 3, 0, 16, 24, 0, 0, data.len() as u32, 0]);
+      bitmaps.extend(data);
+
+      let mut encodings = Vec::new();
+      le32(&mut encodings, &[0]);
+      le16(&mut encodings, &[63, 106, 0, 0, 63]);
+      for code in 63..107 {
+         le16(&mut encodings, &[match code { 65 => 0, 106 => 1, 63 => 2, _ => 0xffff }]);
+      }
+
+      let mut accelerators = Vec::new();
+      le32(&mut accelerators, &[0, 0, 0, 5, 2]);
+
+      let tables = [
+         (PCF_METRICS, metrics), (PCF_BITMAPS, bitmaps),
+         (PCF_BDF_ENCODINGS, encodings), (PCF_BDF_ACCELERATORS, accelerators),
+      ];
+
+      let mut pcf = PCF_MAGIC.to_vec();
+      le32(&mut pcf, &[tables.len() as u32]);
+
+      let mut offset = 8 + 16 * tables.len();
+      for (kind, table) in &tables {
+         le32(&mut pcf, &[*kind, 0, table.len() as u32, offset as u32]);
+         offset += table.len();
+      }
+
+      for (_, table) in &tables {
+         pcf.extend_from_slice(table);
+      }
+
+      pcf
+   }
+
+   #[test]
+   fn test_pcf_matches_bdf() {
+      let bdf = BitmapFont::from_data(TEST_BDF.as_bytes()).unwrap();
+      let pcf = BitmapFont::from_data(&test_pcf()).unwrap();
+
+      assert_eq!((pcf.ascent, pcf.descent), (bdf.ascent, bdf.descent));
+      assert_eq!(pcf.len(), 3);
+
+      for ch in "Aj?x".chars() {
+         assert_eq!(pcf.glyph(ch), bdf.glyph(ch));
+      }
+   }
+
+   #[test]
+   fn test_pcf_malformed() {
+      let pcf = test_pcf();
+      assert!(parse_pcf(&pcf).is_ok());
+
+      // Offset of the bitmap table, whose count follows its format
+      let bitmaps = u32::from_le_bytes([pcf[36], pcf[37], pcf[38], pcf[39]]) as usize;
+
+      let mut huge_count = pcf.clone();
+      huge_count[bitmaps + 4..bitmaps + 8].copy_from_slice(&[0xff; 4]);
+
+      let mut bad_offset = pcf.clone();
+      bad_offset[bitmaps + 8..bitmaps + 12].copy_from_slice(&[0, 0, 0, 0x10]);
+
+      let mut missing_table = pcf.clone();
+      missing_table[8..12].copy_from_slice(&[0; 4]);
+
+      // Font ascent of the accelerators, the last table
+      let mut huge_ascent = pcf.clone();
+      let ascent = pcf.len() - 8;
+      huge_ascent[ascent..ascent + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+
+      let invalid = [
+         pcf[..pcf.len() - 10].to_vec(),
+         pcf[..40].to_vec(),
+         huge_count,
+         bad_offset,
+         missing_table,
+         huge_ascent,
+         b"\x01fcp".to_vec(),
+         b"\x01fcq\x01\0\0\0".to_vec(),
+      ];
+
+      for data in &invalid {
+         assert!(parse_pcf(data).is_err());
+      }
+   }
+}
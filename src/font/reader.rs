@@ -0,0 +1,122 @@
+use error::{RuntimeError, ErrorKind};
+
+
+/// Reads values from font data, failing instead of panicking when the data
+/// ends early. Values are big-endian unless `little_endian` is set.
+#[derive(Debug, Clone, Copy)]
+pub struct Reader<'a> {
+   data: &'a [u8],
+   pos: usize,
+   pub little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+   #[inline]
+   pub fn new(data: &'a [u8]) -> Self {
+      Reader::at(data, 0)
+   }
+
+   #[inline]
+   pub fn at(data: &'a [u8], pos: usize) -> Self {
+      Reader {
+         data,
+         pos,
+         little_endian: false,
+      }
+   }
+
+   #[inline]
+   pub fn pos(&self) -> usize {
+      self.pos
+   }
+
+   #[inline]
+   pub fn seek(&mut self, pos: usize) {
+      self.pos = pos;
+   }
+
+   #[inline]
+   pub fn skip(&mut self, len: usize) {
+      self.pos += len;
+   }
+
+   #[inline]
+   pub fn at_end(&self) -> bool {
+      self.pos >= self.data.len()
+   }
+
+   pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], RuntimeError> {
+      let end = self.pos.checked_add(len).ok_or_else(truncated)?;
+
+      match self.data.get(self.pos..end) {
+         Some(bytes) => {
+            self.pos = end;
+            Ok(bytes)
+         },
+         None => Err(truncated()),
+      }
+   }
+
+   #[inline]
+   pub fn u8(&mut self) -> Result<u8, RuntimeError> {
+      Ok(self.bytes(1)?[0])
+   }
+
+   #[inline]
+   pub fn i8(&mut self) -> Result<i8, RuntimeError> {
+      Ok(self.u8()? as i8)
+   }
+
+   #[inline]
+   pub fn u16(&mut self) -> Result<u16, RuntimeError> {
+      let bytes = self.bytes(2)?;
+
+      if self.little_endian {
+         Ok((bytes[1] as u16) << 8 | bytes[0] as u16)
+      } else {
+         Ok((bytes[0] as u16) << 8 | bytes[1] as u16)
+      }
+   }
+
+   #[inline]
+   pub fn i16(&mut self) -> Result<i16, RuntimeError> {
+      Ok(self.u16()? as i16)
+   }
+
+   #[inline]
+   pub fn u32(&mut self) -> Result<u32, RuntimeError> {
+      let value = self.uint(4)?;
+
+      if self.little_endian {
+         Ok(value.swap_bytes())
+      } else {
+         Ok(value)
+      }
+   }
+
+   #[inline]
+   pub fn i32(&mut self) -> Result<i32, RuntimeError> {
+      Ok(self.u32()? as i32)
+   }
+
+   /// Big-endian unsigned integer of `size` bytes, as used for CFF offsets.
+   pub fn uint(&mut self, size: usize) -> Result<u32, RuntimeError> {
+      let bytes = self.bytes(size)?;
+      Ok(bytes.iter().fold(0, |value, &byte| value << 8 | byte as u32))
+   }
+
+   /// Signed 2.14 fixed point number.
+   #[inline]
+   pub fn f2dot14(&mut self) -> Result<f64, RuntimeError> {
+      Ok(self.i16()? as f64 / 16384.)
+   }
+}
+
+pub fn truncated() -> RuntimeError {
+   invalid_font("Font data truncated".to_string())
+}
+
+#[inline]
+pub fn invalid_font(description: String) -> RuntimeError {
+   RuntimeError::new(ErrorKind::InvalidInput, description)
+}
@@ -0,0 +1,391 @@
+use std::ops::Range;
+
+use error::RuntimeError;
+use geom::affine::Affine;
+
+use super::outline::Outline;
+use super::reader::{Reader, invalid_font};
+
+
+const ON_CURVE: u8 = 0x01;
+const X_SHORT: u8 = 0x02;
+const Y_SHORT: u8 = 0x04;
+const REPEAT: u8 = 0x08;
+const X_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+const ARGS_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const HAVE_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const HAVE_XY_SCALE: u16 = 0x0040;
+const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+/// Composite glyphs nested deeper than this are treated as broken.
+const MAX_COMPONENT_DEPTH: usize = 16;
+
+/// Composite glyphs placing more components than this in total, counting
+/// nested ones, are treated as broken.
+const MAX_COMPONENTS: usize = 1024;
+
+/// Quadratic outlines from the `loca` and `glyf` tables.
+#[derive(Debug, Clone)]
+pub struct Glyf {
+   loca: Range<usize>,
+   glyf: Range<usize>,
+   long_offsets: bool,
+}
+
+impl Glyf {
+   #[inline]
+   pub fn new(loca: Range<usize>, glyf: Range<usize>, long_offsets: bool) -> Self {
+      Glyf {
+         loca,
+         glyf,
+         long_offsets,
+      }
+   }
+
+   pub fn outline(&self, data: &[u8], glyph: u16) -> Result<Outline, RuntimeError> {
+      let mut outline = Outline::new();
+      let mut components = 0;
+      self.append_glyph(data, glyph, &Affine::identity(), 0, &mut components, &mut outline)?;
+      Ok(outline)
+   }
+
+   /// Byte range of the glyph's description within `data`, empty for glyphs
+   /// without an outline such as the space.
+   fn glyph_range(&self, data: &[u8], glyph: u16) -> Result<Range<usize>, RuntimeError> {
+      let index = glyph as usize;
+      let size = if self.long_offsets { 4 } else { 2 };
+
+      if self.loca.start + size * (index + 2) > self.loca.end {
+         return Err(invalid_font(format!("Glyph {} out of range", glyph)));
+      }
+
+      let mut reader = Reader::at(data, self.loca.start + size * index);
+
+      let (start, end) = if self.long_offsets {
+         (reader.u32()? as usize, reader.u32()? as usize)
+      } else {
+         (2 * reader.u16()? as usize, 2 * reader.u16()? as usize)
+      };
+
+      if start > end || self.glyf.start + end > self.glyf.end {
+         return Err(invalid_font(format!("Glyph {} has invalid location", glyph)));
+      }
+
+      Ok(self.glyf.start + start..self.glyf.start + end)
+   }
+
+   fn append_glyph(
+      &self, data: &[u8], glyph: u16, transform: &Affine, depth: usize, components: &mut usize,
+      outline: &mut Outline
+   ) -> Result<(), RuntimeError> {
+      if depth > MAX_COMPONENT_DEPTH {
+         return Err(invalid_font(format!("Composite glyph {} nested too deep", glyph)));
+      }
+
+      let range = self.glyph_range(data, glyph)?;
+      if range.start == range.end {
+         return Ok(());
+      }
+
+      let mut reader = Reader::at(&data[..range.end], range.start);
+
+      let contours = reader.i16()?;
+      reader.skip(8);
+
+      if contours >= 0 {
+         let mut simple = Outline::new();
+         read_simple(&mut reader, contours as usize, &mut simple)?;
+         outline.append(&simple, transform);
+         Ok(())
+      } else {
+         self.append_composite(data, &mut reader, transform, depth, components, outline)
+      }
+   }
+
+   fn append_composite(
+      &self, data: &[u8], reader: &mut Reader, transform: &Affine, depth: usize, components: &mut usize,
+      outline: &mut Outline
+   ) -> Result<(), RuntimeError> {
+      loop {
+         *components += 1;
+         if *components > MAX_COMPONENTS {
+            return Err(invalid_font("Composite glyph has too many components".to_string()));
+         }
+
+         let flags = reader.u16()?;
+         let component = reader.u16()?;
+
+         let (arg1, arg2) = if flags & ARGS_ARE_WORDS != 0 {
+            (reader.i16()? as f64, reader.i16()? as f64)
+         } else {
+            (reader.i8()? as f64, reader.i8()? as f64)
+         };
+
+         // Anchoring components by matching points is not supported, such
+         // components are placed without an offset.
+         let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+            (arg1, arg2)
+         } else {
+            (0., 0.)
+         };
+
+         let (a, b, c, d) = if flags & HAVE_SCALE != 0 {
+            let scale = reader.f2dot14()?;
+            (scale, 0., 0., scale)
+         } else if flags & HAVE_XY_SCALE != 0 {
+            let x_scale = reader.f2dot14()?;
+            let y_scale = reader.f2dot14()?;
+            (x_scale, 0., 0., y_scale)
+         } else if flags & HAVE_TWO_BY_TWO != 0 {
+            (reader.f2dot14()?, reader.f2dot14()?, reader.f2dot14()?, reader.f2dot14()?)
+         } else {
+            (1., 0., 0., 1.)
+         };
+
+         let placement = Affine::new(a, b, c, d, dx, dy).then(transform);
+
+         self.append_glyph(data, component, &placement, depth + 1, components, outline)?;
+
+         if flags & MORE_COMPONENTS == 0 {
+            return Ok(());
+         }
+      }
+   }
+}
+
+fn read_simple(reader: &mut Reader, contours: usize, outline: &mut Outline) -> Result<(), RuntimeError> {
+   let mut end_points = Vec::with_capacity(contours);
+   for _ in 0..contours {
+      end_points.push(reader.u16()? as usize);
+   }
+
+   let points = match end_points.last() {
+      Some(last) => last + 1,
+      None => return Ok(()),
+   };
+
+   let instructions = reader.u16()? as usize;
+   reader.skip(instructions);
+
+   let mut flags = Vec::with_capacity(points);
+   while flags.len() < points {
+      let flag = reader.u8()?;
+      flags.push(flag);
+
+      if flag & REPEAT != 0 {
+         for _ in 0..reader.u8()? {
+            flags.push(flag);
+         }
+      }
+   }
+   flags.truncate(points);
+
+   let xs = read_coordinates(reader, &flags, X_SHORT, X_SAME_OR_POSITIVE)?;
+   let ys = read_coordinates(reader, &flags, Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+   let mut start = 0;
+   for end in end_points {
+      if end < start || end >= points {
+         return Err(invalid_font("Invalid glyph contour end point".to_string()));
+      }
+
+      let contour: Vec<(f64, f64, bool)> = (start..end + 1)
+         .map(|i| (xs[i] as f64, ys[i] as f64, flags[i] & ON_CURVE != 0))
+         .collect();
+
+      add_contour(&contour, outline);
+
+      start = end + 1;
+   }
+
+   Ok(())
+}
+
+fn read_coordinates(
+   reader: &mut Reader, flags: &[u8], short: u8, same_or_positive: u8
+) -> Result<Vec<i32>, RuntimeError> {
+   let mut coordinates = Vec::with_capacity(flags.len());
+   let mut value = 0i32;
+
+   for flag in flags {
+      if flag & short != 0 {
+         let delta = reader.u8()? as i32;
+         value += if flag & same_or_positive != 0 { delta } else { -delta };
+      } else if flag & same_or_positive == 0 {
+         value += reader.i16()? as i32;
+      }
+
+      coordinates.push(value);
+   }
+
+   Ok(coordinates)
+}
+
+/// Converts a contour of on and off curve points to quadratic curves. Two
+/// consecutive off curve points imply an on curve point between them.
+fn add_contour(points: &[(f64, f64, bool)], outline: &mut Outline) {
+   if points.is_empty() {
+      return;
+   }
+
+   let len = points.len();
+   let midpoint = |a: &(f64, f64, bool), b: &(f64, f64, bool)| ((a.0 + b.0) / 2., (a.1 + b.1) / 2.);
+
+   // Start on an on curve point, or between two off curve ones
+   let (start, first) = match points.iter().position(|point| point.2) {
+      Some(i) => ((points[i].0, points[i].1), i + 1),
+      None => (midpoint(&points[len - 1], &points[0]), 0),
+   };
+
+   outline.move_to(start.0, start.1);
+
+   let mut control: Option<(f64, f64)> = None;
+
+   for i in 0..len {
+      let point = &points[(first + i) % len];
+
+      match (point.2, control) {
+         (true, None) => outline.line_to(point.0, point.1),
+         (true, Some(c)) => {
+            outline.quad_to(c.0, c.1, point.0, point.1);
+            control = None;
+         },
+         (false, None) => control = Some((point.0, point.1)),
+         (false, Some(c)) => {
+            let mid = ((c.0 + point.0) / 2., (c.1 + point.1) / 2.);
+            outline.quad_to(c.0, c.1, mid.0, mid.1);
+            control = Some((point.0, point.1));
+         },
+      }
+   }
+
+   if let Some(c) = control {
+      outline.quad_to(c.0, c.1, start.0, start.1);
+   }
+
+   outline.close();
+}
+
+#[cfg(test)]
+mod tests {
+   use font::{Outline, OutlineCommand};
+   use font::tests::be16;
+
+   use super::{Glyf, add_contour};
+
+   /// Simple glyph of one contour with word sized coordinates.
+   fn simple_glyph(points: &[(i32, i32, bool)]) -> Vec<u8> {
+      let mut glyph = Vec::new();
+      be16(&mut glyph, &[1, 0, 0, 0, 0, points.len() as i32 - 1, 0]);
+      glyph.extend(points.iter().map(|point| point.2 as u8));
+
+      let mut last = (0, 0);
+      for point in points {
+         be16(&mut glyph, &[point.0 - last.0]);
+         last.0 = point.0;
+      }
+      for point in points {
+         be16(&mut glyph, &[point.1 - last.1]);
+         last.1 = point.1;
+      }
+
+      glyph
+   }
+
+   /// Data holding `loca` with long offsets followed by `glyf`.
+   fn glyf(glyphs: Vec<Vec<u8>>) -> (Glyf, Vec<u8>) {
+      let loca_len = 4 * (glyphs.len() + 1);
+
+      let mut data = Vec::new();
+      let mut offset = 0;
+      be16(&mut data, &[0, 0]);
+      for glyph in &glyphs {
+         offset += glyph.len() as i32;
+         be16(&mut data, &[offset >> 16, offset]);
+      }
+
+      for glyph in glyphs {
+         data.extend(glyph);
+      }
+
+      (Glyf::new(0..loca_len, loca_len..data.len(), true), data)
+   }
+
+   #[test]
+   fn test_composite_glyph() {
+      let triangle = simple_glyph(&[(0, 0, true), (100, 0, true), (0, 100, true)]);
+
+      // Byte offsets, then word offsets with a separate x and y scale
+      let mut composite = Vec::new();
+      be16(&mut composite, &[-1, 0, 0, 0, 0]);
+      be16(&mut composite, &[0x22, 0, 0x0a14]);
+      be16(&mut composite, &[0x43, 0, 1000, -1000, 0x2000, 0x4000]);
+
+      // Composite referring to itself
+      let mut cycle = Vec::new();
+      be16(&mut cycle, &[-1, 0, 0, 0, 0, 0x02, 2, 0]);
+
+      // Composites placing another glyph eight times
+      let repeat = |component: i32| {
+         let mut glyph = Vec::new();
+         be16(&mut glyph, &[-1, 0, 0, 0, 0]);
+         for i in 0..8 {
+            be16(&mut glyph, &[if i < 7 { 0x22 } else { 0x02 }, component, 0]);
+         }
+         glyph
+      };
+
+      let glyphs = vec![triangle, composite, cycle, repeat(0), repeat(3), repeat(4), repeat(5)];
+      let (glyf, data) = glyf(glyphs);
+
+      assert_eq!(glyf.outline(&data, 1).unwrap().commands, vec![
+         OutlineCommand::MoveTo(10., 20.),
+         OutlineCommand::LineTo(110., 20.),
+         OutlineCommand::LineTo(10., 120.),
+         OutlineCommand::LineTo(10., 20.),
+         OutlineCommand::Close,
+         OutlineCommand::MoveTo(1000., -1000.),
+         OutlineCommand::LineTo(1050., -1000.),
+         OutlineCommand::LineTo(1000., -900.),
+         OutlineCommand::LineTo(1000., -1000.),
+         OutlineCommand::Close,
+      ]);
+
+      assert!(glyf.outline(&data, 2).is_err());
+      assert!(glyf.outline(&data, 7).is_err());
+
+      // Nesting multiplies the components, 584 are fine but 4680 are not
+      assert_eq!(glyf.outline(&data, 5).unwrap().commands.len(), 5 * 512);
+      assert!(glyf.outline(&data, 6).is_err());
+   }
+
+   #[test]
+   fn test_off_curve_start() {
+      let (glyf, data) = glyf(vec![simple_glyph(&[(0, 0, false), (100, 0, true), (100, 100, false)])]);
+
+      assert_eq!(glyf.outline(&data, 0).unwrap().commands, vec![
+         OutlineCommand::MoveTo(100., 0.),
+         OutlineCommand::QuadTo(100., 100., 50., 50.),
+         OutlineCommand::QuadTo(0., 0., 100., 0.),
+         OutlineCommand::Close,
+      ]);
+
+      // Without on curve points the contour starts between the last and
+      // first point
+      let mut outline = Outline::new();
+      add_contour(&[(0., 0., false), (100., 0., false), (100., 100., false), (0., 100., false)], &mut outline);
+
+      assert_eq!(outline.commands, vec![
+         OutlineCommand::MoveTo(0., 50.),
+         OutlineCommand::QuadTo(0., 0., 50., 0.),
+         OutlineCommand::QuadTo(100., 0., 100., 50.),
+         OutlineCommand::QuadTo(100., 100., 50., 100.),
+         OutlineCommand::QuadTo(0., 100., 0., 50.),
+         OutlineCommand::Close,
+      ]);
+   }
+}
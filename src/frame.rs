@@ -1,33 +1,128 @@
 use std::ptr;
 
-use draw::RGB;
+use draw::{RGB, RGBA, GammaTable};
 use sys::gl::{Texture, Framebuffer, Buffer};
 use renderer::Renderer;
 
+pub trait FrameTarget {
+   fn clear(&mut self);
+
+   fn put_pixel(&mut self, x: i32, y: i32, color: &RGB, width: u32, height: u32);
+
+   fn get_pixel(&self, x: i32, y: i32, width: u32, height: u32) -> RGB;
+
+   fn resize(&mut self, width: u32, height: u32);
+
+   fn pre_render(&mut self) {}
+
+   fn post_render(&mut self, _width: u32, _height: u32) {}
+}
+
 pub struct Frame {
    pub width: u32,
    pub height: u32,
-   gl_context: FrameGLContext,
+   target: Box<dyn FrameTarget>,
 }
 
 impl Frame {
+   /// Creates a frame backed by an in-memory pixel buffer, usable without
+   /// any display or OpenGL context.
    #[inline]
    pub fn new(width: u32, height: u32) -> Self {
+      Frame::with_target(width, height, Box::new(MemoryTarget::new(width, height)))
+   }
+
+   /// Creates a frame backed by a mapped OpenGL pixel buffer that is blitted
+   /// to the current framebuffer on render. Requires a current GL context.
+   #[inline]
+   pub fn new_gl(width: u32, height: u32) -> Self {
+      Frame::with_target(width, height, Box::new(GLTarget::new(width, height)))
+   }
+
+   #[inline]
+   pub fn with_target(width: u32, height: u32, target: Box<dyn FrameTarget>) -> Self {
       Frame {
-         width: width,
-         height: height,
-         gl_context: FrameGLContext::new(width, height)
+         width,
+         height,
+         target,
       }
    }
 
    #[inline]
    pub fn clear(&mut self) {
-      self.gl_context.clear();
+      self.target.clear();
    }
 
    #[inline]
    pub fn put_pixel(&mut self, x: i32, y: i32, color: &RGB) {
-      self.gl_context.put_pixel(x, y, color, self.width, self.height);
+      self.target.put_pixel(x, y, color, self.width, self.height);
+   }
+
+   #[inline]
+   pub fn get_pixel(&self, x: i32, y: i32) -> RGB {
+      self.target.get_pixel(x, y, self.width, self.height)
+   }
+
+   /// Composites `color` over the current pixel value using source-over.
+   #[inline]
+   pub fn blend_pixel(&mut self, x: i32, y: i32, color: &RGBA) {
+      match color.a {
+         255 => self.put_pixel(x, y, &color.rgb()),
+         0 => {},
+         _ => {
+            let dst = self.get_pixel(x, y);
+            self.put_pixel(x, y, &color.over(&dst));
+         }
+      }
+   }
+
+   /// Composites `color` over the current pixel value using source-over in
+   /// linear light.
+   #[inline]
+   pub fn blend_pixel_linear(&mut self, x: i32, y: i32, color: &RGBA) {
+      match color.a {
+         255 => self.put_pixel(x, y, &color.rgb()),
+         0 => {},
+         _ => {
+            let dst = self.get_pixel(x, y);
+            self.put_pixel(x, y, &color.over_linear(&dst));
+         }
+      }
+   }
+
+   /// Composites `color` with a separate source-over coverage per channel,
+   /// as produced by sub-pixel LCD rendering.
+   #[inline]
+   pub fn blend_pixel_lcd(&mut self, x: i32, y: i32, color: &RGB, alpha: &RGB) {
+      let dst = self.get_pixel(x, y);
+
+      let blend = |src: u8, dst: u8, a: u8| {
+         ((src as u32 * a as u32 + dst as u32 * (255 - a as u32)) / 255) as u8
+      };
+
+      self.put_pixel(x, y, &RGB::new(
+         blend(color.r, dst.r, alpha.r),
+         blend(color.g, dst.g, alpha.g),
+         blend(color.b, dst.b, alpha.b),
+      ));
+   }
+
+   /// Per channel compositing like `blend_pixel_lcd`, but in linear light.
+   #[inline]
+   pub fn blend_pixel_lcd_linear(&mut self, x: i32, y: i32, color: &RGB, alpha: &RGB) {
+      let gamma = GammaTable::get();
+      let dst = self.get_pixel(x, y);
+
+      let blend = |src: u8, dst: u8, a: u8| {
+         let a = a as u32;
+         gamma.srgb((gamma.linear(src) * a + gamma.linear(dst) * (255 - a) + 127) / 255)
+      };
+
+      self.put_pixel(x, y, &RGB::new(
+         blend(color.r, dst.r, alpha.r),
+         blend(color.g, dst.g, alpha.g),
+         blend(color.b, dst.b, alpha.b),
+      ));
    }
 
    #[inline]
@@ -35,26 +130,79 @@ impl Frame {
       self.width = width;
       self.height = height;
 
-      self.gl_context.resize(width, height);
+      self.target.resize(width, height);
    }
 
    #[inline]
-   pub fn render(&mut self, renderer: &mut Renderer) {
-      self.gl_context.pre_render();
+   pub fn render(&mut self, renderer: &mut dyn Renderer) {
+      self.target.pre_render();
 
       renderer.render(self);
 
-      self.gl_context.post_render(self.width, self.height);
+      self.target.post_render(self.width, self.height);
+   }
+}
+
+#[inline]
+fn pixel_offset(x: i32, y: i32, width: u32, height: u32) -> Option<usize> {
+   if x >= width as i32 || y >= height as i32 || x < 0 || y < 0 {
+      return None;
+   }
+
+   Some(4 * (x as usize + y as usize * width as usize))
+}
+
+pub struct MemoryTarget {
+   pub data: Vec<u8>,
+}
+
+impl MemoryTarget {
+   #[inline]
+   pub fn new(width: u32, height: u32) -> Self {
+      MemoryTarget {
+         data: vec![0; (width * height * 4) as usize],
+      }
+   }
+}
+
+impl FrameTarget for MemoryTarget {
+   #[inline]
+   fn clear(&mut self) {
+      for byte in &mut self.data {
+         *byte = 0;
+      }
+   }
+
+   #[inline]
+   fn put_pixel(&mut self, x: i32, y: i32, color: &RGB, width: u32, height: u32) {
+      if let Some(i) = pixel_offset(x, y, width, height) {
+         self.data[i] = color.r;
+         self.data[i + 1] = color.g;
+         self.data[i + 2] = color.b;
+      }
+   }
+
+   #[inline]
+   fn get_pixel(&self, x: i32, y: i32, width: u32, height: u32) -> RGB {
+      match pixel_offset(x, y, width, height) {
+         Some(i) => RGB::new(self.data[i], self.data[i + 1], self.data[i + 2]),
+         None => RGB::default(),
+      }
+   }
+
+   #[inline]
+   fn resize(&mut self, width: u32, height: u32) {
+      self.data = vec![0; (width * height * 4) as usize];
    }
 }
 
-struct FrameGLContext {
+pub struct GLTarget {
    pub texture: Texture,
    pub framebuffer: Framebuffer,
    pub buffer: Buffer,
 }
 
-impl FrameGLContext {
+impl GLTarget {
    #[inline]
    pub fn new(width: u32, height: u32) -> Self {
       let texture = Texture::new(width, height);
@@ -67,15 +215,17 @@ impl FrameGLContext {
       buffer.bind();
       buffer.init_data((width * height * 4) as usize);
 
-      FrameGLContext {
-         texture: texture,
-         framebuffer: framebuffer,
-         buffer: buffer,
+      GLTarget {
+         texture,
+         framebuffer,
+         buffer,
       }
    }
+}
 
+impl FrameTarget for GLTarget {
    #[inline]
-   pub fn clear(&mut self) {
+   fn clear(&mut self) {
       unsafe {
          ptr::write_bytes(
             self.buffer.ptr as *mut u8, 0, self.buffer.size as usize
@@ -84,33 +234,43 @@ impl FrameGLContext {
    }
 
    #[inline]
-   pub fn put_pixel(&mut self, x: i32, y: i32, color: &RGB, width: u32, height: u32) {
-      if x >= width as i32 || y >= height as i32 || x < 0 || y < 0 {
-         return;
+   fn put_pixel(&mut self, x: i32, y: i32, color: &RGB, width: u32, height: u32) {
+      if let Some(i) = pixel_offset(x, y, width, height) {
+         let p = self.buffer.ptr as *mut u8;
+         unsafe {
+            *p.add(i) = color.r;
+            *p.add(i + 1) = color.g;
+            *p.add(i + 2) = color.b;
+         }
       }
+   }
 
-      let i = 4 * (x + y * width as i32) as isize;
-      let p = self.buffer.ptr as *mut u8;
-      unsafe {
-         *p.offset(i) = color.r;
-         *p.offset(i+1) = color.g;
-         *p.offset(i+2) = color.b;
+   #[inline]
+   fn get_pixel(&self, x: i32, y: i32, width: u32, height: u32) -> RGB {
+      match pixel_offset(x, y, width, height) {
+         Some(i) => {
+            let p = self.buffer.ptr as *const u8;
+            unsafe {
+               RGB::new(*p.add(i), *p.add(i + 1), *p.add(i + 2))
+            }
+         },
+         None => RGB::default(),
       }
    }
 
    #[inline]
-   pub fn resize(&mut self, width: u32, height: u32) {
+   fn resize(&mut self, width: u32, height: u32) {
       self.buffer.init_data((width * height * 4) as usize);
       self.texture.resize(width, height);
    }
 
    #[inline]
-   pub fn pre_render(&mut self) {
-      self.buffer.map();
+   fn pre_render(&mut self) {
+      self.buffer.map_read_write();
    }
 
    #[inline]
-   pub fn post_render(&mut self, width: u32, height: u32) {
+   fn post_render(&mut self, width: u32, height: u32) {
       self.buffer.unmap();
 
       self.texture.update(width, height);
@@ -118,3 +278,65 @@ impl FrameGLContext {
       self.framebuffer.blit(width, height);
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use draw::{RGB, RGBA};
+
+   use super::Frame;
+
+   #[test]
+   fn test_memory_put_get_pixel() {
+      let mut frame = Frame::new(4, 3);
+
+      frame.put_pixel(2, 1, &RGB::new(10, 20, 30));
+
+      assert_eq!(frame.get_pixel(2, 1), RGB::new(10, 20, 30));
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(0, 0, 0));
+   }
+
+   #[test]
+   fn test_memory_out_of_bounds() {
+      let mut frame = Frame::new(4, 3);
+
+      frame.put_pixel(-1, 0, &RGB::new(255, 255, 255));
+      frame.put_pixel(4, 0, &RGB::new(255, 255, 255));
+      frame.put_pixel(0, 3, &RGB::new(255, 255, 255));
+
+      assert_eq!(frame.get_pixel(4, 0), RGB::default());
+
+      for y in 0..3 {
+         for x in 0..4 {
+            assert_eq!(frame.get_pixel(x, y), RGB::default());
+         }
+      }
+   }
+
+   #[test]
+   fn test_memory_clear_resize() {
+      let mut frame = Frame::new(2, 2);
+
+      frame.put_pixel(1, 1, &RGB::new(1, 2, 3));
+      frame.clear();
+      assert_eq!(frame.get_pixel(1, 1), RGB::default());
+
+      frame.resize(5, 5);
+      frame.put_pixel(4, 4, &RGB::new(1, 2, 3));
+      assert_eq!(frame.get_pixel(4, 4), RGB::new(1, 2, 3));
+   }
+
+   #[test]
+   fn test_blend_pixel() {
+      let mut frame = Frame::new(2, 1);
+
+      frame.put_pixel(0, 0, &RGB::new(0, 0, 200));
+      frame.blend_pixel(0, 0, &RGBA::new(255, 0, 0, 0));
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(0, 0, 200));
+
+      frame.blend_pixel(0, 0, &RGBA::new(255, 100, 0, 51));
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(51, 20, 160));
+
+      frame.blend_pixel(0, 0, &RGBA::new(1, 2, 3, 255));
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(1, 2, 3));
+   }
+}
@@ -0,0 +1,131 @@
+/// Affine transform mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Affine {
+   pub a: f64,
+   pub b: f64,
+   pub c: f64,
+   pub d: f64,
+   pub e: f64,
+   pub f: f64,
+}
+
+impl Affine {
+   #[inline]
+   pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+      Affine {
+         a,
+         b,
+         c,
+         d,
+         e,
+         f,
+      }
+   }
+
+   #[inline]
+   pub fn identity() -> Self {
+      Affine::new(1., 0., 0., 1., 0., 0.)
+   }
+
+   #[inline]
+   pub fn translate(x: f64, y: f64) -> Self {
+      Affine::new(1., 0., 0., 1., x, y)
+   }
+
+   #[inline]
+   pub fn scale(x: f64, y: f64) -> Self {
+      Affine::new(x, 0., 0., y, 0., 0.)
+   }
+
+   /// Anti-clockwise rotation by `angle` radians around the origin.
+   #[inline]
+   pub fn rotate(angle: f64) -> Self {
+      let (sin, cos) = angle.sin_cos();
+      Affine::new(cos, sin, -sin, cos, 0., 0.)
+   }
+
+   /// Transform applying `self` first and `other` after it.
+   pub fn then(&self, other: &Affine) -> Affine {
+      Affine::new(
+         other.a * self.a + other.c * self.b,
+         other.b * self.a + other.d * self.b,
+         other.a * self.c + other.c * self.d,
+         other.b * self.c + other.d * self.d,
+         other.a * self.e + other.c * self.f + other.e,
+         other.b * self.e + other.d * self.f + other.f,
+      )
+   }
+
+   #[inline]
+   pub fn determinant(&self) -> f64 {
+      self.a * self.d - self.b * self.c
+   }
+
+   /// Whether the transform keeps circles circular, being a combination of
+   /// rotation, uniform scaling, mirroring and translation.
+   pub fn is_similarity(&self) -> bool {
+      let det = self.determinant();
+      let tolerance = 1e-9 * det.abs().sqrt().max(1.);
+
+      if det > 0. {
+         (self.a - self.d).abs() < tolerance && (self.b + self.c).abs() < tolerance
+      } else {
+         (self.a + self.d).abs() < tolerance && (self.b - self.c).abs() < tolerance
+      }
+   }
+
+   /// Inverse transform, or `None` when the transform is degenerate.
+   pub fn invert(&self) -> Option<Affine> {
+      let det = self.determinant();
+      if det == 0. || !det.is_finite() {
+         return None;
+      }
+
+      Some(Affine::new(
+         self.d / det,
+         -self.b / det,
+         -self.c / det,
+         self.a / det,
+         (self.c * self.f - self.d * self.e) / det,
+         (self.b * self.e - self.a * self.f) / det,
+      ))
+   }
+
+   #[inline]
+   pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+      (
+         self.a * x + self.c * y + self.e,
+         self.b * x + self.d * y + self.f,
+      )
+   }
+}
+
+impl Default for Affine {
+   fn default() -> Affine {
+      Affine::identity()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use std::f64::consts::FRAC_PI_2;
+
+   use super::Affine;
+
+   #[test]
+   fn test_affine_compose_invert() {
+      let transform = Affine::scale(2., 3.)
+         .then(&Affine::rotate(FRAC_PI_2))
+         .then(&Affine::translate(10., 20.));
+
+      let (x, y) = transform.apply(1., 1.);
+      assert!((x - 7.).abs() < 1e-9 && (y - 22.).abs() < 1e-9);
+
+      let inverse = transform.invert().unwrap();
+      let (x, y) = inverse.apply(x, y);
+      assert!((x - 1.).abs() < 1e-9 && (y - 1.).abs() < 1e-9);
+
+      assert!(Affine::scale(0., 1.).invert().is_none());
+   }
+}
@@ -1,6 +1,6 @@
 use std::cmp::{PartialOrd, Ordering};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Point {
    pub x: i64,
    pub y: i64
@@ -13,6 +13,9 @@ pub mod event;
 pub mod event_loop;
 pub mod draw;
 pub mod raster;
+pub mod font;
+pub mod svg;
+pub mod png;
 
 pub use application::Application;
 pub use renderer::Renderer;
@@ -0,0 +1,756 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+
+const WINDOW_SIZE: usize = 1 << 15;
+const HASH_SIZE: usize = 1 << 15;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Symbols collected before a block is written.
+const BLOCK_SYMBOLS: usize = 1 << 15;
+
+const MAX_STORED: usize = 65535;
+
+/// Matches tried per position for each compression level.
+const CHAIN_LENGTHS: [usize; 10] = [0, 4, 8, 16, 32, 64, 128, 256, 1024, 4096];
+
+const END_OF_BLOCK: usize = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+   3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+   35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+
+const LENGTH_EXTRA: [u8; 29] = [
+   0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+   3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+const DISTANCE_BASE: [u16; 30] = [
+   1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+   257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+const DISTANCE_EXTRA: [u8; 30] = [
+   0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+   7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// Order in which code length code lengths are stored in a block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Literal byte or back reference found by the match finder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol {
+   Literal(u8),
+   Match {
+      length: u16,
+      distance: u16,
+   },
+}
+
+/// Compresses `data` into a zlib stream. Level 0 stores the data, levels up
+/// to 9 search longer for matches.
+pub fn zlib_compress(data: &[u8], level: u8) -> Vec<u8> {
+   let level = level.min(9) as usize;
+
+   let mut writer = BitWriter::new();
+
+   // Deflate with a 32K window, the check bits making the header a
+   // multiple of 31
+   let header = 0x7800 | [0, 1, 1, 1, 1, 2, 2, 2, 3, 3][level] << 6;
+   let header = header + (31 - header % 31) % 31;
+   writer.data.extend_from_slice(&[(header >> 8) as u8, header as u8]);
+
+   if level == 0 {
+      write_stored(&mut writer, data, true);
+   } else {
+      deflate(&mut writer, data, CHAIN_LENGTHS[level]);
+   }
+
+   let mut stream = writer.finish();
+   stream.extend_from_slice(&adler32(data).to_be_bytes());
+   stream
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+   let (mut a, mut b) = (1u32, 0u32);
+
+   // Largest run whose sums cannot overflow before the modulo
+   for chunk in data.chunks(5552) {
+      for &byte in chunk {
+         a += byte as u32;
+         b += a;
+      }
+
+      a %= 65521;
+      b %= 65521;
+   }
+
+   b << 16 | a
+}
+
+fn deflate(writer: &mut BitWriter, data: &[u8], chain_length: usize) {
+   let mut head = vec![usize::MAX; HASH_SIZE];
+   let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+   let mut symbols = Vec::with_capacity(BLOCK_SYMBOLS);
+   let mut block_start = 0;
+   let mut pos = 0;
+
+   while pos < data.len() {
+      let (length, distance) = longest_match(data, pos, &head, &prev, chain_length);
+
+      let advance = if length >= MIN_MATCH {
+         symbols.push(Symbol::Match {
+            length: length as u16,
+            distance: distance as u16,
+         });
+         length
+      } else {
+         symbols.push(Symbol::Literal(data[pos]));
+         1
+      };
+
+      for position in pos..pos + advance {
+         if position + MIN_MATCH <= data.len() {
+            let hash = hash(data, position);
+            prev[position % WINDOW_SIZE] = head[hash];
+            head[hash] = position;
+         }
+      }
+
+      pos += advance;
+
+      if symbols.len() >= BLOCK_SYMBOLS {
+         write_block(writer, &symbols, &data[block_start..pos], false);
+         symbols.clear();
+         block_start = pos;
+      }
+   }
+
+   write_block(writer, &symbols, &data[block_start..], true);
+}
+
+#[inline]
+fn hash(data: &[u8], pos: usize) -> usize {
+   let value = (data[pos] as usize) << 16 | (data[pos + 1] as usize) << 8 | data[pos + 2] as usize;
+   (value.wrapping_mul(2654435761) >> 8) % HASH_SIZE
+}
+
+/// Longest earlier match of the bytes at `pos` within the window, following
+/// at most `chain_length` positions with the same hash.
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize], chain_length: usize) -> (usize, usize) {
+   if pos + MIN_MATCH > data.len() {
+      return (0, 0);
+   }
+
+   let max_length = (data.len() - pos).min(MAX_MATCH);
+
+   let mut best = (0, 0);
+   let mut candidate = head[hash(data, pos)];
+
+   for _ in 0..chain_length {
+      if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+         break;
+      }
+
+      let length = data[candidate..candidate + max_length].iter()
+         .zip(&data[pos..pos + max_length])
+         .take_while(|(a, b)| a == b)
+         .count();
+
+      if length > best.0 {
+         best = (length, pos - candidate);
+
+         if length == max_length {
+            break;
+         }
+      }
+
+      // Stale entries from before the window may point forward again
+      let next = prev[candidate % WINDOW_SIZE];
+      if next >= candidate {
+         break;
+      }
+
+      candidate = next;
+   }
+
+   best
+}
+
+/// Writes the symbols of `raw` as whichever of a dynamic, fixed or stored
+/// block is smallest.
+fn write_block(writer: &mut BitWriter, symbols: &[Symbol], raw: &[u8], last: bool) {
+   let mut literal_freqs = [0u32; 286];
+   let mut distance_freqs = [0u32; 30];
+
+   for symbol in symbols {
+      match *symbol {
+         Symbol::Literal(byte) => literal_freqs[byte as usize] += 1,
+         Symbol::Match { length, distance } => {
+            literal_freqs[257 + length_code(length)] += 1;
+            distance_freqs[distance_code(distance)] += 1;
+         },
+      }
+   }
+
+   literal_freqs[END_OF_BLOCK] += 1;
+
+   let dynamic = DynamicHeader::new(&literal_freqs, &distance_freqs);
+   let (fixed_literals, fixed_distances) = fixed_lengths();
+
+   let dynamic_cost = dynamic.cost() + data_cost(&literal_freqs, &distance_freqs, &dynamic.literals, &dynamic.distances);
+   let fixed_cost = 3 + data_cost(&literal_freqs, &distance_freqs, &fixed_literals, &fixed_distances);
+   let stored_cost = (raw.len().div_ceil(MAX_STORED).max(1) * 5 + raw.len()) as u64 * 8 + 7;
+
+   if stored_cost < dynamic_cost.min(fixed_cost) {
+      write_stored(writer, raw, last);
+      return;
+   }
+
+   writer.write(last as u32, 1);
+
+   let (literals, distances) = if dynamic_cost < fixed_cost {
+      writer.write(2, 2);
+      dynamic.write(writer);
+      (dynamic.literals, dynamic.distances)
+   } else {
+      writer.write(1, 2);
+      (fixed_literals, fixed_distances)
+   };
+
+   let literal_codes = canonical_codes(&literals);
+   let distance_codes = canonical_codes(&distances);
+
+   for symbol in symbols {
+      match *symbol {
+         Symbol::Literal(byte) => {
+            writer.write(literal_codes[byte as usize], literals[byte as usize] as u32);
+         },
+         Symbol::Match { length, distance } => {
+            let code = length_code(length);
+            writer.write(literal_codes[257 + code], literals[257 + code] as u32);
+            writer.write((length - LENGTH_BASE[code]) as u32, LENGTH_EXTRA[code] as u32);
+
+            let code = distance_code(distance);
+            writer.write(distance_codes[code], distances[code] as u32);
+            writer.write((distance - DISTANCE_BASE[code]) as u32, DISTANCE_EXTRA[code] as u32);
+         },
+      }
+   }
+
+   writer.write(literal_codes[END_OF_BLOCK], literals[END_OF_BLOCK] as u32);
+}
+
+/// Writes `data` as stored blocks, at least one even when empty.
+fn write_stored(writer: &mut BitWriter, data: &[u8], last: bool) {
+   let chunks: Vec<&[u8]> = if data.is_empty() {
+      vec![data]
+   } else {
+      data.chunks(MAX_STORED).collect()
+   };
+
+   for (i, chunk) in chunks.iter().enumerate() {
+      let final_chunk = last && i == chunks.len() - 1;
+
+      writer.write(final_chunk as u32, 1);
+      writer.write(0, 2);
+      writer.align();
+
+      let len = chunk.len() as u16;
+      writer.data.extend_from_slice(&len.to_le_bytes());
+      writer.data.extend_from_slice(&(!len).to_le_bytes());
+      writer.data.extend_from_slice(chunk);
+   }
+}
+
+/// Bits taken by the symbols and their extra bits with the given code
+/// lengths.
+fn data_cost(literal_freqs: &[u32], distance_freqs: &[u32], literals: &[u8], distances: &[u8]) -> u64 {
+   let mut cost = 0;
+
+   for (symbol, &freq) in literal_freqs.iter().enumerate() {
+      let extra = if symbol > END_OF_BLOCK { LENGTH_EXTRA[symbol - 257] } else { 0 };
+      cost += freq as u64 * (literals[symbol] + extra) as u64;
+   }
+
+   for (code, &freq) in distance_freqs.iter().enumerate() {
+      cost += freq as u64 * (distances[code] + DISTANCE_EXTRA[code]) as u64;
+   }
+
+   cost
+}
+
+#[inline]
+fn length_code(length: u16) -> usize {
+   LENGTH_BASE.iter().rposition(|&base| base <= length).unwrap_or(0)
+}
+
+#[inline]
+fn distance_code(distance: u16) -> usize {
+   DISTANCE_BASE.iter().rposition(|&base| base <= distance).unwrap_or(0)
+}
+
+/// Code lengths of the fixed Huffman codes.
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+   let mut literals = vec![8; 288];
+
+   for length in &mut literals[144..256] {
+      *length = 9;
+   }
+
+   for length in &mut literals[256..280] {
+      *length = 7;
+   }
+
+   (literals, vec![5; 30])
+}
+
+/// Code lengths of a dynamic block and their run length encoding.
+struct DynamicHeader {
+   literals: Vec<u8>,
+   distances: Vec<u8>,
+   /// Code length symbols with their extra bits value.
+   runs: Vec<(u8, u8)>,
+   code_lengths: Vec<u8>,
+   literal_count: usize,
+   distance_count: usize,
+   code_length_count: usize,
+}
+
+impl DynamicHeader {
+   fn new(literal_freqs: &[u32], distance_freqs: &[u32]) -> Self {
+      let literals = huffman_lengths(literal_freqs, 15);
+      let distances = huffman_lengths(distance_freqs, 15);
+
+      let literal_count = last_used(&literals).max(257);
+      let distance_count = last_used(&distances).max(1);
+
+      let mut lengths = literals[..literal_count].to_vec();
+      lengths.extend_from_slice(&distances[..distance_count]);
+
+      let runs = run_lengths(&lengths);
+
+      let mut freqs = [0u32; 19];
+      for &(symbol, _) in &runs {
+         freqs[symbol as usize] += 1;
+      }
+
+      let code_lengths = huffman_lengths(&freqs, 7);
+
+      let code_length_count = CODE_LENGTH_ORDER.iter()
+         .rposition(|&symbol| code_lengths[symbol] != 0)
+         .map_or(4, |index| (index + 1).max(4));
+
+      DynamicHeader {
+         literals,
+         distances,
+         runs,
+         code_lengths,
+         literal_count,
+         distance_count,
+         code_length_count,
+      }
+   }
+
+   fn cost(&self) -> u64 {
+      let runs: u64 = self.runs.iter()
+         .map(|&(symbol, _)| (self.code_lengths[symbol as usize] + run_extra_bits(symbol)) as u64)
+         .sum();
+
+      3 + 14 + 3 * self.code_length_count as u64 + runs
+   }
+
+   fn write(&self, writer: &mut BitWriter) {
+      writer.write((self.literal_count - 257) as u32, 5);
+      writer.write((self.distance_count - 1) as u32, 5);
+      writer.write((self.code_length_count - 4) as u32, 4);
+
+      for &symbol in &CODE_LENGTH_ORDER[..self.code_length_count] {
+         writer.write(self.code_lengths[symbol] as u32, 3);
+      }
+
+      let codes = canonical_codes(&self.code_lengths);
+
+      for &(symbol, extra) in &self.runs {
+         writer.write(codes[symbol as usize], self.code_lengths[symbol as usize] as u32);
+         writer.write(extra as u32, run_extra_bits(symbol) as u32);
+      }
+   }
+}
+
+#[inline]
+fn last_used(lengths: &[u8]) -> usize {
+   lengths.iter().rposition(|&length| length != 0).map_or(0, |index| index + 1)
+}
+
+#[inline]
+fn run_extra_bits(symbol: u8) -> u8 {
+   match symbol {
+      16 => 2,
+      17 => 3,
+      18 => 7,
+      _ => 0,
+   }
+}
+
+/// Encodes code lengths with the repeat symbols 16 (previous length 3-6
+/// times), 17 (3-10 zeros) and 18 (11-138 zeros).
+fn run_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+   let mut runs = Vec::new();
+   let mut i = 0;
+
+   while i < lengths.len() {
+      let length = lengths[i];
+      let count = lengths[i..].iter().take_while(|&&other| other == length).count();
+
+      if length == 0 && count >= 11 {
+         let count = count.min(138);
+         runs.push((18, (count - 11) as u8));
+         i += count;
+      } else if length == 0 && count >= 3 {
+         runs.push((17, (count - 3) as u8));
+         i += count;
+      } else if length != 0 && count >= 4 {
+         runs.push((length, 0));
+
+         let count = (count - 1).min(6);
+         runs.push((16, (count - 3) as u8));
+         i += count + 1;
+      } else {
+         runs.push((length, 0));
+         i += 1;
+      }
+   }
+
+   runs
+}
+
+/// Huffman code lengths for the frequencies, none longer than `limit`.
+/// Two symbols at least get a code so that the code is always complete.
+fn huffman_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+   let mut freqs = freqs.to_vec();
+
+   for symbol in 0..2 {
+      if freqs.iter().filter(|&&freq| freq > 0).count() < 2 && freqs[symbol] == 0 {
+         freqs[symbol] = 1;
+      }
+   }
+
+   // Leaves first, then the inner nodes of the tree as they are merged
+   let mut parents = vec![usize::MAX; freqs.len()];
+   let mut heap = BinaryHeap::new();
+
+   for (symbol, &freq) in freqs.iter().enumerate() {
+      if freq > 0 {
+         heap.push(Reverse((freq as u64, symbol)));
+      }
+   }
+
+   while heap.len() > 1 {
+      let Reverse((freq1, node1)) = heap.pop().unwrap();
+      let Reverse((freq2, node2)) = heap.pop().unwrap();
+
+      let parent = parents.len();
+      parents.push(usize::MAX);
+      parents[node1] = parent;
+      parents[node2] = parent;
+
+      heap.push(Reverse((freq1 + freq2, parent)));
+   }
+
+   let mut depths = vec![0usize; parents.len()];
+   for node in (0..parents.len()).rev() {
+      if parents[node] != usize::MAX {
+         depths[node] = depths[parents[node]] + 1;
+      }
+   }
+
+   let limit = limit as usize;
+   let max_depth = depths[..freqs.len()].iter().cloned().max().unwrap_or(0);
+
+   let mut counts = vec![0usize; max_depth.max(limit) + 1];
+   for (symbol, &freq) in freqs.iter().enumerate() {
+      if freq > 0 {
+         counts[depths[symbol]] += 1;
+      }
+   }
+
+   // Moves overlong codes to the limit, then lengthens shorter codes until
+   // the lengths fit a prefix code again
+   if max_depth > limit {
+      for depth in limit + 1..=max_depth {
+         counts[limit] += counts[depth];
+         counts[depth] = 0;
+      }
+
+      let mut total: usize = (1..=limit).map(|depth| counts[depth] << (limit - depth)).sum();
+
+      while total > 1 << limit {
+         counts[limit] -= 1;
+
+         if let Some(depth) = (1..limit).rev().find(|&depth| counts[depth] != 0) {
+            counts[depth] -= 1;
+            counts[depth + 1] += 2;
+         }
+
+         total -= 1;
+      }
+   }
+
+   // Shortest codes go to the most frequent symbols
+   let mut symbols: Vec<usize> = (0..freqs.len()).filter(|&symbol| freqs[symbol] > 0).collect();
+   symbols.sort_by_key(|&symbol| Reverse(freqs[symbol]));
+
+   let mut lengths = vec![0u8; freqs.len()];
+   let mut symbols = symbols.into_iter();
+
+   for (depth, &count) in counts.iter().enumerate() {
+      for symbol in symbols.by_ref().take(count) {
+         lengths[symbol] = depth as u8;
+      }
+   }
+
+   lengths
+}
+
+/// Canonical codes for the lengths, bit reversed for writing least
+/// significant bit first.
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+   let max_length = lengths.iter().cloned().max().unwrap_or(0) as usize;
+
+   let mut counts = vec![0u32; max_length + 1];
+   for &length in lengths {
+      counts[length as usize] += 1;
+   }
+   counts[0] = 0;
+
+   let mut next = vec![0u32; max_length + 1];
+   let mut code = 0;
+   for length in 1..=max_length {
+      code = (code + counts[length - 1]) << 1;
+      next[length] = code;
+   }
+
+   lengths.iter().map(|&length| {
+      if length == 0 {
+         return 0;
+      }
+
+      let code = next[length as usize];
+      next[length as usize] += 1;
+
+      code.reverse_bits() >> (32 - length as u32)
+   }).collect()
+}
+
+/// Packs bits into bytes starting with the least significant bit.
+struct BitWriter {
+   data: Vec<u8>,
+   bits: u64,
+   count: u32,
+}
+
+impl BitWriter {
+   #[inline]
+   fn new() -> Self {
+      BitWriter {
+         data: Vec::new(),
+         bits: 0,
+         count: 0,
+      }
+   }
+
+   #[inline]
+   fn write(&mut self, value: u32, len: u32) {
+      self.bits |= (value as u64) << self.count;
+      self.count += len;
+
+      while self.count >= 8 {
+         self.data.push(self.bits as u8);
+         self.bits >>= 8;
+         self.count -= 8;
+      }
+   }
+
+   /// Pads the last byte with zero bits.
+   #[inline]
+   fn align(&mut self) {
+      if self.count > 0 {
+         self.write(0, 8 - self.count);
+      }
+   }
+
+   fn finish(mut self) -> Vec<u8> {
+      self.align();
+      self.data
+   }
+}
+
+#[cfg(test)]
+pub mod tests {
+   use std::collections::HashMap;
+
+   use super::{zlib_compress, adler32, fixed_lengths, LENGTH_BASE, LENGTH_EXTRA, DISTANCE_BASE, DISTANCE_EXTRA, CODE_LENGTH_ORDER};
+
+   struct BitReader<'a> {
+      data: &'a [u8],
+      pos: usize,
+   }
+
+   impl<'a> BitReader<'a> {
+      fn bits(&mut self, len: usize) -> usize {
+         let mut value = 0;
+         for i in 0..len {
+            value |= ((self.data[self.pos / 8] >> (self.pos % 8)) as usize & 1) << i;
+            self.pos += 1;
+         }
+         value
+      }
+
+      fn symbol(&mut self, codes: &HashMap<(usize, usize), usize>) -> usize {
+         let mut code = 0;
+         for len in 1..16 {
+            code = code << 1 | self.bits(1);
+            if let Some(&symbol) = codes.get(&(len, code)) {
+               return symbol;
+            }
+         }
+         panic!("Invalid code");
+      }
+   }
+
+   /// Symbols by code length and canonical code.
+   fn decode_table(lengths: &[u8]) -> HashMap<(usize, usize), usize> {
+      let mut codes = HashMap::new();
+      let mut code = 0;
+
+      for len in 1..16 {
+         for (symbol, _) in lengths.iter().enumerate().filter(|&(_, &other)| other as usize == len) {
+            codes.insert((len, code), symbol);
+            code += 1;
+         }
+         code <<= 1;
+      }
+
+      codes
+   }
+
+   /// Straightforward inflate to check the compressor against.
+   pub fn zlib_decompress(stream: &[u8]) -> Vec<u8> {
+      assert_eq!((stream[0] as u32 * 256 + stream[1] as u32) % 31, 0);
+
+      let mut reader = BitReader { data: &stream[2..], pos: 0 };
+      let mut data = Vec::new();
+
+      loop {
+         let last = reader.bits(1);
+
+         let (literals, distances) = match reader.bits(2) {
+            0 => {
+               reader.pos = reader.pos.div_ceil(8) * 8;
+               let len = reader.bits(16);
+               assert_eq!(reader.bits(16), !len & 0xffff);
+               for _ in 0..len {
+                  data.push(reader.bits(8) as u8);
+               }
+               if last == 1 { break } else { continue }
+            },
+            1 => fixed_lengths(),
+            2 => {
+               let (literal_count, distance_count) = (reader.bits(5) + 257, reader.bits(5) + 1);
+               let mut code_lengths = [0u8; 19];
+               for &symbol in &CODE_LENGTH_ORDER[..reader.bits(4) + 4] {
+                  code_lengths[symbol] = reader.bits(3) as u8;
+               }
+
+               let code_length_table = decode_table(&code_lengths);
+               let mut lengths: Vec<u8> = Vec::new();
+               while lengths.len() < literal_count + distance_count {
+                  match reader.symbol(&code_length_table) {
+                     16 => {
+                        let previous = *lengths.last().unwrap();
+                        for _ in 0..reader.bits(2) + 3 { lengths.push(previous); }
+                     },
+                     17 => for _ in 0..reader.bits(3) + 3 { lengths.push(0); },
+                     18 => for _ in 0..reader.bits(7) + 11 { lengths.push(0); },
+                     length => lengths.push(length as u8),
+                  }
+               }
+
+               let distances = lengths.split_off(literal_count);
+               (lengths, distances)
+            },
+            _ => panic!("Invalid block type"),
+         };
+
+         let (literals, distances) = (decode_table(&literals), decode_table(&distances));
+
+         loop {
+            let symbol = reader.symbol(&literals);
+            if symbol == 256 {
+               break;
+            }
+
+            if symbol < 256 {
+               data.push(symbol as u8);
+               continue;
+            }
+
+            let code = symbol - 257;
+            let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as usize);
+
+            let code = reader.symbol(&distances);
+            let distance = DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code] as usize);
+
+            for _ in 0..length {
+               data.push(data[data.len() - distance]);
+            }
+         }
+
+         if last == 1 {
+            break;
+         }
+      }
+
+      let end = 2 + reader.pos.div_ceil(8);
+      assert_eq!(stream[end..], adler32(&data).to_be_bytes());
+
+      data
+   }
+
+   #[test]
+   fn test_zlib_round_trip() {
+      assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+
+      let mut noise = Vec::new();
+      let mut seed = 1u32;
+      for _ in 0..70000 {
+         seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+         noise.push((seed >> 16) as u8);
+      }
+
+      let text = b"To be, or not to be, that is the question: whether 'tis nobler in the mind to suffer".repeat(50);
+
+      let mut mixed = noise[..5000].to_vec();
+      mixed.extend_from_slice(&[7; 1000]);
+      mixed.extend_from_slice(&text);
+      mixed.extend_from_slice(&noise[..5000]);
+
+      for data in &[Vec::new(), b"a".to_vec(), vec![0; 70000], noise, text, mixed] {
+         for level in 0..10 {
+            let stream = zlib_compress(data, level);
+            assert_eq!(&zlib_decompress(&stream), data, "level {}", level);
+
+            // Incompressible data falls back to stored blocks
+            assert!(stream.len() <= data.len() + 5 * (data.len() / 32768 + 1) + 6);
+         }
+      }
+
+      assert!(zlib_compress(&[0; 70000], 6).len() < 200);
+   }
+}
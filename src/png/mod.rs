@@ -0,0 +1,366 @@
+pub mod deflate;
+
+use std::fs;
+
+use error::{RuntimeError, ErrorKind};
+use frame::Frame;
+use raster::Image;
+
+pub use self::deflate::{zlib_compress, adler32};
+
+
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Pixel layout of the encoded image, 8 bits per channel.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColorType {
+   #[default]
+   Rgb,
+   Rgba,
+}
+
+impl ColorType {
+   #[inline]
+   pub fn channels(&self) -> usize {
+      match *self {
+         ColorType::Rgb => 3,
+         ColorType::Rgba => 4,
+      }
+   }
+
+   /// Color type number in the PNG header.
+   #[inline]
+   fn code(&self) -> u8 {
+      match *self {
+         ColorType::Rgb => 2,
+         ColorType::Rgba => 6,
+      }
+   }
+}
+
+/// Writes 8-bit truecolor PNG files, without interlacing or ancillary
+/// chunks.
+#[derive(Debug, Clone)]
+pub struct PngEncoder {
+   pub color_type: ColorType,
+   /// Deflate level from 0 (stored) to 9 (slowest, smallest).
+   pub level: u8,
+}
+
+impl PngEncoder {
+   #[inline]
+   pub fn new(color_type: ColorType) -> Self {
+      PngEncoder {
+         color_type,
+         level: 6,
+      }
+   }
+
+   /// Encodes pixels given row by row from the top, each with the channels
+   /// of the color type.
+   pub fn encode(&self, width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+      if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+         return Err(RuntimeError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid PNG size {}x{}", width, height)
+         ));
+      }
+
+      let stride = width as usize * self.color_type.channels();
+
+      if pixels.len() != stride * height as usize {
+         return Err(RuntimeError::new(
+            ErrorKind::InvalidInput,
+            format!("Expected {} bytes of pixels for {}x{}, got {}", stride * height as usize, width, height, pixels.len())
+         ));
+      }
+
+      let mut header = Vec::with_capacity(13);
+      header.extend_from_slice(&width.to_be_bytes());
+      header.extend_from_slice(&height.to_be_bytes());
+      header.extend_from_slice(&[8, self.color_type.code(), 0, 0, 0]);
+
+      let filtered = self.filter(pixels, stride);
+
+      let mut png = PNG_SIGNATURE.to_vec();
+      write_chunk(&mut png, b"IHDR", &header);
+      write_chunk(&mut png, b"IDAT", &zlib_compress(&filtered, self.level));
+      write_chunk(&mut png, b"IEND", &[]);
+
+      Ok(png)
+   }
+
+   /// Encodes the frame contents, which are opaque when written with alpha.
+   pub fn encode_frame(&self, frame: &Frame) -> Result<Vec<u8>, RuntimeError> {
+      let mut pixels = Vec::with_capacity(frame.width as usize * frame.height as usize * self.color_type.channels());
+
+      // Frame rows start at the bottom
+      for y in (0..frame.height as i32).rev() {
+         for x in 0..frame.width as i32 {
+            let color = frame.get_pixel(x, y);
+            pixels.extend_from_slice(&[color.r, color.g, color.b]);
+
+            if self.color_type == ColorType::Rgba {
+               pixels.push(255);
+            }
+         }
+      }
+
+      self.encode(frame.width, frame.height, &pixels)
+   }
+
+   /// Encodes the image, dropping its alpha channel when written as RGB.
+   pub fn encode_image(&self, image: &Image) -> Result<Vec<u8>, RuntimeError> {
+      let mut pixels = Vec::with_capacity(image.data.len() * self.color_type.channels());
+
+      for color in &image.data {
+         pixels.extend_from_slice(&[color.r, color.g, color.b]);
+
+         if self.color_type == ColorType::Rgba {
+            pixels.push(color.a);
+         }
+      }
+
+      self.encode(image.width as u32, image.height as u32, &pixels)
+   }
+
+   pub fn save_frame(&self, frame: &Frame, path: &str) -> Result<(), RuntimeError> {
+      let png = self.encode_frame(frame)?;
+
+      fs::write(path, png).map_err(|err| RuntimeError::new(
+         ErrorKind::Other,
+         format!("Cannot write PNG {}: {}", path, err)
+      ))
+   }
+
+   /// Prefixes every row with a filter type and filters it, picking for
+   /// each row the filter with the smallest sum of absolute differences.
+   /// Level 0 leaves the rows unfiltered.
+   fn filter(&self, pixels: &[u8], stride: usize) -> Vec<u8> {
+      let bpp = self.color_type.channels();
+      let rows = pixels.len() / stride;
+
+      let mut filtered = Vec::with_capacity((stride + 1) * rows);
+      let mut candidates: Vec<Vec<u8>> = (0..5).map(|_| Vec::with_capacity(stride)).collect();
+      let zeros = vec![0; stride];
+
+      for (row, line) in pixels.chunks(stride).enumerate() {
+         let above = if row > 0 { &pixels[(row - 1) * stride..row * stride] } else { &zeros[..] };
+
+         for (filter, candidate) in candidates.iter_mut().enumerate() {
+            candidate.clear();
+
+            for i in 0..stride {
+               let left = if i >= bpp { line[i - bpp] } else { 0 };
+               let up_left = if i >= bpp { above[i - bpp] } else { 0 };
+
+               let predicted = match filter {
+                  0 => 0,
+                  1 => left,
+                  2 => above[i],
+                  3 => ((left as u16 + above[i] as u16) / 2) as u8,
+                  _ => paeth(left, above[i], up_left),
+               };
+
+               candidate.push(line[i].wrapping_sub(predicted));
+            }
+
+            if self.level == 0 {
+               break;
+            }
+         }
+
+         let best = if self.level == 0 {
+            0
+         } else {
+            (0..candidates.len()).min_by_key(|&filter| {
+               candidates[filter].iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum::<u64>()
+            }).unwrap_or(0)
+         };
+
+         filtered.push(best as u8);
+         filtered.extend_from_slice(&candidates[best]);
+      }
+
+      filtered
+   }
+}
+
+impl Default for PngEncoder {
+   fn default() -> PngEncoder {
+      PngEncoder::new(ColorType::Rgb)
+   }
+}
+
+/// Neighbour closest to the gradient prediction `left + above - up_left`.
+#[inline]
+fn paeth(left: u8, above: u8, up_left: u8) -> u8 {
+   let estimate = left as i16 + above as i16 - up_left as i16;
+
+   let distance_left = (estimate - left as i16).abs();
+   let distance_above = (estimate - above as i16).abs();
+   let distance_up_left = (estimate - up_left as i16).abs();
+
+   if distance_left <= distance_above && distance_left <= distance_up_left {
+      left
+   } else if distance_above <= distance_up_left {
+      above
+   } else {
+      up_left
+   }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+   png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+   let start = png.len();
+   png.extend_from_slice(kind);
+   png.extend_from_slice(data);
+
+   let crc = crc32(&png[start..]);
+   png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// CRC-32 as used by PNG chunks and zip files.
+pub fn crc32(data: &[u8]) -> u32 {
+   !data.iter().fold(!0u32, |crc, &byte| {
+      CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ crc >> 8
+   })
+}
+
+const fn crc_table() -> [u32; 256] {
+   let mut table = [0u32; 256];
+   let mut i = 0;
+
+   while i < 256 {
+      let mut crc = i as u32;
+      let mut bit = 0;
+
+      while bit < 8 {
+         crc = if crc & 1 != 0 { 0xedb88320 ^ crc >> 1 } else { crc >> 1 };
+         bit += 1;
+      }
+
+      table[i] = crc;
+      i += 1;
+   }
+
+   table
+}
+
+#[cfg(test)]
+mod tests {
+   use draw::{RGB, RGBA};
+   use frame::Frame;
+   use raster::Image;
+
+   use super::{PngEncoder, ColorType, PNG_SIGNATURE, crc32, adler32, paeth};
+   use super::deflate::tests::zlib_decompress;
+
+   #[test]
+   fn test_encode_frame() {
+      assert_eq!(crc32(b"IEND"), 0xae426082);
+
+      let mut frame = Frame::new(2, 2);
+      frame.put_pixel(0, 1, &RGB::new(255, 0, 0));
+      frame.put_pixel(1, 0, &RGB::new(0, 0, 255));
+
+      let mut encoder = PngEncoder::new(ColorType::Rgb);
+      encoder.level = 0;
+
+      let png = encoder.encode_frame(&frame).unwrap();
+
+      assert!(png.starts_with(PNG_SIGNATURE));
+      assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+      assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+      assert_eq!(png[29..33], crc32(&png[12..29]).to_be_bytes());
+      assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+
+      // Unfiltered rows from the top inside a single stored block
+      let rows = [0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255];
+      assert_eq!(&png[33..37], &(2 + 5 + rows.len() as u32 + 4).to_be_bytes());
+      assert_eq!(&png[41..48], &[0x78, 0x01, 1, 14, 0, !14, 0xff]);
+      assert_eq!(&png[48..62], &rows);
+      assert_eq!(png[62..66], adler32(&rows).to_be_bytes());
+
+      let mut image = Image::new(3, 1);
+      image.set_pixel(1, 0, RGBA::new(10, 20, 30, 40));
+
+      let rgba = PngEncoder::new(ColorType::Rgba).encode_image(&image).unwrap();
+      assert_eq!(rgba[25], 6);
+
+      assert!(encoder.encode(2, 2, &[0; 11]).is_err());
+      assert!(encoder.encode(0, 2, &[]).is_err());
+   }
+
+   #[test]
+   fn test_filter_round_trip() {
+      let (width, height) = (37, 23);
+      let mut image = Image::new(width, height);
+
+      // Gradients, noise and flat areas so that every filter gets picked
+      let mut seed = 12345u32;
+
+      for y in 0..height {
+         for x in 0..width {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+
+            let color = if y < 8 {
+               RGBA::new((x * 7) as u8, (y * 11) as u8, (x + y) as u8, 255)
+            } else if y < 16 {
+               RGBA::new((seed >> 16) as u8, (seed >> 8) as u8, seed as u8, (seed >> 24) as u8)
+            } else {
+               RGBA::new(200, 100, 50, (x * 3) as u8)
+            };
+
+            image.set_pixel(x, y, color);
+         }
+      }
+
+      let encoder = PngEncoder::new(ColorType::Rgba);
+      assert_eq!(encoder.level, 6);
+
+      let png = encoder.encode_image(&image).unwrap();
+
+      let length = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+      assert_eq!(&png[37..41], b"IDAT");
+
+      let filtered = zlib_decompress(&png[41..41 + length]);
+
+      let bpp = 4;
+      let stride = width * bpp;
+      assert_eq!(filtered.len(), (stride + 1) * height);
+
+      let mut pixels: Vec<u8> = Vec::with_capacity(stride * height);
+      let mut used = [false; 5];
+
+      for (row, line) in filtered.chunks(stride + 1).enumerate() {
+         let filter = line[0] as usize;
+         assert!(filter < 5);
+         used[filter] = true;
+
+         for i in 0..stride {
+            let left = if i >= bpp { pixels[row * stride + i - bpp] } else { 0 };
+            let above = if row > 0 { pixels[(row - 1) * stride + i] } else { 0 };
+            let up_left = if row > 0 && i >= bpp { pixels[(row - 1) * stride + i - bpp] } else { 0 };
+
+            let predicted = match filter {
+               0 => 0,
+               1 => left,
+               2 => above,
+               3 => ((left as u16 + above as u16) / 2) as u8,
+               _ => paeth(left, above, up_left),
+            };
+
+            pixels.push(line[1 + i].wrapping_add(predicted));
+         }
+      }
+
+      assert!(used[1..].iter().filter(|&&used| used).count() >= 2);
+
+      let original: Vec<u8> = image.data.iter().flat_map(|color| vec![color.r, color.g, color.b, color.a]).collect();
+      assert_eq!(pixels, original);
+   }
+}
@@ -0,0 +1,1059 @@
+use std::cmp::{min, max, Ordering};
+use std::collections::{BTreeSet, HashMap};
+use std::f64::consts::FRAC_PI_2;
+
+use error::RuntimeError;
+use geom::point::Point;
+use draw::RGBA;
+use num::NumberOps;
+
+use super::path::{Path, PathEdge, Contour, FillRule, arc_pieces, arc_sweep, flatten_bezier};
+use super::scene::{Scene, SceneBuilder};
+use super::paint::Paint;
+
+
+/// Default distance in world units Bézier curves are flattened within.
+pub const FLATTEN_TOLERANCE: f64 = 100.;
+
+/// Slack on the quadrant sweep of an arc side for its rounded end points.
+const ROUNDING_SWEEP: f64 = 0.01;
+
+/// Least bulge off its chord an arc side keeps. Rounded end points leave the
+/// circle up to a unit away, flatter arcs could end up on the wrong side of
+/// their neighbours.
+const MIN_SAGITTA: f64 = 1.;
+
+/// Heights across a face its sides are compared at.
+const FIT_SAMPLES: usize = 8;
+
+/// Part of a shape outline that is monotone in y, from its lower point `p1`
+/// to its upper point `p2`. Arc spans lie within one circle quadrant.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+   p1: Point,
+   p2: Point,
+   arc: Option<(Point, i64)>,
+   winding: i32,
+   shape: usize,
+}
+
+impl Span {
+   #[inline]
+   fn left_half(&self, center: &Point) -> bool {
+      self.p1.x + self.p2.x < 2 * center.x
+   }
+
+   #[inline]
+   fn min_x(&self) -> i64 {
+      min(self.p1.x, self.p2.x)
+   }
+
+   #[inline]
+   fn max_x(&self) -> i64 {
+      max(self.p1.x, self.p2.x)
+   }
+
+   /// X at a horizontal line through the span, rounded the same way the
+   /// rasterizer intersects it.
+   fn x_at(&self, y: i64) -> i64 {
+      if y <= self.p1.y {
+         return self.p1.x;
+      }
+
+      if y >= self.p2.y {
+         return self.p2.x;
+      }
+
+      let x = match self.arc {
+         Some((center, radius)) => {
+            let dy = y - center.y;
+            let dx = max(0, radius * radius - dy * dy).sqrt();
+
+            if self.left_half(&center) { center.x - dx } else { center.x + dx }
+         },
+         None => {
+            self.p1.x + ((self.p2.x - self.p1.x) * (y - self.p1.y)).rounding_idiv(self.p2.y - self.p1.y)
+         },
+      };
+
+      max(self.min_x(), min(self.max_x(), x))
+   }
+
+   fn x_at_f64(&self, y: f64) -> f64 {
+      match self.arc {
+         Some((center, radius)) => {
+            let dy = y - center.y as f64;
+            let dx = (radius as f64 * radius as f64 - dy * dy).max(0.).sqrt();
+
+            if self.left_half(&center) { center.x as f64 - dx } else { center.x as f64 + dx }
+         },
+         None => {
+            let t = (y - self.p1.y as f64) / (self.p2.y - self.p1.y) as f64;
+
+            self.p1.x as f64 + t * (self.p2.x - self.p1.x) as f64
+         },
+      }
+   }
+
+   /// Whether a point of the span's line or circle lies on the span itself,
+   /// allowing a unit for rounded end points.
+   #[inline]
+   fn contains(&self, x: f64, y: f64) -> bool {
+      y >= (self.p1.y - 1) as f64 && y <= (self.p2.y + 1) as f64 &&
+      x >= (self.min_x() - 1) as f64 && x <= (self.max_x() + 1) as f64
+   }
+}
+
+/// Face of the subdivision between two spans, within a range of slabs.
+#[derive(Debug, Clone, Copy)]
+struct Face {
+   left: usize,
+   right: usize,
+   shape: usize,
+   y0: i64,
+   y1: i64,
+   left_x0: i64,
+   left_x1: i64,
+   right_x0: i64,
+   right_x1: i64,
+   exact: bool,
+}
+
+impl Face {
+   #[inline]
+   fn new(left: &Column, right: &Column, shape: usize, y0: i64, y1: i64) -> Self {
+      Face {
+         left: left.span,
+         right: right.span,
+         shape,
+         y0,
+         y1,
+         left_x0: left.x0,
+         left_x1: left.x1,
+         right_x0: right.x0,
+         right_x1: right.x1,
+         exact: left.exact && right.exact,
+      }
+   }
+}
+
+/// Active span with its x at the bottom and top of a slab.
+#[derive(Debug, Clone, Copy)]
+struct Column {
+   span: usize,
+   x0: i64,
+   x1: i64,
+   exact: bool,
+}
+
+/// Decides which shape shows in each gap between the spans of a slab, as
+/// the sweep crosses them from left to right.
+trait Coverage {
+   /// Starts a new slab left of all spans.
+   fn reset(&mut self);
+
+   /// Crosses a span of `shape`, leaving its winding number at `winding`.
+   fn cross(&mut self, shape: usize, winding: i32);
+
+   /// Shape showing right of the spans crossed so far, if any.
+   fn top(&self) -> Option<usize>;
+
+   /// Whether neighbouring faces of two shapes can be merged.
+   fn same(&self, shape1: usize, shape2: usize) -> bool;
+}
+
+/// Compiles overlapping filled paths given in painter's order into a `Scene`
+/// of non-overlapping polys, as the rasterizer expects. Every face of the
+/// planar subdivision gets the paint of the topmost path covering it by the
+/// path's fill rule, and neighbouring faces share their segments. Contours
+/// may cross themselves and each other.
+///
+/// Paths can be clipped to other paths. Clip outlines take part in the
+/// subdivision like any path, so the clipped result is exact geometry rather
+/// than a pixel mask. Existing scenes are clipped by adding them with
+/// `add_scene`.
+///
+/// Arcs are kept, Bézier curves are flattened to lines within `tolerance`.
+pub struct SceneCompiler {
+   shapes: Vec<Shape>,
+   clips: Vec<Path>,
+   clip: usize,
+   pub tolerance: f64,
+}
+
+/// Path added to a `SceneCompiler` with its fill and the clip applying to
+/// it, `usize::MAX` for none.
+struct Shape {
+   path: Path,
+   paint: Paint,
+   opacity: u8,
+   clip: usize,
+}
+
+impl SceneCompiler {
+   #[inline]
+   pub fn new() -> Self {
+      SceneCompiler {
+         shapes: Vec::new(),
+         clips: Vec::new(),
+         clip: usize::MAX,
+         tolerance: FLATTEN_TOLERANCE,
+      }
+   }
+
+   /// Adds a filled path above all paths added before.
+   pub fn add_path(&mut self, path: &Path, color: RGBA) {
+      self.add_path_paint(path, Paint::Solid(color));
+   }
+
+   /// Adds `path` as `add_path` does, filled with `paint`.
+   pub fn add_path_paint(&mut self, path: &Path, paint: Paint) {
+      self.add_shape(path.clone(), paint, 255);
+   }
+
+   /// Adds the polys of `scene` in their order above all paths added
+   /// before, keeping their paint and opacity. Combined with `set_clip`
+   /// this clips a scene built by other means.
+   pub fn add_scene(&mut self, scene: &Scene) {
+      for poly in &scene.polys {
+         if let Some(paint) = scene.paints.get(poly.paint) {
+            self.add_shape(scene.poly_path(poly), paint.clone(), poly.opacity);
+         }
+      }
+   }
+
+   fn add_shape(&mut self, path: Path, paint: Paint, opacity: u8) {
+      self.shapes.push(Shape {
+         path,
+         paint,
+         opacity,
+         clip: self.clip,
+      });
+   }
+
+   /// Clips the paths added from now on to the inside of `clip` by its fill
+   /// rule, or stops clipping with `None`.
+   pub fn set_clip(&mut self, clip: Option<&Path>) {
+      self.clip = match clip {
+         Some(path) => {
+            self.clips.push(path.clone());
+            self.clips.len() - 1
+         },
+         None => usize::MAX,
+      };
+   }
+
+   /// Resolves the added paths into a `Scene`. Fails if a resolved face
+   /// cannot be turned into monotone polygons.
+   pub fn compile(&self) -> Result<Scene, RuntimeError> {
+      let mut spans = Vec::new();
+
+      for (index, shape) in self.shapes.iter().enumerate() {
+         add_path_spans(&mut spans, &shape.path, index, self.tolerance);
+      }
+
+      // Clip outlines follow the shapes in the winding indices
+      for (clip, path) in self.clips.iter().enumerate() {
+         add_path_spans(&mut spans, path, self.shapes.len() + clip, self.tolerance);
+      }
+
+      let mut painter = Painter {
+         compiler: self,
+         covering: BTreeSet::new(),
+         clipping: vec![false; self.clips.len()],
+      };
+
+      let mut builder = SceneBuilder::new();
+
+      let paints: Vec<usize> = self.shapes.iter().map(|shape| builder.add_paint(shape.paint.clone())).collect();
+
+      let mut opacities = Vec::new();
+
+      for (contour, shape) in subdivide(spans, &mut painter) {
+         if builder.add_contour(&contour, paints[shape])? {
+            opacities.push(self.shapes[shape].opacity);
+         }
+      }
+
+      let mut scene = builder.build();
+
+      for (poly, opacity) in scene.polys.iter_mut().zip(opacities) {
+         poly.opacity = opacity;
+      }
+
+      Ok(scene)
+   }
+
+   #[inline]
+   fn same_paint(&self, shape1: usize, shape2: usize) -> bool {
+      if shape1 == shape2 {
+         return true;
+      }
+
+      if self.shapes[shape1].opacity != self.shapes[shape2].opacity {
+         return false;
+      }
+
+      match (&self.shapes[shape1].paint, &self.shapes[shape2].paint) {
+         (&Paint::Solid(color1), &Paint::Solid(color2)) => color1 == color2,
+         _ => false,
+      }
+   }
+}
+
+impl Default for SceneCompiler {
+   fn default() -> SceneCompiler {
+      SceneCompiler::new()
+   }
+}
+
+/// Coverage of `SceneCompiler`: the topmost covering shape not clipped away.
+struct Painter<'a> {
+   compiler: &'a SceneCompiler,
+   covering: BTreeSet<usize>,
+   clipping: Vec<bool>,
+}
+
+impl<'a> Coverage for Painter<'a> {
+   fn reset(&mut self) {
+      self.covering.clear();
+
+      for inside in self.clipping.iter_mut() {
+         *inside = false;
+      }
+   }
+
+   fn cross(&mut self, shape: usize, winding: i32) {
+      let shapes = &self.compiler.shapes;
+
+      if shape >= shapes.len() {
+         let clip = shape - shapes.len();
+         self.clipping[clip] = self.compiler.clips[clip].fill_rule.inside(winding);
+      } else if shapes[shape].path.fill_rule.inside(winding) {
+         self.covering.insert(shape);
+      } else {
+         self.covering.remove(&shape);
+      }
+   }
+
+   fn top(&self) -> Option<usize> {
+      self.covering.iter().rev().find(|&&shape| {
+         let clip = self.compiler.shapes[shape].clip;
+         clip == usize::MAX || self.clipping[clip]
+      }).cloned()
+   }
+
+   #[inline]
+   fn same(&self, shape1: usize, shape2: usize) -> bool {
+      self.compiler.same_paint(shape1, shape2)
+   }
+}
+
+/// Boolean operation combining the areas of two paths.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BooleanOp {
+   Union,
+   Intersection,
+   Difference,
+   Xor,
+}
+
+impl BooleanOp {
+   /// Whether a point inside `a` and `b` as given is inside the result.
+   #[inline]
+   pub fn apply(&self, a: bool, b: bool) -> bool {
+      match *self {
+         BooleanOp::Union => a || b,
+         BooleanOp::Intersection => a && b,
+         BooleanOp::Difference => a && !b,
+         BooleanOp::Xor => a != b,
+      }
+   }
+}
+
+/// Combines the areas of two closed paths of lines and arcs, each inside by
+/// its own fill rule. Crossings are resolved exactly on the integer grid, and
+/// the result is a path of non-overlapping, clockwise and orthogonally convex
+/// contours that `SceneBuilder` takes as is. Bézier curves are flattened.
+pub fn boolean(a: &Path, b: &Path, op: BooleanOp) -> Path {
+   let mut spans = Vec::new();
+
+   add_path_spans(&mut spans, a, 0, FLATTEN_TOLERANCE);
+   add_path_spans(&mut spans, b, 1, FLATTEN_TOLERANCE);
+
+   let mut combine = Combine {
+      op,
+      fill_rules: [a.fill_rule, b.fill_rule],
+      inside: [false, false],
+   };
+
+   let mut path = Path::new();
+
+   path.contours = subdivide(spans, &mut combine).into_iter().map(|(contour, _)| contour).collect();
+
+   path
+}
+
+/// Coverage of `boolean`, with the two paths as shapes 0 and 1.
+struct Combine {
+   op: BooleanOp,
+   fill_rules: [FillRule; 2],
+   inside: [bool; 2],
+}
+
+impl Coverage for Combine {
+   fn reset(&mut self) {
+      self.inside = [false, false];
+   }
+
+   fn cross(&mut self, shape: usize, winding: i32) {
+      self.inside[shape] = self.fill_rules[shape].inside(winding);
+   }
+
+   fn top(&self) -> Option<usize> {
+      if self.op.apply(self.inside[0], self.inside[1]) { Some(0) } else { None }
+   }
+
+   #[inline]
+   fn same(&self, _shape1: usize, _shape2: usize) -> bool {
+      true
+   }
+}
+
+/// Sweeps the spans in horizontal slabs and returns the faces of the planar
+/// subdivision as clockwise contours, each with the shape showing in it.
+/// Faces of the same shape are merged upwards while their sides stay exact.
+fn subdivide<C: Coverage>(mut spans: Vec<Span>, coverage: &mut C) -> Vec<(Contour, usize)> {
+   spans.sort_by_key(|span| span.p1.y);
+
+   // Span ends, with crossings added as the sweep finds them
+   let mut events: BTreeSet<i64> = spans.iter().flat_map(|span| vec![span.p1.y, span.p2.y]).collect();
+
+   let mut contours = Vec::new();
+
+   let mut open: HashMap<(usize, usize), Face> = HashMap::new();
+
+   let mut active: Vec<usize> = Vec::new();
+   let mut next = 0;
+
+   let mut y0 = match events.iter().next() {
+      Some(&y) => y,
+      None => return contours,
+   };
+
+   while let Some(&y1) = events.range(y0 + 1..).next() {
+      while next < spans.len() && spans[next].p1.y <= y0 {
+         active.push(next);
+         next += 1;
+      }
+
+      active.retain(|&i| spans[i].p2.y > y0);
+
+      let y1 = match first_crossing(&spans, &mut active, y0, y1) {
+         Some(y) => {
+            let (below, above) = (y.floor() as i64, y.ceil() as i64);
+
+            events.insert(below);
+            events.insert(above);
+
+            if below > y0 { below } else { above }
+         },
+         None => y1,
+      };
+
+      let faces = slab_faces(&spans, &mut active, y0, y1, coverage);
+
+      let mut closed = open;
+      open = HashMap::new();
+
+      for mut face in faces {
+         if let Some(below) = closed.remove(&(face.left, face.right)) {
+            if below.exact && face.exact && coverage.same(below.shape, face.shape) {
+               face.y0 = below.y0;
+               face.left_x0 = below.left_x0;
+               face.right_x0 = below.right_x0;
+               face.shape = below.shape;
+            } else {
+               emit(&mut contours, &spans, &below);
+            }
+         }
+
+         open.insert((face.left, face.right), face);
+      }
+
+      for face in closed.values() {
+         emit(&mut contours, &spans, face);
+      }
+
+      y0 = y1;
+   }
+
+   for face in open.values() {
+      emit(&mut contours, &spans, face);
+   }
+
+   contours
+}
+
+/// Faces between the active spans within the slab from `y0` to `y1`,
+/// with neighbouring gaps of the same shape merged.
+fn slab_faces<C: Coverage>(spans: &[Span], active: &mut [usize], y0: i64, y1: i64, coverage: &mut C) -> Vec<Face> {
+   let middle = (y0 + y1) as f64 / 2.;
+
+   active.sort_by(|&a, &b| {
+      spans[a].x_at_f64(middle).partial_cmp(&spans[b].x_at_f64(middle)).unwrap_or(Ordering::Equal)
+   });
+
+   // Rounding must not let spans cross within the slab
+   let mut columns: Vec<Column> = Vec::with_capacity(active.len());
+
+   for &span_index in active.iter() {
+      let span = &spans[span_index];
+
+      let mut column = Column {
+         span: span_index,
+         x0: span.x_at(y0),
+         x1: span.x_at(y1),
+         exact: true,
+      };
+
+      if let Some(previous) = columns.last() {
+         if column.x0 < previous.x0 {
+            column.x0 = previous.x0;
+            column.exact = false;
+         }
+         if column.x1 < previous.x1 {
+            column.x1 = previous.x1;
+            column.exact = false;
+         }
+      }
+
+      columns.push(column);
+   }
+
+   let mut faces = Vec::new();
+
+   let mut windings: HashMap<usize, i32> = HashMap::new();
+
+   coverage.reset();
+
+   let mut run: Option<(usize, usize)> = None;
+
+   for i in 0..active.len() {
+      let span = &spans[active[i]];
+
+      let winding = {
+         let winding = windings.entry(span.shape).or_insert(0);
+         *winding += span.winding;
+         *winding
+      };
+
+      coverage.cross(span.shape, winding);
+
+      let top = if i + 1 < active.len() { coverage.top() } else { None };
+
+      run = match (run, top) {
+         (Some((start, shape)), Some(top)) if coverage.same(shape, top) => Some((start, shape)),
+         (previous, top) => {
+            if let Some((start, shape)) = previous {
+               faces.push(Face::new(&columns[start], &columns[i], shape, y0, y1));
+            }
+
+            top.map(|top| (i, top))
+         },
+      };
+   }
+
+   faces
+}
+
+/// Adds a face as a clockwise contour: up its left side, along its top and
+/// down its right side.
+fn emit(contours: &mut Vec<(Contour, usize)>, spans: &[Span], face: &Face) {
+   let l0 = Point::new(face.left_x0, face.y0);
+   let l1 = Point::new(face.left_x1, face.y1);
+   let r1 = Point::new(face.right_x1, face.y1);
+   let r0 = Point::new(face.right_x0, face.y0);
+
+   let mut left = side(&spans[face.left], &l0, &l1, true, face.exact);
+   let mut right = side(&spans[face.right], &r1, &r0, false, face.exact);
+
+   // Arcs bulging past the other side through rounding are kept as lines
+   if !sides_fit(spans, face, &left, &right) {
+      left = PathEdge::Line(l1);
+      right = PathEdge::Line(r0);
+   }
+
+   // Faces pinched at both ends only have area along arc sides
+   let curved = is_arc(&left) || is_arc(&right);
+
+   if l0 == r0 && l1 == r1 && !curved {
+      return;
+   }
+
+   let mut contour = Contour::new(l0);
+
+   if l0 != l1 {
+      contour.edges.push(left);
+   }
+
+   if l1 != r1 {
+      contour.edges.push(PathEdge::Line(r1));
+   }
+
+   if r1 != r0 {
+      contour.edges.push(right);
+   }
+
+   contour.closed = true;
+
+   contours.push((contour, face.shape));
+}
+
+#[inline]
+fn is_arc(edge: &PathEdge) -> bool {
+   matches!(*edge, PathEdge::Arc { .. })
+}
+
+/// Whether the left side of a face stays left of its right side, sampled
+/// across the face with the rounded end points the sides get.
+fn sides_fit(spans: &[Span], face: &Face, left: &PathEdge, right: &PathEdge) -> bool {
+   if !is_arc(left) && !is_arc(right) {
+      return true;
+   }
+
+   let height = (face.y1 - face.y0) as f64;
+
+   (1..FIT_SAMPLES).all(|i| {
+      let y = face.y0 as f64 + height * i as f64 / FIT_SAMPLES as f64;
+
+      let left_x = side_x(&spans[face.left], is_arc(left), face.left_x0, face.left_x1, face.y0, face.y1, y);
+      let right_x = side_x(&spans[face.right], is_arc(right), face.right_x0, face.right_x1, face.y0, face.y1, y);
+
+      left_x <= right_x
+   })
+}
+
+/// X of a face side at `y`, along its circle clamped between its end points
+/// as the rasterizer intersects arcs, or along the line between them.
+fn side_x(span: &Span, arc: bool, x0: i64, x1: i64, y0: i64, y1: i64, y: f64) -> f64 {
+   if arc {
+      span.x_at_f64(y).max(min(x0, x1) as f64).min(max(x0, x1) as f64)
+   } else {
+      x0 as f64 + (x1 - x0) as f64 * (y - y0 as f64) / (y1 - y0) as f64
+   }
+}
+
+fn add_path_spans(spans: &mut Vec<Span>, path: &Path, shape: usize, tolerance: f64) {
+   for contour in &path.contours {
+      let mut from = contour.start;
+
+      for edge in &contour.edges {
+         match *edge {
+            PathEdge::Line(ref end) => {
+               add_span(spans, &from, end, None, shape);
+            },
+            PathEdge::Arc { ref end, ref center, radius, clockwise } => {
+               let points = arc_pieces(&from, end, center, radius, clockwise);
+
+               for piece in points.windows(2) {
+                  add_span(spans, &piece[0], &piece[1], Some((*center, radius)), shape);
+               }
+            },
+            PathEdge::Quad { ref control, ref end } => {
+               add_flattened(spans, &[from, *control, *end], shape, tolerance);
+            },
+            PathEdge::Cubic { ref control1, ref control2, ref end } => {
+               add_flattened(spans, &[from, *control1, *control2, *end], shape, tolerance);
+            },
+         }
+
+         from = edge.end();
+      }
+
+      add_span(spans, &from, &contour.start, None, shape);
+   }
+}
+
+fn add_flattened(spans: &mut Vec<Span>, controls: &[Point], shape: usize, tolerance: f64) {
+   let mut from = controls[0];
+
+   for to in flatten_bezier(controls, tolerance) {
+      add_span(spans, &from, &to, None, shape);
+      from = to;
+   }
+}
+
+fn add_span(spans: &mut Vec<Span>, from: &Point, to: &Point, arc: Option<(Point, i64)>, shape: usize) {
+   if from.y == to.y {
+      return;
+   }
+
+   let (p1, p2, winding) = if from.y < to.y { (*from, *to, 1) } else { (*to, *from, -1) };
+
+   spans.push(Span { p1, p2, arc, winding, shape });
+}
+
+/// Edge along a span from `start` to `end`, keeping arcs unless rounding
+/// moved the ends off the circle. Arcs whose rounded ends no longer run
+/// the short way round within a quadrant become lines, so the edge keeps
+/// the direction the span has.
+fn side(span: &Span, start: &Point, end: &Point, up: bool, exact: bool) -> PathEdge {
+   match span.arc {
+      Some((center, radius)) if exact => {
+         let clockwise = up == span.left_half(&center);
+
+         let (dx, dy) = ((end.x - start.x) as f64, (end.y - start.y) as f64);
+         let half_chord = (dx * dx + dy * dy).sqrt() / 2.;
+         let sagitta = radius as f64 - (radius as f64 * radius as f64 - half_chord * half_chord).max(0.).sqrt();
+
+         if sagitta >= MIN_SAGITTA && arc_sweep(start, end, &center, clockwise) <= FRAC_PI_2 + ROUNDING_SWEEP {
+            PathEdge::Arc { end: *end, center, radius, clockwise }
+         } else {
+            PathEdge::Line(*end)
+         }
+      },
+      _ => PathEdge::Line(*end),
+   }
+}
+
+/// Lowest crossing within the slab from `y0` to `y1`, sorting the active
+/// spans by x at `y0`. Spans keep their order until neighbours cross, so only
+/// neighbours are checked.
+fn first_crossing(spans: &[Span], active: &mut [usize], y0: i64, y1: i64) -> Option<f64> {
+   let (bottom, top) = (y0 as f64, y1 as f64);
+
+   let key = |i: usize| (spans[i].x_at_f64(bottom), spans[i].x_at_f64(top));
+
+   active.sort_by(|&a, &b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+
+   let mut first: Option<f64> = None;
+
+   for pair in active.windows(2) {
+      let (a, b) = (&spans[pair[0]], &spans[pair[1]]);
+
+      if b.max_x() < a.min_x() || b.min_x() > a.max_x() {
+         continue;
+      }
+
+      for y in crossings(a, b) {
+         if y > bottom && y < top && first.is_none_or(|first| y < first) {
+            first = Some(y);
+         }
+      }
+   }
+
+   first
+}
+
+/// Y coordinates where two spans cross.
+fn crossings(a: &Span, b: &Span) -> Vec<f64> {
+   let points = match (a.arc, b.arc) {
+      (None, None) => line_line(a, b),
+      (Some((center, radius)), None) => line_circle(b, &center, radius),
+      (None, Some((center, radius))) => line_circle(a, &center, radius),
+      (Some((c1, r1)), Some((c2, r2))) => circle_circle(&c1, r1, &c2, r2),
+   };
+
+   points.into_iter()
+      .filter(|&(x, y)| a.contains(x, y) && b.contains(x, y))
+      .map(|(_, y)| y)
+      .collect()
+}
+
+fn line_line(a: &Span, b: &Span) -> Vec<(f64, f64)> {
+   let (rx, ry) = ((a.p2.x - a.p1.x) as i128, (a.p2.y - a.p1.y) as i128);
+   let (sx, sy) = ((b.p2.x - b.p1.x) as i128, (b.p2.y - b.p1.y) as i128);
+   let (qx, qy) = ((b.p1.x - a.p1.x) as i128, (b.p1.y - a.p1.y) as i128);
+
+   let denom = rx * sy - ry * sx;
+   if denom == 0 {
+      return Vec::new();
+   }
+
+   let t = (qx * sy - qy * sx) as f64 / denom as f64;
+
+   vec![(a.p1.x as f64 + t * rx as f64, a.p1.y as f64 + t * ry as f64)]
+}
+
+fn line_circle(line: &Span, center: &Point, radius: i64) -> Vec<(f64, f64)> {
+   let dx = (line.p2.x - line.p1.x) as f64;
+   let dy = (line.p2.y - line.p1.y) as f64;
+   let fx = (line.p1.x - center.x) as f64;
+   let fy = (line.p1.y - center.y) as f64;
+   let r = radius as f64;
+
+   let a = dx * dx + dy * dy;
+   let b = 2. * (fx * dx + fy * dy);
+   let c = fx * fx + fy * fy - r * r;
+
+   let disc = b * b - 4. * a * c;
+   if disc < 0. {
+      return Vec::new();
+   }
+
+   let root = disc.sqrt();
+
+   [(-b - root) / (2. * a), (-b + root) / (2. * a)].iter()
+      .map(|t| (line.p1.x as f64 + t * dx, line.p1.y as f64 + t * dy))
+      .collect()
+}
+
+fn circle_circle(c1: &Point, r1: i64, c2: &Point, r2: i64) -> Vec<(f64, f64)> {
+   let dx = (c2.x - c1.x) as f64;
+   let dy = (c2.y - c1.y) as f64;
+   let (r1, r2) = (r1 as f64, r2 as f64);
+
+   let d = (dx * dx + dy * dy).sqrt();
+   if d == 0. || d > r1 + r2 || d < (r1 - r2).abs() {
+      return Vec::new();
+   }
+
+   let a = (r1 * r1 - r2 * r2 + d * d) / (2. * d);
+   let h = (r1 * r1 - a * a).max(0.).sqrt();
+
+   let mx = c1.x as f64 + a * dx / d;
+   let my = c1.y as f64 + a * dy / d;
+
+   vec![
+      (mx - h * dy / d, my + h * dx / d),
+      (mx + h * dy / d, my - h * dx / d),
+   ]
+}
+
+#[cfg(test)]
+mod tests {
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+   use geom::point::Point;
+
+   use raster::{Rasterizer, SceneBuilder, Path, FillRule, Paint, Gradient};
+
+   use super::{SceneCompiler, BooleanOp, boolean};
+
+   fn rect(x1: i64, y1: i64, x2: i64, y2: i64) -> Path {
+      let mut path = Path::new();
+      path.move_to(x1, y1);
+      path.line_to(x1, y2);
+      path.line_to(x2, y2);
+      path.line_to(x2, y1);
+      path.close();
+      path
+   }
+
+   #[test]
+   fn test_compile_overlapping_rects() {
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&rect(1000, 1000, 5000, 5000), RGBA::new(255, 0, 0, 255));
+      compiler.add_path(&rect(3000, 3000, 7000, 7000), RGBA::new(0, 0, 255, 255));
+      compiler.add_path(&rect(4000, 4000, 6000, 6000), RGBA::new(0, 0, 255, 255));
+      let scene = compiler.compile().unwrap();
+
+      // Red below the blue rect, red beside it and the merged blue rect
+      assert_eq!(scene.polys.len(), 3);
+
+      let mut frame = Frame::new(8, 8);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 1), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(4, 4), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(4, 2), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(2, 4), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(6, 6), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(6, 2), RGB::default());
+   }
+
+   #[test]
+   fn test_compile_circle_over_rect() {
+      let mut circle = Path::new();
+      circle.move_to(9000, 6000);
+      circle.arc_to(9000, 6000, 6000, 6000, true);
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&rect(0, 0, 12000, 12000), RGBA::new(0, 255, 0, 255));
+      compiler.add_path(&rect(0, 0, 6000, 6000), RGBA::new(255, 0, 0, 255));
+      compiler.add_path(&circle, RGBA::new(0, 0, 255, 255));
+      let scene = compiler.compile().unwrap();
+
+      assert!(!scene.circles.is_empty());
+
+      let mut frame = Frame::new(12, 12);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 1), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(6, 6), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(4, 4), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(10, 10), RGB::new(0, 255, 0));
+
+      // Pixels on the circle are split between the colors without gaps
+      for y in 0..12 {
+         for x in 0..12 {
+            let pixel = frame.get_pixel(x, y);
+            let sum = pixel.r as i64 + pixel.g as i64 + pixel.b as i64;
+            assert!((sum - 255).abs() <= 2, "pixel {} {} is {:?}", x, y, pixel);
+         }
+      }
+   }
+
+   #[test]
+   fn test_compile_gradient() {
+      let mut gradient = Gradient::linear(Point::new(0, 0), Point::new(10000, 0));
+      gradient.add_stop(0., RGBA::new(0, 0, 0, 255));
+      gradient.add_stop(1., RGBA::new(200, 100, 250, 255));
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path_paint(&rect(0, 0, 10000, 2000), Paint::Gradient(gradient));
+      compiler.add_path(&rect(8000, 0, 10000, 1000), RGBA::new(255, 0, 0, 255));
+      let scene = compiler.compile().unwrap();
+
+      let mut frame = Frame::new(10, 2);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      // Faces cut from the gradient rect are painted as one
+      assert_eq!(frame.get_pixel(0, 1), RGB::new(10, 5, 13));
+      assert_eq!(frame.get_pixel(5, 0), RGB::new(110, 55, 138));
+      assert_eq!(frame.get_pixel(9, 1), RGB::new(190, 95, 238));
+      assert_eq!(frame.get_pixel(9, 0), RGB::new(255, 0, 0));
+   }
+
+   #[test]
+   fn test_compile_fill_rules() {
+      let mut star = Path::new();
+      star.move_to(10500, 18000);
+      star.line_to(15202, 3528);
+      star.line_to(2892, 12472);
+      star.line_to(18108, 12472);
+      star.line_to(5798, 3528);
+      star.close();
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&star, RGBA::new(255, 0, 0, 255));
+
+      let mut frame = Frame::new(21, 21);
+      Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(10, 9), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(10, 14), RGB::new(255, 0, 0));
+
+      star.fill_rule = FillRule::EvenOdd;
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&star, RGBA::new(255, 0, 0, 255));
+
+      let mut frame = Frame::new(21, 21);
+      Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(10, 9), RGB::default());
+      assert_eq!(frame.get_pixel(10, 14), RGB::new(255, 0, 0));
+   }
+
+   #[test]
+   fn test_compile_clip() {
+      let mut viewport = Path::new();
+      viewport.move_to(8000, 5000);
+      viewport.arc_to(8000, 5000, 5000, 5000, true);
+
+      let mut compiler = SceneCompiler::new();
+      compiler.set_clip(Some(&viewport));
+      compiler.add_path(&rect(0, 0, 10000, 10000), RGBA::new(255, 0, 0, 255));
+      compiler.add_path(&rect(5000, 0, 10000, 10000), RGBA::new(0, 0, 255, 255));
+      compiler.set_clip(None);
+      compiler.add_path(&rect(0, 9000, 1000, 10000), RGBA::new(0, 255, 0, 255));
+      let scene = compiler.compile().unwrap();
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(3, 5), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(6, 4), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(1, 1), RGB::default());
+      assert_eq!(frame.get_pixel(0, 9), RGB::new(0, 255, 0));
+
+      // The clipped content covers exactly the viewport circle
+      let mut covered = 0;
+      for y in 0..9 {
+         for x in 0..10 {
+            let pixel = frame.get_pixel(x, y);
+            covered += pixel.r as i64 + pixel.b as i64;
+         }
+      }
+
+      let area = covered * 1000 * 1000 / 255;
+      assert!((area - 28274334).abs() < 50000, "Clipped area {}", area);
+   }
+
+   #[test]
+   fn test_compile_clip_scene() {
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&rect(0, 0, 5000, 10000), RGBA::new(255, 0, 0, 255)).unwrap();
+
+      let mut ring = Path::new();
+      ring.move_to(8000, 5000);
+      ring.arc_to(8000, 5000, 6500, 5000, true);
+      ring.move_to(7000, 5000);
+      ring.arc_to(7000, 5000, 6500, 5000, false);
+      builder.add_path(&ring, RGBA::new(0, 0, 255, 255)).unwrap();
+
+      let mut scene = builder.build();
+      scene.polys[0].opacity = 102;
+
+      let mut viewport = Path::new();
+      viewport.move_to(8000, 5000);
+      viewport.arc_to(8000, 5000, 5000, 5000, true);
+
+      let mut compiler = SceneCompiler::new();
+      compiler.set_clip(Some(&viewport));
+      compiler.add_scene(&scene);
+      let clipped = compiler.compile().unwrap();
+
+      assert!(clipped.polys.iter().all(|poly| poly.holes.is_empty()));
+
+      let mut frame = Frame::new(100, 100);
+      Rasterizer::new().render(&clipped, &mut frame, 100).unwrap();
+
+      // The translucent rect only shows inside the viewport, the ring keeps
+      // its hole
+      assert_eq!(frame.get_pixel(30, 50), RGB::new(102, 0, 0));
+      assert_eq!(frame.get_pixel(10, 10), RGB::default());
+      assert_eq!(frame.get_pixel(76, 50), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(65, 50), RGB::default());
+   }
+
+   #[test]
+   fn test_boolean_ops() {
+      let square = rect(2000, 2000, 6000, 6000);
+
+      let mut circle = Path::new();
+      circle.move_to(8000, 4000);
+      circle.arc_to(8000, 4000, 6000, 4000, true);
+
+      let half_circle = 6283185;
+
+      let ops = [
+         (BooleanOp::Union, 16000000 + half_circle),
+         (BooleanOp::Intersection, half_circle),
+         (BooleanOp::Difference, 16000000 - half_circle),
+         (BooleanOp::Xor, 16000000),
+      ];
+
+      for &(op, expected) in &ops {
+         let result = boolean(&square, &circle, op);
+
+         let mut builder = SceneBuilder::new();
+         builder.add_path(&result, RGBA::new(255, 255, 255, 255)).unwrap();
+
+         let mut frame = Frame::new(100, 100);
+         Rasterizer::new().render(&builder.build(), &mut frame, 100).unwrap();
+
+         let mut covered = 0;
+         for y in 0..100 {
+            for x in 0..100 {
+               covered += frame.get_pixel(x, y).r as i64;
+            }
+         }
+
+         let area = covered * 100 * 100 / 255;
+         assert!((area - expected).abs() < 20000, "{:?} area {}", op, area);
+      }
+   }
+}
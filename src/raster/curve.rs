@@ -0,0 +1,406 @@
+use geom::point::Point;
+
+use super::edge::EdgeType;
+use super::scene::Scene;
+
+
+/// Control points of a Bézier edge. The curve runs from `segment.p1` to
+/// `segment.p2`, with `c1` next to `p1` and `c2` next to `p2`. Quadratic
+/// edges only use `c1`.
+#[derive(Debug, Clone, Copy)]
+pub struct Curve {
+   pub c1: usize,
+   pub c2: usize,
+}
+
+impl Curve {
+   #[inline]
+   pub fn new(c1: usize, c2: usize) -> Self {
+      Curve {
+         c1,
+         c2,
+      }
+   }
+
+   #[inline]
+   pub fn quadratic(c1: usize) -> Self {
+      Curve::new(c1, usize::MAX)
+   }
+}
+
+impl Default for Curve {
+   fn default() -> Curve {
+      Curve::new(usize::MAX, usize::MAX)
+   }
+}
+
+/// Cubic Bézier in power basis, relative to the curve start point to keep
+/// the floating point terms small. Quadratics are degree elevated.
+#[derive(Debug, Clone, Copy)]
+pub struct Bezier {
+   pub origin: Point,
+   x: [f64; 4],
+   y: [f64; 4],
+}
+
+impl Bezier {
+   pub fn quadratic(p1: &Point, c: &Point, p2: &Point) -> Self {
+      let (cx, cy) = rel(c, p1);
+      let (x2, y2) = rel(p2, p1);
+
+      Bezier::from_controls(
+         *p1,
+         [0., 2. * cx / 3., x2 + 2. * (cx - x2) / 3., x2],
+         [0., 2. * cy / 3., y2 + 2. * (cy - y2) / 3., y2],
+      )
+   }
+
+   pub fn cubic(p1: &Point, c1: &Point, c2: &Point, p2: &Point) -> Self {
+      let (c1x, c1y) = rel(c1, p1);
+      let (c2x, c2y) = rel(c2, p1);
+      let (x2, y2) = rel(p2, p1);
+
+      Bezier::from_controls(*p1, [0., c1x, c2x, x2], [0., c1y, c2y, y2])
+   }
+
+   /// Curve of a quadratic or cubic edge, running along its segment.
+   pub fn from_edge(edge_type: EdgeType, segment: usize, curve: usize, scene: &Scene) -> Self {
+      let segment = &scene.segments[segment];
+      let p1 = &scene.points[segment.p1];
+      let p2 = &scene.points[segment.p2];
+
+      let curve = &scene.curves[curve];
+      let c1 = &scene.points[curve.c1];
+
+      if edge_type.cubic() {
+         Bezier::cubic(p1, c1, &scene.points[curve.c2], p2)
+      } else {
+         Bezier::quadratic(p1, c1, p2)
+      }
+   }
+
+   fn from_controls(origin: Point, x: [f64; 4], y: [f64; 4]) -> Self {
+      Bezier {
+         origin,
+         x: power_basis(&x),
+         y: power_basis(&y),
+      }
+   }
+
+   #[inline]
+   pub fn x_at(&self, t: f64) -> f64 {
+      eval(&self.x, t) + self.origin.x as f64
+   }
+
+   #[inline]
+   pub fn y_at(&self, t: f64) -> f64 {
+      eval(&self.y, t) + self.origin.y as f64
+   }
+
+   /// Parameter where a curve monotone in y reaches `y`.
+   #[inline]
+   pub fn t_for_y(&self, y: i64) -> f64 {
+      solve(&self.y, (y - self.origin.y) as f64)
+   }
+
+   /// Parameter where a curve monotone in x reaches `x`.
+   #[inline]
+   pub fn t_for_x(&self, x: i64) -> f64 {
+      solve(&self.x, (x - self.origin.x) as f64)
+   }
+
+   /// Doubled signed area between the chord and the curve over `t1..t2`,
+   /// matching the sign convention of the chord term `dx * (y1 + y2)`.
+   pub fn double_bulge_area(&self, t1: f64, t2: f64) -> f64 {
+      // Integral of y * x' as a polynomial of degree 5
+      let mut product = [0.; 6];
+      for i in 0..4 {
+         for j in 0..3 {
+            product[i + j] += self.y[i] * self.x[j + 1] * (j + 1) as f64;
+         }
+      }
+
+      let integral = |t: f64| {
+         let mut sum = 0.;
+         for k in (0..6).rev() {
+            sum = sum * t + product[k] / (k + 1) as f64;
+         }
+         sum * t
+      };
+
+      let (x1, y1) = (eval(&self.x, t1), eval(&self.y, t1));
+      let (x2, y2) = (eval(&self.x, t2), eval(&self.y, t2));
+
+      2. * (integral(t2) - integral(t1)) - (x2 - x1) * (y1 + y2)
+   }
+}
+
+#[inline]
+fn rel(p: &Point, origin: &Point) -> (f64, f64) {
+   ((p.x - origin.x) as f64, (p.y - origin.y) as f64)
+}
+
+#[inline]
+fn power_basis(c: &[f64; 4]) -> [f64; 4] {
+   [
+      c[0],
+      3. * (c[1] - c[0]),
+      3. * (c[0] - 2. * c[1] + c[2]),
+      c[3] - c[0] + 3. * (c[1] - c[2]),
+   ]
+}
+
+#[inline]
+fn eval(coeffs: &[f64; 4], t: f64) -> f64 {
+   ((coeffs[3] * t + coeffs[2]) * t + coeffs[1]) * t + coeffs[0]
+}
+
+fn solve(coeffs: &[f64; 4], value: f64) -> f64 {
+   let ascending = eval(coeffs, 1.) >= eval(coeffs, 0.);
+
+   let mut low = 0.;
+   let mut high = 1.;
+
+   for _ in 0..60 {
+      let mid = 0.5 * (low + high);
+
+      if (eval(coeffs, mid) < value) == ascending {
+         low = mid;
+      } else {
+         high = mid;
+      }
+   }
+
+   0.5 * (low + high)
+}
+
+/// Splits a quadratic Bézier into pieces monotone in both x and y, returned
+/// as `[start, control, end]` with integer coordinates.
+pub fn quadratic_monotone_pieces(p1: &Point, c: &Point, p2: &Point) -> Vec<[Point; 3]> {
+   let mut splits = Vec::new();
+
+   push_root(&mut splits, (p1.x - c.x) as f64, (p1.x - 2 * c.x + p2.x) as f64);
+   push_root(&mut splits, (p1.y - c.y) as f64, (p1.y - 2 * c.y + p2.y) as f64);
+
+   let controls = [
+      [p1.x as f64, c.x as f64, p2.x as f64],
+      [p1.y as f64, c.y as f64, p2.y as f64],
+   ];
+
+   split_pieces(&controls, &mut splits).into_iter().map(|piece| {
+      let start = piece[0];
+      let end = piece[2];
+      [start, clamp_point(&piece[1], &start, &end), end]
+   }).collect()
+}
+
+/// Splits a cubic Bézier into pieces monotone in both x and y, returned as
+/// `[start, control 1, control 2, end]` with integer coordinates.
+pub fn cubic_monotone_pieces(p1: &Point, c1: &Point, c2: &Point, p2: &Point) -> Vec<[Point; 4]> {
+   let mut splits = Vec::new();
+
+   push_cubic_roots(&mut splits, p1.x, c1.x, c2.x, p2.x);
+   push_cubic_roots(&mut splits, p1.y, c1.y, c2.y, p2.y);
+
+   let controls = [
+      [p1.x as f64, c1.x as f64, c2.x as f64, p2.x as f64],
+      [p1.y as f64, c1.y as f64, c2.y as f64, p2.y as f64],
+   ];
+
+   split_pieces(&controls, &mut splits).into_iter().map(|piece| {
+      let start = piece[0];
+      let end = piece[3];
+      [
+         start,
+         clamp_point(&piece[1], &start, &end),
+         clamp_point(&piece[2], &start, &end),
+         end
+      ]
+   }).collect()
+}
+
+/// Edge type of a monotone curve piece traversed from `from` to `to`.
+/// Flat pieces degenerate to horizontal or vertical lines.
+pub fn curve_edge_type(cubic: bool, from: &Point, to: &Point) -> EdgeType {
+   let up = to.y > from.y;
+   let right = to.x > from.x;
+
+   if from.y == to.y {
+      return if right { EdgeType::LHR } else { EdgeType::LHL };
+   }
+
+   if from.x == to.x {
+      return if up { EdgeType::LVT } else { EdgeType::LVB };
+   }
+
+   match (cubic, up, right) {
+      (false, true, true) => EdgeType::QTR,
+      (false, true, false) => EdgeType::QTL,
+      (false, false, true) => EdgeType::QBR,
+      (false, false, false) => EdgeType::QBL,
+      (true, true, true) => EdgeType::KTR,
+      (true, true, false) => EdgeType::KTL,
+      (true, false, true) => EdgeType::KBR,
+      (true, false, false) => EdgeType::KBL,
+   }
+}
+
+#[inline]
+fn push_root(splits: &mut Vec<f64>, numerator: f64, denominator: f64) {
+   if denominator != 0. {
+      let t = numerator / denominator;
+      if t > 0. && t < 1. {
+         splits.push(t);
+      }
+   }
+}
+
+fn push_cubic_roots(splits: &mut Vec<f64>, v0: i64, v1: i64, v2: i64, v3: i64) {
+   // Derivative is 3 * (a t^2 + 2 b t + c)
+   let a = (-v0 + 3 * v1 - 3 * v2 + v3) as f64;
+   let b = (v0 - 2 * v1 + v2) as f64;
+   let c = (v1 - v0) as f64;
+
+   if a == 0. {
+      push_root(splits, -c, 2. * b);
+      return;
+   }
+
+   let discriminant = b * b - a * c;
+   if discriminant < 0. {
+      return;
+   }
+
+   let sqrt = discriminant.sqrt();
+   for root in &[(-b - sqrt) / a, (-b + sqrt) / a] {
+      if *root > 0. && *root < 1. {
+         splits.push(*root);
+      }
+   }
+}
+
+/// De Casteljau subdivision of the per-axis control values at the sorted
+/// `splits` parameters.
+fn split_pieces<A>(controls: &[A; 2], splits: &mut Vec<f64>) -> Vec<Vec<Point>> where A: AsRef<[f64]> {
+   splits.sort_by(|a, b| a.partial_cmp(b).unwrap());
+   splits.dedup();
+
+   let mut rest = [controls[0].as_ref().to_vec(), controls[1].as_ref().to_vec()];
+   let mut pieces = Vec::new();
+   let mut t_prev = 0.;
+
+   for t in splits.iter() {
+      let local_t = (t - t_prev) / (1. - t_prev);
+
+      let (x_left, x_right) = subdivide(&rest[0], local_t);
+      let (y_left, y_right) = subdivide(&rest[1], local_t);
+
+      pieces.push(round_points(&x_left, &y_left));
+
+      rest = [x_right, y_right];
+      t_prev = *t;
+   }
+
+   pieces.push(round_points(&rest[0], &rest[1]));
+
+   pieces.retain(|piece| piece[0] != piece[piece.len() - 1]);
+
+   for i in 1..pieces.len() {
+      let end = pieces[i - 1][pieces[i - 1].len() - 1];
+      pieces[i][0] = end;
+   }
+
+   pieces
+}
+
+fn subdivide(values: &[f64], t: f64) -> (Vec<f64>, Vec<f64>) {
+   let mut work = values.to_vec();
+   let len = work.len();
+
+   let mut left = vec![work[0]];
+   let mut right = vec![work[len - 1]];
+
+   for level in 1..len {
+      for i in 0..len - level {
+         work[i] += (work[i + 1] - work[i]) * t;
+      }
+      left.push(work[0]);
+      right.push(work[len - level - 1]);
+   }
+
+   right.reverse();
+
+   (left, right)
+}
+
+fn round_points(xs: &[f64], ys: &[f64]) -> Vec<Point> {
+   xs.iter().zip(ys.iter()).map(|(x, y)| {
+      Point::new(x.round() as i64, y.round() as i64)
+   }).collect()
+}
+
+#[inline]
+fn clamp_point(p: &Point, start: &Point, end: &Point) -> Point {
+   Point::new(
+      clamp(p.x, start.x, end.x),
+      clamp(p.y, start.y, end.y),
+   )
+}
+
+#[inline]
+fn clamp(v: i64, a: i64, b: i64) -> i64 {
+   if a < b {
+      v.max(a).min(b)
+   } else {
+      v.max(b).min(a)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::point::Point;
+
+   use raster::EdgeType;
+
+   use super::{quadratic_monotone_pieces, cubic_monotone_pieces, curve_edge_type};
+
+   #[test]
+   fn test_quadratic_monotone_pieces() {
+      let pieces = quadratic_monotone_pieces(
+         &Point::new(0, 0), &Point::new(500, 1000), &Point::new(1000, 0)
+      );
+
+      assert_eq!(pieces.len(), 2);
+      assert_eq!(pieces[0], [Point::new(0, 0), Point::new(250, 500), Point::new(500, 500)]);
+      assert_eq!(pieces[1], [Point::new(500, 500), Point::new(750, 500), Point::new(1000, 0)]);
+
+      assert_eq!(curve_edge_type(false, &pieces[0][0], &pieces[0][2]), EdgeType::QTR);
+      assert_eq!(curve_edge_type(false, &pieces[1][0], &pieces[1][2]), EdgeType::QBR);
+   }
+
+   #[test]
+   fn test_cubic_monotone_pieces() {
+      let start = Point::new(0, 0);
+      let end = Point::new(3000, 0);
+
+      let pieces = cubic_monotone_pieces(
+         &start, &Point::new(1000, 3000), &Point::new(2000, -3000), &end
+      );
+
+      assert_eq!(pieces.len(), 3);
+      assert_eq!(pieces[0][0], start);
+      assert_eq!(pieces[2][3], end);
+
+      for piece in &pieces {
+         let (min_x, max_x) = (piece[0].x.min(piece[3].x), piece[0].x.max(piece[3].x));
+         let (min_y, max_y) = (piece[0].y.min(piece[3].y), piece[0].y.max(piece[3].y));
+
+         for control in &piece[1..3] {
+            assert!(control.x >= min_x && control.x <= max_x);
+            assert!(control.y >= min_y && control.y <= max_y);
+         }
+      }
+
+      assert_eq!(curve_edge_type(true, &pieces[1][0], &pieces[1][3]), EdgeType::KBR);
+   }
+}
@@ -0,0 +1,121 @@
+/// Dash pattern of alternating dash and gap lengths in world coordinates,
+/// starting `offset` into the pattern. Odd length arrays are repeated to
+/// make the pattern even, as in SVG.
+#[derive(Debug, Clone)]
+pub struct Dash {
+   pub array: Vec<f64>,
+   pub offset: f64,
+}
+
+impl Dash {
+   #[inline]
+   pub fn new(array: Vec<f64>, offset: f64) -> Self {
+      Dash {
+         array,
+         offset,
+      }
+   }
+}
+
+/// Position within a dash pattern while walking along a contour.
+#[derive(Debug, Clone)]
+pub struct DashCursor {
+   pattern: Vec<f64>,
+   index: usize,
+   remaining: f64,
+   on: bool,
+}
+
+impl DashCursor {
+   /// Cursor at the start of a contour, or `None` for patterns that do not
+   /// dash anything (empty, negative or all zero lengths).
+   pub fn new(dash: &Dash) -> Option<Self> {
+      if dash.array.is_empty() || dash.array.iter().any(|len| *len < 0. || !len.is_finite()) {
+         return None;
+      }
+
+      let mut pattern = dash.array.clone();
+      if pattern.len() % 2 == 1 {
+         pattern.extend_from_slice(&dash.array);
+      }
+
+      let total: f64 = pattern.iter().sum();
+      if total <= 0. {
+         return None;
+      }
+
+      let mut offset = dash.offset % total;
+      if offset < 0. {
+         offset += total;
+      }
+
+      // Zero length dashes at the very start are kept as dots
+      let mut index = 0;
+      while offset > pattern[index] || (offset > 0. && offset == pattern[index]) {
+         offset -= pattern[index];
+         index = (index + 1) % pattern.len();
+      }
+
+      Some(DashCursor {
+         remaining: pattern[index] - offset,
+         on: index % 2 == 0,
+         pattern,
+         index,
+      })
+   }
+
+   /// Dash intervals within the next `length` of the contour, as distances
+   /// from its start. An interval starting at zero continues a dash still
+   /// open at the end of the previous length, other zero length intervals
+   /// are dots.
+   pub fn intervals(&mut self, length: f64) -> Vec<(f64, f64)> {
+      let mut intervals = Vec::new();
+      let mut position = 0.;
+
+      loop {
+         if self.remaining >= length - position {
+            if self.on {
+               intervals.push((position, length));
+            }
+
+            self.remaining -= length - position;
+
+            return intervals;
+         }
+
+         if self.on {
+            intervals.push((position, position + self.remaining));
+         }
+
+         position += self.remaining;
+
+         self.index = (self.index + 1) % self.pattern.len();
+         self.remaining = self.pattern[self.index];
+         self.on = !self.on;
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::{Dash, DashCursor};
+
+   #[test]
+   fn test_dash_intervals() {
+      let mut cursor = DashCursor::new(&Dash::new(vec![3., 1.], 2.)).unwrap();
+
+      assert_eq!(cursor.intervals(5.), vec![(0., 1.), (2., 5.)]);
+      assert_eq!(cursor.intervals(4.), vec![(0., 0.), (1., 4.)]);
+
+      let mut cursor = DashCursor::new(&Dash::new(vec![0., 2., 1.], -1.)).unwrap();
+
+      assert_eq!(cursor.intervals(6.), vec![(1., 1.), (3., 4.), (4., 6.)]);
+
+      let mut cursor = DashCursor::new(&Dash::new(vec![0., 2.], 0.)).unwrap();
+
+      assert_eq!(cursor.intervals(3.), vec![(0., 0.), (2., 2.)]);
+
+      assert!(DashCursor::new(&Dash::new(vec![0., 0.], 0.)).is_none());
+      assert!(DashCursor::new(&Dash::new(vec![1., -1.], 0.)).is_none());
+   }
+}
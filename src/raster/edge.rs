@@ -24,14 +24,79 @@ pub enum EdgeType {
    ATL, // anti-clockwise arc top-left
    ABR, // anti-clockwise arc bottom-right
    ABL, // anti-clockwise arc bottom-left
+
+   QTR, // quadratic curve top-right
+   QTL, // quadratic curve top-left
+   QBR, // quadratic curve bottom-right
+   QBL, // quadratic curve bottom-left
+
+   KTR, // cubic curve top-right
+   KTL, // cubic curve top-left
+   KBR, // cubic curve bottom-right
+   KBL, // cubic curve bottom-left
 }
 
 impl EdgeType {
+   #[inline]
+   pub fn circular(&self) -> bool {
+      matches!(
+         *self,
+         EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL |
+         EdgeType::ATR | EdgeType::ATL | EdgeType::ABR | EdgeType::ABL
+      )
+   }
+
+   #[inline]
+   pub fn quadratic(&self) -> bool {
+      matches!(*self, EdgeType::QTR | EdgeType::QTL | EdgeType::QBR | EdgeType::QBL)
+   }
+
+   #[inline]
+   pub fn cubic(&self) -> bool {
+      matches!(*self, EdgeType::KTR | EdgeType::KTL | EdgeType::KBR | EdgeType::KBL)
+   }
+
+   #[inline]
+   pub fn bezier(&self) -> bool {
+      self.quadratic() || self.cubic()
+   }
+
+   /// Type of the same edge traversed the other way.
+   pub fn reverse(&self) -> EdgeType {
+      match *self {
+         EdgeType::LTR => EdgeType::LBL,
+         EdgeType::LTL => EdgeType::LBR,
+         EdgeType::LBR => EdgeType::LTL,
+         EdgeType::LBL => EdgeType::LTR,
+         EdgeType::LHR => EdgeType::LHL,
+         EdgeType::LHL => EdgeType::LHR,
+         EdgeType::LVT => EdgeType::LVB,
+         EdgeType::LVB => EdgeType::LVT,
+         EdgeType::CTR => EdgeType::ABL,
+         EdgeType::CTL => EdgeType::ABR,
+         EdgeType::CBR => EdgeType::ATL,
+         EdgeType::CBL => EdgeType::ATR,
+         EdgeType::ATR => EdgeType::CBL,
+         EdgeType::ATL => EdgeType::CBR,
+         EdgeType::ABR => EdgeType::CTL,
+         EdgeType::ABL => EdgeType::CTR,
+         EdgeType::QTR => EdgeType::QBL,
+         EdgeType::QTL => EdgeType::QBR,
+         EdgeType::QBR => EdgeType::QTL,
+         EdgeType::QBL => EdgeType::QTR,
+         EdgeType::KTR => EdgeType::KBL,
+         EdgeType::KTL => EdgeType::KBR,
+         EdgeType::KBR => EdgeType::KTL,
+         EdgeType::KBL => EdgeType::KTR,
+      }
+   }
+
    #[inline]
    pub fn reversed(&self) -> bool {
       match *self {
          EdgeType::LBR | EdgeType::LBL | EdgeType::LHL | EdgeType::LVB |
-         EdgeType::CBR | EdgeType::CBL | EdgeType::ABR | EdgeType::ABL => {
+         EdgeType::CBR | EdgeType::CBL | EdgeType::ABR | EdgeType::ABL |
+         EdgeType::QBR | EdgeType::QBL | EdgeType::KBR | EdgeType::KBL => {
             true
          },
          _ => {
@@ -46,6 +111,7 @@ pub struct EdgeSrc {
    pub edge_type: EdgeType,
    pub segment: usize,
    pub circle: usize,
+   pub curve: usize,
 }
 
 impl EdgeSrc {
@@ -55,6 +121,17 @@ impl EdgeSrc {
          edge_type: edge_type,
          segment: segment,
          circle: circle,
+         curve: usize::MAX,
+      }
+   }
+
+   #[inline]
+   pub fn new_curve(edge_type: EdgeType, segment: usize, curve: usize) -> Self {
+      EdgeSrc {
+         edge_type,
+         segment,
+         circle: usize::MAX,
+         curve,
       }
    }
 
@@ -75,8 +152,13 @@ pub struct Edge {
    pub edge_type: EdgeType,
    pub segment: usize,
    pub circle: usize,
+   pub curve: usize,
    pub p1: Point,
    pub p2: Point,
+   /// Bézier parameters of `p1` and `p2` on curve edges, carried along as
+   /// the edge is sliced.
+   pub t1: f64,
+   pub t2: f64,
 }
 
 impl Edge {
@@ -86,8 +168,11 @@ impl Edge {
          edge_type: edge_type,
          segment: segment,
          circle: circle,
+         curve: usize::MAX,
          p1: p1,
          p2: p2,
+         t1: 0.,
+         t2: 1.,
       }
    }
 
@@ -96,9 +181,12 @@ impl Edge {
       self.edge_type.reversed()
    }
 
+   /// Closing edge of a slice. Slices touching a poly in a single point get
+   /// a zero-length edge, typed upwards as it has no direction.
    #[inline]
    pub fn vert_bottom(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LVB, usize::MAX, usize::MAX, p1, p2)
+      let edge_type = if p1 == p2 { EdgeType::LVT } else { EdgeType::LVB };
+      Edge::new(edge_type, usize::MAX, usize::MAX, p1, p2)
    }
 
    #[inline]
@@ -106,9 +194,12 @@ impl Edge {
       Edge::new(EdgeType::LVT, usize::MAX, usize::MAX, p1, p2)
    }
 
+   /// Closing edge of a slice, zero-length ones typed rightwards as
+   /// `vert_bottom` does.
    #[inline]
    pub fn hori_left(p1: Point, p2: Point) -> Edge {
-      Edge::new(EdgeType::LHL, usize::MAX, usize::MAX, p1, p2)
+      let edge_type = if p1 == p2 { EdgeType::LHR } else { EdgeType::LHL };
+      Edge::new(edge_type, usize::MAX, usize::MAX, p1, p2)
    }
 
    #[inline]
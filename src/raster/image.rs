@@ -0,0 +1,45 @@
+use draw::RGBA;
+
+
+/// Bitmap of `width * height` colors stored row by row from the top.
+#[derive(Debug, Clone)]
+pub struct Image {
+   pub width: usize,
+   pub height: usize,
+   pub data: Vec<RGBA>,
+}
+
+impl Image {
+   #[inline]
+   pub fn new(width: usize, height: usize) -> Self {
+      Image {
+         width,
+         height,
+         data: vec![RGBA::new(0, 0, 0, 0); width * height],
+      }
+   }
+
+   /// Image from existing pixel data, or `None` when the length does not
+   /// match the size.
+   pub fn from_data(width: usize, height: usize, data: Vec<RGBA>) -> Option<Self> {
+      if data.len() != width * height {
+         return None;
+      }
+
+      Some(Image {
+         width,
+         height,
+         data,
+      })
+   }
+
+   #[inline]
+   pub fn get_pixel(&self, x: usize, y: usize) -> RGBA {
+      self.data[y * self.width + x]
+   }
+
+   #[inline]
+   pub fn set_pixel(&mut self, x: usize, y: usize, color: RGBA) {
+      self.data[y * self.width + x] = color;
+   }
+}
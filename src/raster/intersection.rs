@@ -1,11 +1,13 @@
 use std::cmp::{min, max};
 use std::{i64, usize};
+use std::sync::Arc;
 
 use geom::point::Point;
 use num::NumberOps;
 
 use super::rasterizer::Rasterizer;
 use super::edge::{Edge, EdgeType};
+use super::curve::Bezier;
 use super::scene::Scene;
 
 
@@ -38,6 +40,50 @@ impl Default for IntersectionRef {
 }
 
 
+/// Pixel grid intersections of all scene segments. They are computed once
+/// per render and only read while slicing, so the bands of a
+/// `ParallelRasterizer` share them.
+#[derive(Debug, Clone, Default)]
+pub struct Intersections {
+   pub vert_intersections_ref: Vec<IntersectionRef>,
+   pub hori_intersections_ref: Vec<IntersectionRef>,
+   pub vert_intersections: Vec<i64>,
+   pub hori_intersections: Vec<i64>,
+   /// Bézier parameters of the curve intersections, unused for other edges.
+   pub vert_intersections_t: Vec<f64>,
+   pub hori_intersections_t: Vec<f64>,
+}
+
+
+/// Upper bound of vertical and horizontal intersection entries for a scene.
+pub fn intersections_len(scene: &Scene, div_per_pixel: i64, div_per_row: i64) -> (usize, usize) {
+   let mut vert_len = 0;
+   let mut hori_len = 0;
+
+   for segment in &scene.segments {
+      let p1 = &scene.points[segment.p1];
+      let p2 = &scene.points[segment.p2];
+
+      vert_len += px_span(min(p1.x, p2.x), max(p1.x, p2.x), div_per_pixel);
+      hori_len += px_span(min(p1.y, p2.y), max(p1.y, p2.y), div_per_row);
+   }
+
+   (vert_len, hori_len)
+}
+
+#[inline]
+fn px_span(v1: i64, v2: i64, step: i64) -> usize {
+   let start = 1 + v1.div_euclid(step);
+   let end = 1 + (v2 - 1).div_euclid(step);
+
+   if end > start {
+      (end - start) as usize
+   } else {
+      0
+   }
+}
+
+
 pub trait RasterizerIntersection {
    fn reset_intersections(&mut self, scene: &Scene);
 
@@ -48,20 +94,29 @@ pub trait RasterizerIntersection {
    fn h_intersection(&self, edge: &Edge, y_px: i64) -> i64;
 
    fn v_intersection(&self, edge: &Edge, x_px: i64) -> i64;
+
+   fn h_intersection_t(&self, edge: &Edge, y_px: i64) -> f64;
+
+   fn v_intersection_t(&self, edge: &Edge, x_px: i64) -> f64;
 }
 
 
 impl RasterizerIntersection for Rasterizer {
    fn reset_intersections(&mut self, scene: &Scene) {
+      let intersections = Arc::make_mut(&mut self.intersections);
+
       for i in 0..scene.segments.len() {
-         self.vert_intersections_ref[i].start = usize::MAX;
-         self.hori_intersections_ref[i].start = usize::MAX;
+         intersections.vert_intersections_ref[i].start = usize::MAX;
+         intersections.hori_intersections_ref[i].start = usize::MAX;
       }
    }
 
    fn intersect_edges(&mut self, scene: &Scene) {
       self.reset_intersections(scene);
 
+      let (div_per_pixel, div_per_row) = (self.div_per_pixel, self.div_per_row);
+      let intersections = Arc::make_mut(&mut self.intersections);
+
       let mut vert_prev_end = 0;
       let mut hori_prev_end = 0;
 
@@ -71,12 +126,12 @@ impl RasterizerIntersection for Rasterizer {
 
                let segment_index = edge.segment;
 
-               let ref mut vert_ref = self.vert_intersections_ref[segment_index];
+               let ref mut vert_ref = intersections.vert_intersections_ref[segment_index];
                if vert_ref.start != usize::MAX {
                   continue;
                }
 
-               let ref mut hori_ref = self.hori_intersections_ref[segment_index];
+               let ref mut hori_ref = intersections.hori_intersections_ref[segment_index];
 
                let ref segment = scene.segments[segment_index];
                let ref p1 = scene.points[segment.p1];
@@ -86,11 +141,11 @@ impl RasterizerIntersection for Rasterizer {
                hori_ref.start = hori_prev_end;
 
                let (vert_end, x_first_px) = v_multi_intersect_fast(
-                  p1, p2, self.div_per_pixel, vert_ref.start, &mut self.vert_intersections
+                  p1, p2, div_per_pixel, vert_ref.start, &mut intersections.vert_intersections
                );
 
                let (hori_end, y_first_px) = h_multi_intersect_fast(
-                  p1, p2, self.div_per_pixel, hori_ref.start, &mut self.hori_intersections
+                  p1, p2, div_per_row, hori_ref.start, &mut intersections.hori_intersections
                );
 
                vert_prev_end = vert_end;
@@ -101,16 +156,17 @@ impl RasterizerIntersection for Rasterizer {
                hori_ref.end = hori_end;
                hori_ref.first_px = y_first_px;
             },
-            EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL => {
+            EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL |
+            EdgeType::ATR | EdgeType::ATL | EdgeType::ABR | EdgeType::ABL => {
 
                let segment_index = edge.segment;
 
-               let ref mut vert_ref = self.vert_intersections_ref[segment_index];
+               let ref mut vert_ref = intersections.vert_intersections_ref[segment_index];
                if vert_ref.start != usize::MAX {
                   continue;
                }
 
-               let ref mut hori_ref = self.hori_intersections_ref[segment_index];
+               let ref mut hori_ref = intersections.hori_intersections_ref[segment_index];
 
                let ref segment = scene.segments[segment_index];
                let ref p1 = scene.points[segment.p1];
@@ -123,13 +179,13 @@ impl RasterizerIntersection for Rasterizer {
                let ref center = scene.points[circle.center];
                let radius = circle.radius;
 
-               let start = 1 + p1.y / self.div_per_pixel;
-               let end = 1 + (p2.y - 1) / self.div_per_pixel;
+               let start = 1 + p1.y.div_euclid(div_per_row);
+               let end = 1 + (p2.y - 1).div_euclid(div_per_row);
 
                debug_assert!(p1.y <= p2.y);
 
                for y_px in start..end {
-                  let y = y_px * self.div_per_pixel;
+                  let y = y_px * div_per_row;
                   let dy = y - center.y;
 
                   debug_assert!(radius > dy.abs());
@@ -138,12 +194,16 @@ impl RasterizerIntersection for Rasterizer {
 
                   debug_assert!(dx > 0);
 
-                  let x = match edge.edge_type {
-                     EdgeType::CTR | EdgeType::CTL => center.x - dx,
+                  let x = match edge.edge_type { // Left half of the circle
+                     EdgeType::CTR | EdgeType::CTL |
+                     EdgeType::ABR | EdgeType::ABL => center.x - dx,
                      _ => center.x + dx
                   };
 
-                  self.hori_intersections[hori_prev_end] = x;
+                  // End points rounded onto the circle can be off by a unit
+                  let x = max(min(p1.x, p2.x), min(max(p1.x, p2.x), x));
+
+                  intersections.hori_intersections[hori_prev_end] = x;
                   hori_prev_end += 1;
 
                }
@@ -152,17 +212,18 @@ impl RasterizerIntersection for Rasterizer {
                hori_ref.first_px = start;
 
                let (x1, x2) = match edge.edge_type {
-                  EdgeType::CTR | EdgeType::CBL => (p1.x, p2.x),
+                  EdgeType::CTR | EdgeType::CBL |
+                  EdgeType::ATR | EdgeType::ABL => (p1.x, p2.x),
                   _ => (p2.x, p1.x),
                };
 
                debug_assert!(x1 <= x2);
 
-               let start = 1 + x1 / self.div_per_pixel;
-               let end = 1 + (x2 - 1) / self.div_per_pixel;
+               let start = 1 + x1.div_euclid(div_per_pixel);
+               let end = 1 + (x2 - 1).div_euclid(div_per_pixel);
 
                for x_px in start..end {
-                  let x = x_px * self.div_per_pixel;
+                  let x = x_px * div_per_pixel;
                   let dx = center.x - x;
 
                   debug_assert!(radius > dx.abs());
@@ -171,12 +232,79 @@ impl RasterizerIntersection for Rasterizer {
 
                   debug_assert!(dy > 0);
 
-                  let y = match edge.edge_type {
-                     EdgeType::CTR | EdgeType::CBR => center.y + dy,
+                  let y = match edge.edge_type { // Upper half of the circle
+                     EdgeType::CTR | EdgeType::CBR |
+                     EdgeType::ATL | EdgeType::ABL => center.y + dy,
                      _ => center.y - dy
                   };
 
-                  self.vert_intersections[vert_prev_end] = y;
+                  let y = max(p1.y, min(p2.y, y));
+
+                  intersections.vert_intersections[vert_prev_end] = y;
+                  vert_prev_end += 1;
+               }
+
+               vert_ref.end = vert_prev_end;
+               vert_ref.first_px = start;
+            },
+            EdgeType::QTR | EdgeType::QTL | EdgeType::QBR | EdgeType::QBL |
+            EdgeType::KTR | EdgeType::KTL | EdgeType::KBR | EdgeType::KBL => {
+
+               let segment_index = edge.segment;
+
+               let vert_ref = &mut intersections.vert_intersections_ref[segment_index];
+               if vert_ref.start != usize::MAX {
+                  continue;
+               }
+
+               let hori_ref = &mut intersections.hori_intersections_ref[segment_index];
+
+               let segment = &scene.segments[segment_index];
+               let p1 = &scene.points[segment.p1];
+               let p2 = &scene.points[segment.p2];
+
+               hori_ref.start = hori_prev_end;
+               vert_ref.start = vert_prev_end;
+
+               let bezier = Bezier::from_edge(edge.edge_type, segment_index, edge.curve, scene);
+
+               // Rounding may leave tiny wiggles in an otherwise monotone
+               // curve, so values are kept within the segment and ordered
+               let ascending = p1.x <= p2.x;
+               let (min_x, max_x) = (min(p1.x, p2.x), max(p1.x, p2.x));
+
+               debug_assert!(p1.y <= p2.y);
+
+               let start = 1 + p1.y.div_euclid(div_per_row);
+               let end = 1 + (p2.y - 1).div_euclid(div_per_row);
+
+               let mut prev_x = if ascending { min_x } else { max_x };
+               for y_px in start..end {
+                  let t = bezier.t_for_y(y_px * div_per_row);
+                  let x = bezier.x_at(t).round() as i64;
+
+                  prev_x = monotone_clamp(x, prev_x, min_x, max_x, ascending);
+
+                  intersections.hori_intersections[hori_prev_end] = prev_x;
+                  intersections.hori_intersections_t[hori_prev_end] = t;
+                  hori_prev_end += 1;
+               }
+
+               hori_ref.end = hori_prev_end;
+               hori_ref.first_px = start;
+
+               let start = 1 + min_x.div_euclid(div_per_pixel);
+               let end = 1 + (max_x - 1).div_euclid(div_per_pixel);
+
+               let mut prev_y = if ascending { p1.y } else { p2.y };
+               for x_px in start..end {
+                  let t = bezier.t_for_x(x_px * div_per_pixel);
+                  let y = bezier.y_at(t).round() as i64;
+
+                  prev_y = monotone_clamp(y, prev_y, p1.y, p2.y, ascending);
+
+                  intersections.vert_intersections[vert_prev_end] = prev_y;
+                  intersections.vert_intersections_t[vert_prev_end] = t;
                   vert_prev_end += 1;
                }
 
@@ -193,7 +321,9 @@ impl RasterizerIntersection for Rasterizer {
          match edge.edge_type {
             EdgeType::LTR | EdgeType::LTL | EdgeType::LBR | EdgeType::LBL |
             EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL |
-            EdgeType::ATR | EdgeType::ATL | EdgeType::ABR | EdgeType::ABL => {
+            EdgeType::ATR | EdgeType::ATL | EdgeType::ABR | EdgeType::ABL |
+            EdgeType::QTR | EdgeType::QTL | EdgeType::QBR | EdgeType::QBL |
+            EdgeType::KTR | EdgeType::KTL | EdgeType::KBR | EdgeType::KBL => {
                let ref segment = scene.segments[edge.segment];
                let ref p1 = scene.points[segment.p1];
                let ref p2 = scene.points[segment.p2];
@@ -203,25 +333,29 @@ impl RasterizerIntersection for Rasterizer {
                let min_y = min(p1.y, p2.y);
                let max_y = max(p1.y, p2.y);
 
-               let ref vert_ref = self.vert_intersections_ref[edge.segment];
+               // Segment points are ordered by y, so x and y grow together
+               // only when the second point is also to the right
+               let ascending = p1.x <= p2.x;
+
+               let ref vert_ref = self.intersections.vert_intersections_ref[edge.segment];
 
-               let mut prev_y = i64::MIN;
+               let mut prev_y = if ascending { i64::MIN } else { i64::MAX };
                for i in vert_ref.start..vert_ref.end {
-                  let y = self.vert_intersections[i];
+                  let y = self.intersections.vert_intersections[i];
                   debug_assert!(min_y <= y);
                   debug_assert!(max_y >= y);
-                  debug_assert!(prev_y < y);
+                  debug_assert!(if ascending { prev_y <= y } else { prev_y >= y });
                   prev_y = y;
                }
 
-               let ref hori_ref = self.hori_intersections_ref[edge.segment];
+               let ref hori_ref = self.intersections.hori_intersections_ref[edge.segment];
 
-               let mut prev_x = i64::MIN;
+               let mut prev_x = if ascending { i64::MIN } else { i64::MAX };
                for i in hori_ref.start..hori_ref.end {
-                  let x = self.hori_intersections[i];
+                  let x = self.intersections.hori_intersections[i];
                   debug_assert!(min_x <= x);
                   debug_assert!(max_x >= x);
-                  debug_assert!(prev_x < x);
+                  debug_assert!(if ascending { prev_x <= x } else { prev_x >= x });
                   prev_x = x;
                }
             },
@@ -236,12 +370,12 @@ impl RasterizerIntersection for Rasterizer {
          return edge.p1.x;
       }
 
-      let ref h_ref = self.hori_intersections_ref[edge.segment];
+      let ref h_ref = self.intersections.hori_intersections_ref[edge.segment];
 
       debug_assert!(y_px >= h_ref.first_px);
       debug_assert!(h_ref.start != usize::MAX);
 
-      self.hori_intersections[
+      self.intersections.hori_intersections[
          h_ref.start + (y_px - h_ref.first_px) as usize
       ]
    }
@@ -252,15 +386,43 @@ impl RasterizerIntersection for Rasterizer {
          return edge.p1.y;
       }
 
-      let ref v_ref = self.vert_intersections_ref[edge.segment];
+      let ref v_ref = self.intersections.vert_intersections_ref[edge.segment];
 
       debug_assert!(x_px >= v_ref.first_px);
       debug_assert!(v_ref.start != usize::MAX);
 
-      self.vert_intersections[
+      self.intersections.vert_intersections[
          v_ref.start + (x_px - v_ref.first_px) as usize
       ]
    }
+
+   #[inline]
+   fn h_intersection_t(&self, edge: &Edge, y_px: i64) -> f64 {
+      let ref h_ref = self.intersections.hori_intersections_ref[edge.segment];
+
+      self.intersections.hori_intersections_t[
+         h_ref.start + (y_px - h_ref.first_px) as usize
+      ]
+   }
+
+   #[inline]
+   fn v_intersection_t(&self, edge: &Edge, x_px: i64) -> f64 {
+      let ref v_ref = self.intersections.vert_intersections_ref[edge.segment];
+
+      self.intersections.vert_intersections_t[
+         v_ref.start + (x_px - v_ref.first_px) as usize
+      ]
+   }
+}
+
+
+#[inline]
+fn monotone_clamp(v: i64, prev: i64, min_v: i64, max_v: i64, ascending: bool) -> i64 {
+   if ascending {
+      min(max(v, prev), max_v)
+   } else {
+      max(min(v, prev), min_v)
+   }
 }
 
 
@@ -271,8 +433,8 @@ fn h_multi_intersect_fast(p1: &Point, p2: &Point, step_y: i64, mut vec_start: us
       (p1, p2)
    };
 
-   let start = 1 + p1.y / step_y;
-   let end = 1 + (p2.y - 1) / step_y;
+   let start = 1 + p1.y.div_euclid(step_y);
+   let end = 1 + (p2.y - 1).div_euclid(step_y);
 
    let dy = p2.y - p1.y;
    let dx = p2.x - p1.x;
@@ -329,8 +491,8 @@ fn v_multi_intersect_fast(p1: &Point, p2: &Point, step_x: i64, mut vec_start: us
       (p1, p2)
    };
 
-   let start = 1 + p1.x / step_x;
-   let end = 1 + (p2.x - 1) / step_x;
+   let start = 1 + p1.x.div_euclid(step_x);
+   let end = 1 + (p2.x - 1).div_euclid(step_x);
 
    let dx = p2.x - p1.x;
    let dy = p2.y - p1.y;
@@ -0,0 +1,225 @@
+use frame::Frame;
+use draw::{RGB, RGBA, GammaTable};
+
+
+/// FIR filter weights spreading each sub-pixel over its neighbours to tame
+/// the color fringes of sub-pixel rendering.
+pub const LCD_FILTER: [u32; 5] = [8, 77, 86, 77, 8];
+
+/// Physical order of the sub-pixels within a pixel, from left to right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubpixelOrder {
+   #[default]
+   Rgb,
+   Bgr,
+}
+
+/// Settings for `Rasterizer::render_lcd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lcd {
+   pub order: SubpixelOrder,
+   /// Five tap filter applied across the sub-pixel sequence, `None` keeps
+   /// the raw per sub-pixel coverage.
+   pub filter: Option<[u32; 5]>,
+}
+
+impl Lcd {
+   #[inline]
+   pub fn new(order: SubpixelOrder) -> Self {
+      Lcd {
+         order,
+         filter: Some(LCD_FILTER),
+      }
+   }
+
+   #[inline]
+   pub fn unfiltered(order: SubpixelOrder) -> Self {
+      Lcd {
+         order,
+         filter: None,
+      }
+   }
+}
+
+impl Default for Lcd {
+   fn default() -> Lcd {
+      Lcd::new(SubpixelOrder::Rgb)
+   }
+}
+
+/// Accumulates the sub-pixels of a pixel row rendered at a third of the
+/// pixel width. Each cell holds the premultiplied color and alpha, with the
+/// color in linear light when `linear_light` is set.
+pub struct LcdRow {
+   cells: Vec<[u32; 4]>,
+   touched: bool,
+   linear_light: bool,
+}
+
+impl LcdRow {
+   #[inline]
+   pub fn new(width: usize, linear_light: bool) -> Self {
+      LcdRow {
+         cells: vec![[0; 4]; 3 * width],
+         touched: false,
+         linear_light,
+      }
+   }
+
+   #[inline]
+   pub fn add(&mut self, x: i64, color: &RGBA) {
+      if x < 0 || x as usize >= self.cells.len() || color.a == 0 {
+         return;
+      }
+
+      let a = color.a as u32;
+      let cell = &mut self.cells[x as usize];
+
+      if self.linear_light {
+         let gamma = GammaTable::get();
+
+         cell[0] += gamma.linear(color.r) * a;
+         cell[1] += gamma.linear(color.g) * a;
+         cell[2] += gamma.linear(color.b) * a;
+      } else {
+         cell[0] += color.r as u32 * a;
+         cell[1] += color.g as u32 * a;
+         cell[2] += color.b as u32 * a;
+      }
+
+      cell[3] += a;
+
+      self.touched = true;
+   }
+
+   /// Filters the accumulated sub-pixels, blends them into pixel row `y` of
+   /// the frame and resets the row.
+   pub fn flush(&mut self, frame: &mut Frame, y: i64, lcd: &Lcd) {
+      if !self.touched {
+         return;
+      }
+
+      let weights = lcd.filter.unwrap_or([0, 0, 1, 0, 0]);
+      let weight_sum = weights.iter().sum::<u32>();
+
+      let width = self.cells.len() / 3;
+      let gamma = GammaTable::get();
+
+      for x in 0..width {
+         let mut color = [0u8; 3];
+         let mut alpha = [0u8; 3];
+
+         for (channel, (c, a)) in color.iter_mut().zip(alpha.iter_mut()).enumerate() {
+            let sub = 3 * x + match lcd.order {
+               SubpixelOrder::Rgb => channel,
+               SubpixelOrder::Bgr => 2 - channel,
+            };
+
+            let mut premul = 0;
+            let mut coverage = 0;
+
+            for (i, weight) in weights.iter().enumerate() {
+               if *weight == 0 || sub + i < 2 || sub + i - 2 >= self.cells.len() {
+                  continue;
+               }
+
+               let cell = &self.cells[sub + i - 2];
+
+               premul += weight * cell[channel];
+               coverage += weight * cell[3];
+            }
+
+            if let Some(straight) = (premul + coverage / 2).checked_div(coverage) {
+               *c = if self.linear_light {
+                  gamma.srgb(straight)
+               } else {
+                  straight as u8
+               };
+               *a = ((coverage + weight_sum / 2) / weight_sum) as u8;
+            }
+         }
+
+         if alpha == [0; 3] {
+            continue;
+         }
+
+         let color = RGB::new(color[0], color[1], color[2]);
+         let alpha = RGB::new(alpha[0], alpha[1], alpha[2]);
+
+         if self.linear_light {
+            frame.blend_pixel_lcd_linear(x as i32, y as i32, &color, &alpha);
+         } else {
+            frame.blend_pixel_lcd(x as i32, y as i32, &color, &alpha);
+         }
+      }
+
+      for cell in self.cells.iter_mut() {
+         *cell = [0; 4];
+      }
+
+      self.touched = false;
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+
+   use raster::Rasterizer;
+   use raster::rasterizer::tests::{add_rect, empty_scene};
+
+   use super::{Lcd, SubpixelOrder};
+
+   #[test]
+   fn test_render_lcd_orders() {
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 4000, 0, 12000, 3000, RGBA::new(255, 255, 255, 255));
+
+      let mut rasterizer = Rasterizer::new();
+
+      let mut frame = Frame::new(5, 1);
+      rasterizer.render_lcd(&scene, &mut frame, 3000, &Lcd::unfiltered(SubpixelOrder::Rgb)).unwrap();
+      assert_eq!(frame.get_pixel(1, 0), RGB::new(0, 255, 255));
+      assert_eq!(frame.get_pixel(2, 0), RGB::new(255, 255, 255));
+
+      let mut frame = Frame::new(5, 1);
+      rasterizer.render_lcd(&scene, &mut frame, 3000, &Lcd::new(SubpixelOrder::Rgb)).unwrap();
+      let pixel = frame.get_pixel(1, 0);
+      assert!(0 < pixel.r && pixel.r < pixel.g && pixel.g < pixel.b && pixel.b < 255);
+      assert_eq!(frame.get_pixel(2, 0), RGB::new(255, 255, 255));
+   }
+
+   #[test]
+   fn test_render_lcd_bgr() {
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 4000, 0, 8000, 3000, RGBA::new(255, 255, 255, 255));
+      add_rect(&mut scene, 0, 3000, 9000, 4000, RGBA::new(255, 255, 255, 255));
+
+      let mut rasterizer = Rasterizer::new();
+
+      let mut frame = Frame::new(4, 2);
+      rasterizer.render_lcd(&scene, &mut frame, 3000, &Lcd::unfiltered(SubpixelOrder::Bgr)).unwrap();
+
+      // Blue sub-pixels are on the left
+      assert_eq!(frame.get_pixel(1, 0), RGB::new(255, 255, 0));
+      assert_eq!(frame.get_pixel(2, 0), RGB::new(0, 255, 255));
+
+      // Rows cover the full pixel height
+      assert_eq!(frame.get_pixel(0, 1), RGB::new(85, 85, 85));
+      assert_eq!(frame.get_pixel(3, 1), RGB::default());
+   }
+
+   #[test]
+   fn test_render_lcd_division() {
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 2000, 2000, RGBA::new(255, 255, 255, 255));
+
+      let mut rasterizer = Rasterizer::new();
+      let mut frame = Frame::new(4, 4);
+
+      assert!(rasterizer.render_lcd(&scene, &mut frame, 1000, &Lcd::default()).is_err());
+      assert!(rasterizer.render_lcd(&scene, &mut frame, 0, &Lcd::default()).is_err());
+      assert!(rasterizer.render_lcd(&scene, &mut frame, 999, &Lcd::default()).is_ok());
+   }
+}
@@ -1,4 +1,5 @@
 pub mod circle;
+pub mod curve;
 pub mod segment;
 pub mod edge;
 pub mod intersection;
@@ -6,10 +7,27 @@ pub mod poly;
 pub mod rasterizer;
 pub mod scene;
 pub mod pool;
+pub mod parallel;
+pub mod path;
+pub mod stroke;
+pub mod dash;
+pub mod compile;
+pub mod paint;
+pub mod image;
+pub mod lcd;
 
-pub use self::scene::Scene;
+pub use self::scene::{Scene, SceneBuilder};
+pub use self::compile::{SceneCompiler, BooleanOp, boolean};
+pub use self::path::{Path, FillRule};
+pub use self::stroke::{Stroke, LineJoin, LineCap};
+pub use self::dash::Dash;
+pub use self::paint::{Paint, Gradient, GradientShape, ColorStop, Spread, ImagePattern, Filter, Wrap};
+pub use self::image::Image;
 pub use self::circle::Circle;
+pub use self::curve::Curve;
 pub use self::segment::Segment;
 pub use self::edge::{EdgeType, EdgeSrc};
 pub use self::poly::Poly;
 pub use self::rasterizer::Rasterizer;
+pub use self::lcd::{Lcd, SubpixelOrder};
+pub use self::parallel::ParallelRasterizer;
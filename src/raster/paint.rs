@@ -0,0 +1,426 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use geom::point::Point;
+use geom::affine::Affine;
+use draw::RGBA;
+
+use super::image::Image;
+
+
+/// How a gradient continues outside of its `0..1` offset range.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Spread {
+   Pad,
+   Repeat,
+   Reflect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorStop {
+   pub offset: f64,
+   pub color: RGBA,
+}
+
+impl ColorStop {
+   #[inline]
+   pub fn new(offset: f64, color: RGBA) -> Self {
+      ColorStop {
+         offset,
+         color,
+      }
+   }
+}
+
+/// Geometry mapping world coordinates to gradient offsets.
+#[derive(Debug, Clone, Copy)]
+pub enum GradientShape {
+   /// Offset 0 at `start` to 1 at `end`, constant across the line between.
+   Linear {
+      start: Point,
+      end: Point,
+   },
+   /// Offset 0 at `center` to 1 at `radius` distance.
+   Radial {
+      center: Point,
+      radius: i64,
+   },
+   /// Offset 0 to 1 anti-clockwise around `center`, starting at `angle`
+   /// radians from the positive x axis.
+   Conic {
+      center: Point,
+      angle: f64,
+   },
+}
+
+#[derive(Debug, Clone)]
+pub struct Gradient {
+   pub shape: GradientShape,
+   pub stops: Vec<ColorStop>,
+   pub spread: Spread,
+}
+
+impl Gradient {
+   #[inline]
+   pub fn new(shape: GradientShape) -> Self {
+      Gradient {
+         shape,
+         stops: Vec::new(),
+         spread: Spread::Pad,
+      }
+   }
+
+   #[inline]
+   pub fn linear(start: Point, end: Point) -> Self {
+      Gradient::new(GradientShape::Linear { start, end })
+   }
+
+   #[inline]
+   pub fn radial(center: Point, radius: i64) -> Self {
+      Gradient::new(GradientShape::Radial { center, radius })
+   }
+
+   #[inline]
+   pub fn conic(center: Point, angle: f64) -> Self {
+      Gradient::new(GradientShape::Conic { center, angle })
+   }
+
+   /// Adds a color stop, keeping stops ordered by offset. Stops at equal
+   /// offsets make a hard transition in the order they were added.
+   pub fn add_stop(&mut self, offset: f64, color: RGBA) {
+      let index = self.stops.iter().position(|stop| stop.offset > offset).unwrap_or(self.stops.len());
+      self.stops.insert(index, ColorStop::new(offset, color));
+   }
+
+   /// Gradient offset at a world point before spreading.
+   pub fn offset_at(&self, x: f64, y: f64) -> f64 {
+      match self.shape {
+         GradientShape::Linear { start, end } => {
+            let dx = (end.x - start.x) as f64;
+            let dy = (end.y - start.y) as f64;
+
+            let len = dx * dx + dy * dy;
+            if len == 0. {
+               return 0.;
+            }
+
+            ((x - start.x as f64) * dx + (y - start.y as f64) * dy) / len
+         },
+         GradientShape::Radial { center, radius } => {
+            if radius <= 0 {
+               return 1.;
+            }
+
+            let dx = x - center.x as f64;
+            let dy = y - center.y as f64;
+
+            (dx * dx + dy * dy).sqrt() / radius as f64
+         },
+         GradientShape::Conic { center, angle } => {
+            let a = (y - center.y as f64).atan2(x - center.x as f64) - angle;
+
+            let turns = a / (2. * PI);
+            turns - turns.floor()
+         },
+      }
+   }
+
+   pub fn color_at(&self, x: f64, y: f64) -> RGBA {
+      let offset = match self.spread {
+         Spread::Pad => self.offset_at(x, y),
+         Spread::Repeat => {
+            let t = self.offset_at(x, y);
+            t - t.floor()
+         },
+         Spread::Reflect => {
+            let t = self.offset_at(x, y).abs() % 2.;
+            if t > 1. { 2. - t } else { t }
+         },
+      };
+
+      self.color_at_offset(offset)
+   }
+
+   /// Color between the stops around `offset`, padded with the first and
+   /// last stop colors.
+   pub fn color_at_offset(&self, offset: f64) -> RGBA {
+      let first = match self.stops.first() {
+         Some(stop) => stop,
+         None => return RGBA::new(0, 0, 0, 0),
+      };
+
+      if offset <= first.offset {
+         return first.color;
+      }
+
+      for pair in self.stops.windows(2) {
+         let (s1, s2) = (&pair[0], &pair[1]);
+
+         if offset < s2.offset {
+            let t = (offset - s1.offset) / (s2.offset - s1.offset);
+
+            return lerp_premultiplied(s1.color, s2.color, t);
+         }
+      }
+
+      self.stops[self.stops.len() - 1].color
+   }
+}
+
+/// Interpolates premultiplied like the bilinear image filter, so that a
+/// transparent stop fades the other one out instead of darkening it.
+#[inline]
+fn lerp_premultiplied(c1: RGBA, c2: RGBA, t: f64) -> RGBA {
+   let (w1, w2) = ((1. - t) * c1.a as f64, t * c2.a as f64);
+   let a = w1 + w2;
+
+   if a <= 0. {
+      return RGBA::new(0, 0, 0, 0);
+   }
+
+   let channel = |v1: u8, v2: u8| ((v1 as f64 * w1 + v2 as f64 * w2) / a).round() as u8;
+
+   RGBA::new(
+      channel(c1.r, c2.r),
+      channel(c1.g, c2.g),
+      channel(c1.b, c2.b),
+      a.round() as u8,
+   )
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Filter {
+   Nearest,
+   Bilinear,
+}
+
+/// How an image pattern continues outside of the image.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Wrap {
+   Clamp,
+   Repeat,
+}
+
+/// Image placed in the world by `transform`, which maps image coordinates,
+/// in pixels from the top left corner with y running down, to world
+/// coordinates. The inverse is kept along for lookups, patterns with a
+/// singular transform are transparent.
+#[derive(Debug, Clone)]
+pub struct ImagePattern {
+   pub image: Arc<Image>,
+   pub filter: Filter,
+   pub wrap: Wrap,
+   transform: Affine,
+   inverse: Option<Affine>,
+}
+
+impl ImagePattern {
+   #[inline]
+   pub fn new(image: Arc<Image>, transform: Affine) -> Self {
+      ImagePattern {
+         image,
+         filter: Filter::Bilinear,
+         wrap: Wrap::Repeat,
+         transform,
+         inverse: transform.invert(),
+      }
+   }
+
+   #[inline]
+   pub fn transform(&self) -> &Affine {
+      &self.transform
+   }
+
+   pub fn set_transform(&mut self, transform: Affine) {
+      self.transform = transform;
+      self.inverse = transform.invert();
+   }
+
+   pub fn color_at(&self, x: f64, y: f64) -> RGBA {
+      let inverse = match self.inverse {
+         Some(ref inverse) => inverse,
+         None => return RGBA::new(0, 0, 0, 0),
+      };
+
+      if self.image.width == 0 || self.image.height == 0 {
+         return RGBA::new(0, 0, 0, 0);
+      }
+
+      let (u, v) = inverse.apply(x, y);
+
+      match self.filter {
+         Filter::Nearest => self.texel(u.floor() as i64, v.floor() as i64),
+         Filter::Bilinear => {
+            // Texel centers are at half pixels
+            let (u, v) = (u - 0.5, v - 0.5);
+            let (x0, y0) = (u.floor(), v.floor());
+            let (tx, ty) = (u - x0, v - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+
+            let taps = [
+               (self.texel(x0, y0), (1. - tx) * (1. - ty)),
+               (self.texel(x0 + 1, y0), tx * (1. - ty)),
+               (self.texel(x0, y0 + 1), (1. - tx) * ty),
+               (self.texel(x0 + 1, y0 + 1), tx * ty),
+            ];
+
+            // Interpolated premultiplied, so transparent texels do not
+            // darken their neighbours
+            let (mut r, mut g, mut b, mut a) = (0., 0., 0., 0.);
+            for &(color, weight) in &taps {
+               let wa = weight * color.a as f64;
+               r += color.r as f64 * wa;
+               g += color.g as f64 * wa;
+               b += color.b as f64 * wa;
+               a += wa;
+            }
+
+            if a <= 0. {
+               return RGBA::new(0, 0, 0, 0);
+            }
+
+            RGBA::new(
+               (r / a).round() as u8,
+               (g / a).round() as u8,
+               (b / a).round() as u8,
+               a.round() as u8,
+            )
+         },
+      }
+   }
+
+   fn texel(&self, x: i64, y: i64) -> RGBA {
+      let width = self.image.width as i64;
+      let height = self.image.height as i64;
+
+      let (x, y) = match self.wrap {
+         Wrap::Clamp => (clamp_index(x, width), clamp_index(y, height)),
+         Wrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+      };
+
+      self.image.get_pixel(x as usize, y as usize)
+   }
+}
+
+#[inline]
+fn clamp_index(v: i64, len: i64) -> i64 {
+   v.max(0).min(len - 1)
+}
+
+/// Fill of a poly, evaluated at every pixel center it covers.
+#[derive(Debug, Clone)]
+pub enum Paint {
+   Solid(RGBA),
+   Gradient(Gradient),
+   Image(ImagePattern),
+}
+
+impl Paint {
+   #[inline]
+   pub fn color_at(&self, x: f64, y: f64) -> RGBA {
+      match *self {
+         Paint::Solid(color) => color,
+         Paint::Gradient(ref gradient) => gradient.color_at(x, y),
+         Paint::Image(ref pattern) => pattern.color_at(x, y),
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use std::f64::consts::PI;
+
+   use std::sync::Arc;
+
+   use geom::point::Point;
+   use geom::affine::Affine;
+   use draw::RGBA;
+
+   use raster::image::Image;
+
+   use super::{Gradient, Spread, ImagePattern, Filter, Wrap};
+
+   #[test]
+   fn test_gradient_spread() {
+      let mut gradient = Gradient::linear(Point::new(0, 0), Point::new(100, 0));
+      gradient.add_stop(0., RGBA::new(0, 0, 0, 255));
+      gradient.add_stop(1., RGBA::new(200, 100, 0, 255));
+
+      assert_eq!(gradient.color_at(50., 30.), RGBA::new(100, 50, 0, 255));
+      assert_eq!(gradient.color_at(-50., 0.), RGBA::new(0, 0, 0, 255));
+      assert_eq!(gradient.color_at(150., 0.), RGBA::new(200, 100, 0, 255));
+
+      gradient.spread = Spread::Repeat;
+      assert_eq!(gradient.color_at(125., 0.), RGBA::new(50, 25, 0, 255));
+      assert_eq!(gradient.color_at(-25., 0.), RGBA::new(150, 75, 0, 255));
+
+      gradient.spread = Spread::Reflect;
+      assert_eq!(gradient.color_at(125., 0.), RGBA::new(150, 75, 0, 255));
+      assert_eq!(gradient.color_at(-25., 0.), RGBA::new(50, 25, 0, 255));
+   }
+
+   #[test]
+   fn test_gradient_shapes() {
+      let mut radial = Gradient::radial(Point::new(100, 100), 50);
+      radial.add_stop(0., RGBA::new(255, 255, 255, 255));
+      radial.add_stop(0.5, RGBA::new(255, 0, 0, 255));
+      radial.add_stop(0.5, RGBA::new(0, 0, 255, 255));
+
+      assert_eq!(radial.color_at(100., 110.), RGBA::new(255, 153, 153, 255));
+      assert_eq!(radial.color_at(130., 100.), RGBA::new(0, 0, 255, 255));
+
+      let mut conic = Gradient::conic(Point::new(100, 100), PI / 2.);
+      conic.add_stop(0., RGBA::new(0, 0, 0, 255));
+      conic.add_stop(1., RGBA::new(200, 0, 0, 255));
+
+      assert_eq!(conic.color_at(50., 100.), RGBA::new(50, 0, 0, 255));
+      assert_eq!(conic.color_at(100., 50.), RGBA::new(100, 0, 0, 255));
+
+      // Transparent stops only fade the color out
+      let mut fade = Gradient::linear(Point::new(0, 0), Point::new(100, 0));
+      fade.add_stop(0., RGBA::new(255, 0, 0, 255));
+      fade.add_stop(1., RGBA::new(0, 0, 255, 0));
+
+      assert_eq!(fade.color_at(50., 0.), RGBA::new(255, 0, 0, 128));
+      assert_eq!(fade.color_at(75., 0.), RGBA::new(255, 0, 0, 64));
+      assert_eq!(fade.color_at(100., 0.), RGBA::new(0, 0, 255, 0));
+
+      fade.stops[1].color = RGBA::new(0, 0, 255, 85);
+      assert_eq!(fade.color_at(50., 0.), RGBA::new(191, 0, 64, 170));
+   }
+
+   #[test]
+   fn test_image_pattern() {
+      let mut image = Image::new(2, 2);
+      image.set_pixel(0, 0, RGBA::new(255, 0, 0, 255));
+      image.set_pixel(1, 0, RGBA::new(0, 255, 0, 255));
+      image.set_pixel(0, 1, RGBA::new(0, 0, 255, 255));
+      image.set_pixel(1, 1, RGBA::new(0, 0, 255, 0));
+
+      // Image 200 wide with its top left corner at (0, 200)
+      let transform = Affine::scale(100., -100.).then(&Affine::translate(0., 200.));
+
+      let mut pattern = ImagePattern::new(Arc::new(image), transform);
+      pattern.filter = Filter::Nearest;
+
+      assert_eq!(pattern.color_at(50., 150.), RGBA::new(255, 0, 0, 255));
+      assert_eq!(pattern.color_at(150., 150.), RGBA::new(0, 255, 0, 255));
+      assert_eq!(pattern.color_at(250., 150.), RGBA::new(255, 0, 0, 255));
+
+      pattern.wrap = Wrap::Clamp;
+      assert_eq!(pattern.color_at(250., 150.), RGBA::new(0, 255, 0, 255));
+
+      pattern.filter = Filter::Bilinear;
+      assert_eq!(pattern.color_at(100., 150.), RGBA::new(128, 128, 0, 255));
+      assert_eq!(pattern.color_at(50., 100.), RGBA::new(128, 0, 128, 255));
+      assert_eq!(pattern.color_at(150., 100.), RGBA::new(0, 255, 0, 128));
+
+      // Moved one image pixel right, then flattened to a line
+      pattern.filter = Filter::Nearest;
+      pattern.set_transform(transform.then(&Affine::translate(100., 0.)));
+      assert_eq!(pattern.color_at(150., 150.), RGBA::new(255, 0, 0, 255));
+
+      pattern.set_transform(Affine::scale(100., 0.));
+      assert_eq!(pattern.color_at(150., 150.), RGBA::new(0, 0, 0, 0));
+   }
+}
@@ -0,0 +1,197 @@
+use std::cmp::{min, max};
+use std::thread;
+
+use frame::Frame;
+use draw::RGBA;
+use error::{RuntimeError, ErrorKind};
+
+use super::rasterizer::Rasterizer;
+use super::scene::Scene;
+
+
+/// Renders a scene on several threads by splitting the frame into horizontal
+/// bands. The scene is prepared once and borrowed read-only by a scoped
+/// thread per band, each slicing its band with pools kept between renders.
+/// The results are composited into the frame in the same way as
+/// `Rasterizer` does, so the output is identical to the single-threaded
+/// path.
+pub struct ParallelRasterizer {
+   prepared: Rasterizer,
+   bands: Vec<Rasterizer>,
+   buffers: Vec<Vec<RGBA>>,
+}
+
+impl ParallelRasterizer {
+   pub fn new(threads: usize) -> Self {
+      let threads = max(threads, 1);
+
+      ParallelRasterizer {
+         prepared: Rasterizer::new(),
+         bands: (0..threads).map(|_| Rasterizer::new()).collect(),
+         buffers: (0..threads).map(|_| Vec::new()).collect(),
+      }
+   }
+
+   /// Switches linear light mixing, see `Rasterizer::linear_light`, for all
+   /// bands.
+   pub fn set_linear_light(&mut self, linear_light: bool) {
+      self.prepared.linear_light = linear_light;
+   }
+
+   pub fn render(&mut self, scene: &Scene, frame: &mut Frame, div_per_pixel: i64) -> Result<(), RuntimeError> {
+      let width = frame.width as i64;
+      let height = frame.height as i64;
+
+      let bounds = match self.prepared.prepare(scene, div_per_pixel, width, height)? {
+         Some(bounds) => bounds,
+         None => return Ok(())
+      };
+
+      let bands_len = self.bands.len() as i64;
+      let band_height = (height + bands_len - 1) / bands_len;
+
+      let prepared = &self.prepared;
+      let bands = &mut self.bands;
+      let buffers = &mut self.buffers;
+
+      let results: Vec<Option<Result<(), RuntimeError>>> = thread::scope(|scope| {
+         let threads: Vec<_> = bands.iter_mut().zip(buffers.iter_mut()).enumerate().map(
+            |(i, (band, buffer))| {
+               let y0 = min(i as i64 * band_height, height);
+               let y1 = min(y0 + band_height, height);
+
+               scope.spawn(move || band.render_band(prepared, scene, bounds, y0..y1, width, buffer))
+            }
+         ).collect();
+
+         threads.into_iter().map(|thread| thread.join().ok()).collect()
+      });
+
+      let mut result = Ok(());
+
+      for (i, band_result) in results.into_iter().enumerate() {
+         let band_result = match band_result {
+            Some(band_result) => band_result,
+            None => {
+               // The pools may be left inconsistent
+               self.bands[i] = Rasterizer::new();
+               Err(worker_error())
+            }
+         };
+
+         if result.is_ok() {
+            result = band_result;
+         }
+      }
+
+      result?;
+
+      let linear_light = self.prepared.linear_light;
+
+      for (i, buffer) in self.buffers.iter().enumerate() {
+         let y0 = i as i64 * band_height;
+
+         for (offset, color) in buffer.iter().enumerate() {
+            let x = offset as i64 % width;
+            let y = y0 + offset as i64 / width;
+
+            if linear_light {
+               frame.blend_pixel_linear(x as i32, y as i32, color);
+            } else {
+               frame.blend_pixel(x as i32, y as i32, color);
+            }
+         }
+      }
+
+      Ok(())
+   }
+}
+
+fn worker_error() -> RuntimeError {
+   RuntimeError::new(
+      ErrorKind::Other,
+      "Rasterizer band thread panicked".to_string()
+   )
+}
+
+#[cfg(test)]
+mod tests {
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+
+   use raster::Rasterizer;
+   use raster::scene::Scene;
+   use raster::rasterizer::tests::{add_rect, empty_scene, arcs_scene};
+
+   use super::ParallelRasterizer;
+
+   fn filled_frame(width: u32, height: u32, background: &RGB) -> Frame {
+      let mut frame = Frame::new(width, height);
+
+      for y in 0..height as i32 {
+         for x in 0..width as i32 {
+            frame.put_pixel(x, y, background);
+         }
+      }
+
+      frame
+   }
+
+   fn assert_same_output(scene: &Scene, width: u32, height: u32, background: RGB) {
+      let mut serial_frame = filled_frame(width, height, &background);
+
+      Rasterizer::new().render(scene, &mut serial_frame, 1000).unwrap();
+
+      for threads in 1..6 {
+         let mut frame = filled_frame(width, height, &background);
+
+         ParallelRasterizer::new(threads).render(scene, &mut frame, 1000).unwrap();
+
+         for y in 0..height as i32 {
+            for x in 0..width as i32 {
+               assert_eq!(serial_frame.get_pixel(x, y), frame.get_pixel(x, y));
+            }
+         }
+      }
+   }
+
+   #[test]
+   fn test_parallel_arcs() {
+      assert_same_output(&arcs_scene(5000), 61, 57, RGB::default());
+   }
+
+   #[test]
+   fn test_parallel_off_screen() {
+      let mut scene = arcs_scene(5000);
+      for point in &mut scene.points {
+         point.x -= 12500;
+         point.y -= 21000;
+      }
+
+      assert_same_output(&scene, 50, 45, RGB::new(10, 60, 200));
+   }
+
+   #[test]
+   fn test_parallel_translucent() {
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1500, 1500, 9300, RGBA::new(0, 0, 0, 0));
+      add_rect(&mut scene, 1500, 1500, 4300, 9300, RGBA::new(250, 10, 0, 102));
+      add_rect(&mut scene, 4300, 1500, 6000, 9300, RGBA::new(0, 250, 30, 255));
+      scene.polys[2].opacity = 51;
+
+      assert_same_output(&scene, 8, 12, RGB::new(10, 60, 200));
+   }
+
+   #[test]
+   fn test_parallel_reuse() {
+      let mut rasterizer = ParallelRasterizer::new(4);
+      let mut frame = Frame::new(60, 60);
+
+      rasterizer.render(&arcs_scene(5000), &mut frame, 1000).unwrap();
+      rasterizer.render(&empty_scene(), &mut frame, 1000).unwrap();
+      rasterizer.render(&arcs_scene(2000), &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(59, 0), RGB::new(0, 255, 0));
+      assert_eq!(frame.get_pixel(23, 0), RGB::new(0, 255, 0));
+   }
+}
@@ -0,0 +1,640 @@
+use std::f64::consts::FRAC_PI_2;
+
+use geom::point::Point;
+use geom::affine::Affine;
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PathEdge {
+   Line(Point),
+   Arc {
+      end: Point,
+      center: Point,
+      radius: i64,
+      clockwise: bool,
+   },
+   Quad {
+      control: Point,
+      end: Point,
+   },
+   Cubic {
+      control1: Point,
+      control2: Point,
+      end: Point,
+   },
+}
+
+impl PathEdge {
+   #[inline]
+   pub fn end(&self) -> Point {
+      match *self {
+         PathEdge::Line(end) => end,
+         PathEdge::Arc { end, .. } => end,
+         PathEdge::Quad { end, .. } => end,
+         PathEdge::Cubic { end, .. } => end,
+      }
+   }
+
+   /// The edge from `from` cut in two halves.
+   pub fn split(&self, from: &Point) -> (PathEdge, PathEdge) {
+      match *self {
+         PathEdge::Line(end) => {
+            (PathEdge::Line(bezier_point(&[*from, end], 0.5)), PathEdge::Line(end))
+         },
+         PathEdge::Arc { end, center, radius, clockwise } => {
+            let sweep = arc_sweep(from, &end, &center, clockwise);
+            let start = angle(from, &center);
+            let middle = circle_point(&center, radius, if clockwise { start - sweep / 2. } else { start + sweep / 2. });
+
+            // Arcs too short to round a middle point onto are cut as lines
+            if arc_sweep(from, &middle, &center, clockwise) >= sweep ||
+               arc_sweep(&middle, &end, &center, clockwise) >= sweep {
+               return PathEdge::Line(end).split(from);
+            }
+
+            (
+               PathEdge::Arc { end: middle, center, radius, clockwise },
+               PathEdge::Arc { end, center, radius, clockwise },
+            )
+         },
+         PathEdge::Quad { control, end } => {
+            (
+               PathEdge::Quad {
+                  control: bezier_point(&[*from, control], 0.5),
+                  end: bezier_point(&[*from, control, end], 0.5),
+               },
+               PathEdge::Quad { control: bezier_point(&[control, end], 0.5), end },
+            )
+         },
+         PathEdge::Cubic { control1, control2, end } => {
+            (
+               PathEdge::Cubic {
+                  control1: bezier_point(&[*from, control1], 0.5),
+                  control2: bezier_point(&[*from, control1, control2], 0.5),
+                  end: bezier_point(&[*from, control1, control2, end], 0.5),
+               },
+               PathEdge::Cubic {
+                  control1: bezier_point(&[control1, control2, end], 0.5),
+                  control2: bezier_point(&[control2, end], 0.5),
+                  end,
+               },
+            )
+         },
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct Contour {
+   pub start: Point,
+   pub edges: Vec<PathEdge>,
+   pub closed: bool,
+}
+
+impl Contour {
+   #[inline]
+   pub fn new(start: Point) -> Self {
+      Contour {
+         start,
+         edges: Vec::new(),
+         closed: false,
+      }
+   }
+
+   #[inline]
+   pub fn end(&self) -> Point {
+      match self.edges.last() {
+         Some(edge) => edge.end(),
+         None => self.start,
+      }
+   }
+
+   /// Whether the contour runs clockwise, judged by the signed area of a
+   /// polygon through its points, arc middles and curve controls.
+   pub fn clockwise(&self) -> bool {
+      let mut points = vec![self.start];
+      let mut from = self.start;
+
+      for edge in &self.edges {
+         match *edge {
+            PathEdge::Line(end) => {
+               points.push(end);
+            },
+            PathEdge::Arc { end, center, radius, clockwise } => {
+               let pieces = arc_pieces(&from, &end, &center, radius, clockwise);
+
+               for piece in pieces.windows(2) {
+                  let half = arc_sweep(&piece[0], &piece[1], &center, clockwise) / 2.;
+                  let start = angle(&piece[0], &center);
+                  let middle = if clockwise { start - half } else { start + half };
+
+                  points.push(circle_point(&center, radius, middle));
+                  points.push(piece[1]);
+               }
+            },
+            PathEdge::Quad { control, end } => {
+               points.push(control);
+               points.push(end);
+            },
+            PathEdge::Cubic { control1, control2, end } => {
+               points.push(control1);
+               points.push(control2);
+               points.push(end);
+            },
+         }
+
+         from = edge.end();
+      }
+
+      let mut area = 0.;
+      for i in 0..points.len() {
+         let p1 = &points[i];
+         let p2 = &points[(i + 1) % points.len()];
+         area += (p2.x - p1.x) as f64 * (p1.y + p2.y) as f64;
+      }
+
+      area > 0.
+   }
+
+   /// Contour mapped through `transform`. Arcs stay circular under
+   /// rotations and uniform scaling and become cubic Béziers otherwise.
+   /// Mirroring transforms reverse the contour to keep its orientation.
+   pub fn transform(&self, transform: &Affine) -> Contour {
+      let det = transform.determinant();
+      let scale = det.abs().sqrt();
+      let similar = transform.is_similarity();
+
+      let mut contour = Contour::new(map_point(transform, &self.start));
+      contour.closed = self.closed;
+
+      let mut from = self.start;
+
+      for edge in &self.edges {
+         match *edge {
+            PathEdge::Line(end) => {
+               contour.edges.push(PathEdge::Line(map_point(transform, &end)));
+            },
+            PathEdge::Arc { end, center, radius, clockwise } if similar => {
+               let center = map_point(transform, &center);
+               let radius = (radius as f64 * scale).round() as i64;
+
+               if radius == 0 {
+                  contour.edges.push(PathEdge::Line(map_point(transform, &end)));
+               } else {
+                  contour.edges.push(PathEdge::Arc {
+                     end: snap_to_circle(&map_point(transform, &end), &center, radius),
+                     center,
+                     radius,
+                     clockwise: clockwise == (det > 0.),
+                  });
+               }
+            },
+            PathEdge::Arc { end, center, radius, clockwise } => {
+               let pieces = arc_pieces(&from, &end, &center, radius, clockwise);
+
+               for piece in pieces.windows(2) {
+                  let sweep = arc_sweep(&piece[0], &piece[1], &center, clockwise);
+                  let sweep = if clockwise { -sweep } else { sweep };
+
+                  let a0 = angle(&piece[0], &center);
+                  let a1 = a0 + sweep;
+
+                  // Cubic approximation of a circular arc of at most a quarter
+                  let k = 4. / 3. * (sweep / 4.).tan() * radius as f64;
+
+                  let (cx, cy) = (center.x as f64, center.y as f64);
+                  let (r, (sin0, cos0), (sin1, cos1)) = (radius as f64, a0.sin_cos(), a1.sin_cos());
+
+                  contour.edges.push(PathEdge::Cubic {
+                     control1: map_f64(transform, cx + r * cos0 - k * sin0, cy + r * sin0 + k * cos0),
+                     control2: map_f64(transform, cx + r * cos1 + k * sin1, cy + r * sin1 - k * cos1),
+                     end: map_point(transform, &piece[1]),
+                  });
+               }
+            },
+            PathEdge::Quad { control, end } => {
+               contour.edges.push(PathEdge::Quad {
+                  control: map_point(transform, &control),
+                  end: map_point(transform, &end),
+               });
+            },
+            PathEdge::Cubic { control1, control2, end } => {
+               contour.edges.push(PathEdge::Cubic {
+                  control1: map_point(transform, &control1),
+                  control2: map_point(transform, &control2),
+                  end: map_point(transform, &end),
+               });
+            },
+         }
+
+         from = edge.end();
+      }
+
+      if det < 0. {
+         contour.reversed()
+      } else {
+         contour
+      }
+   }
+
+   /// The same outline traversed in the opposite direction.
+   pub fn reversed(&self) -> Contour {
+      let mut contour = Contour::new(self.end());
+      contour.closed = self.closed;
+
+      let mut ends = vec![self.start];
+      ends.extend(self.edges.iter().map(|edge| edge.end()));
+
+      for (i, edge) in self.edges.iter().enumerate().rev() {
+         let end = ends[i];
+
+         contour.edges.push(match *edge {
+            PathEdge::Line(_) => PathEdge::Line(end),
+            PathEdge::Arc { center, radius, clockwise, .. } => {
+               PathEdge::Arc { end, center, radius, clockwise: !clockwise }
+            },
+            PathEdge::Quad { control, .. } => PathEdge::Quad { control, end },
+            PathEdge::Cubic { control1, control2, .. } => {
+               PathEdge::Cubic { control1: control2, control2: control1, end }
+            },
+         });
+      }
+
+      contour
+   }
+
+   /// The same outline with its first edge cut in two.
+   pub fn split_first(&self) -> Contour {
+      let mut contour = self.clone();
+
+      if let Some(first) = self.edges.first() {
+         let (head, tail) = first.split(&self.start);
+         contour.edges[0] = head;
+         contour.edges.insert(1, tail);
+      }
+
+      contour
+   }
+}
+
+/// Decides which areas of a path with crossing or nested contours are
+/// inside, from the winding number of the contours around them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum FillRule {
+   #[default]
+   NonZero,
+   EvenOdd,
+}
+
+impl FillRule {
+   #[inline]
+   pub fn inside(&self, winding: i32) -> bool {
+      match *self {
+         FillRule::NonZero => winding != 0,
+         FillRule::EvenOdd => winding % 2 != 0,
+      }
+   }
+}
+
+/// Outline made of lines, circular arcs and Bézier curves in world
+/// coordinates. Every contour is filled as if closed; `SceneBuilder` turns
+/// paths of simple contours into a `Scene`, while `SceneCompiler` also
+/// resolves crossings using `fill_rule`.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+   pub contours: Vec<Contour>,
+   pub fill_rule: FillRule,
+}
+
+impl Path {
+   #[inline]
+   pub fn new() -> Self {
+      Path {
+         contours: Vec::new(),
+         fill_rule: FillRule::NonZero,
+      }
+   }
+
+   /// Path mapped through `transform`, see `Contour::transform`. Points are
+   /// rounded to the integer grid, and must stay in the rasterizer's range.
+   pub fn transform(&self, transform: &Affine) -> Path {
+      Path {
+         contours: self.contours.iter().map(|contour| contour.transform(transform)).collect(),
+         fill_rule: self.fill_rule,
+      }
+   }
+
+   pub fn move_to(&mut self, x: i64, y: i64) {
+      self.contours.push(Contour::new(Point::new(x, y)));
+   }
+
+   pub fn line_to(&mut self, x: i64, y: i64) {
+      if let Some(contour) = self.current(x, y) {
+         contour.edges.push(PathEdge::Line(Point::new(x, y)));
+      }
+   }
+
+   /// Circular arc around `(cx, cy)` from the current point to `(x, y)`.
+   /// The radius is taken from the current point and the end point is moved
+   /// onto the circle. Ending at the current point draws a full circle.
+   pub fn arc_to(&mut self, x: i64, y: i64, cx: i64, cy: i64, clockwise: bool) {
+      if let Some(contour) = self.current(x, y) {
+         let start = contour.end();
+         let center = Point::new(cx, cy);
+
+         let radius = distance(&start, &center).round() as i64;
+         if radius == 0 {
+            return;
+         }
+
+         let end = snap_to_circle(&Point::new(x, y), &center, radius);
+
+         contour.edges.push(PathEdge::Arc { end, center, radius, clockwise });
+      }
+   }
+
+   pub fn quad_to(&mut self, cx: i64, cy: i64, x: i64, y: i64) {
+      if let Some(contour) = self.current(x, y) {
+         contour.edges.push(PathEdge::Quad {
+            control: Point::new(cx, cy),
+            end: Point::new(x, y),
+         });
+      }
+   }
+
+   pub fn cubic_to(&mut self, c1x: i64, c1y: i64, c2x: i64, c2y: i64, x: i64, y: i64) {
+      if let Some(contour) = self.current(x, y) {
+         contour.edges.push(PathEdge::Cubic {
+            control1: Point::new(c1x, c1y),
+            control2: Point::new(c2x, c2y),
+            end: Point::new(x, y),
+         });
+      }
+   }
+
+   /// Closes the current contour with a line back to its start. Drawing
+   /// after a close starts a new contour at the same point.
+   pub fn close(&mut self) {
+      if let Some(contour) = self.contours.last_mut() {
+         if contour.closed {
+            return;
+         }
+
+         if contour.end() != contour.start {
+            let start = contour.start;
+            contour.edges.push(PathEdge::Line(start));
+         }
+
+         contour.closed = true;
+      }
+   }
+
+   /// Contour the next edge is appended to. Without a current point the
+   /// edge end just starts a new contour and `None` is returned.
+   fn current(&mut self, x: i64, y: i64) -> Option<&mut Contour> {
+      let start = match self.contours.last() {
+         None => {
+            self.move_to(x, y);
+            return None;
+         },
+         Some(contour) if contour.closed => Some(contour.start),
+         _ => None,
+      };
+
+      if let Some(start) = start {
+         self.contours.push(Contour::new(start));
+      }
+
+      self.contours.last_mut()
+   }
+}
+
+#[inline]
+fn distance(p1: &Point, p2: &Point) -> f64 {
+   let dx = (p1.x - p2.x) as f64;
+   let dy = (p1.y - p2.y) as f64;
+
+   (dx * dx + dy * dy).sqrt()
+}
+
+#[inline]
+fn map_point(transform: &Affine, p: &Point) -> Point {
+   map_f64(transform, p.x as f64, p.y as f64)
+}
+
+#[inline]
+fn map_f64(transform: &Affine, x: f64, y: f64) -> Point {
+   let (x, y) = transform.apply(x, y);
+   Point::new(x.round() as i64, y.round() as i64)
+}
+
+fn snap_to_circle(p: &Point, center: &Point, radius: i64) -> Point {
+   circle_point(center, radius, angle(p, center))
+}
+
+#[inline]
+pub fn circle_point(center: &Point, radius: i64, angle: f64) -> Point {
+   Point::new(
+      center.x + (radius as f64 * angle.cos()).round() as i64,
+      center.y + (radius as f64 * angle.sin()).round() as i64,
+   )
+}
+
+/// Point where the circle meets an axis, with quarter turns counted
+/// anti-clockwise from the positive x axis.
+#[inline]
+fn axis_point(center: &Point, radius: i64, quarter: i64) -> Point {
+   match quarter.rem_euclid(4) {
+      0 => Point::new(center.x + radius, center.y),
+      1 => Point::new(center.x, center.y + radius),
+      2 => Point::new(center.x - radius, center.y),
+      _ => Point::new(center.x, center.y - radius),
+   }
+}
+
+/// Angle of `p` around `center`, anti-clockwise from the positive x axis.
+#[inline]
+pub fn angle(p: &Point, center: &Point) -> f64 {
+   ((p.y - center.y) as f64).atan2((p.x - center.x) as f64)
+}
+
+/// Angle covered by an arc from `start` to `end`, in `(0, 2π]` so that equal
+/// points make a full circle.
+pub fn arc_sweep(start: &Point, end: &Point, center: &Point, clockwise: bool) -> f64 {
+   let a_start = angle(start, center);
+   let a_end = angle(end, center);
+
+   let mut sweep = if clockwise { a_start - a_end } else { a_end - a_start };
+   while sweep <= 0. {
+      sweep += 4. * FRAC_PI_2;
+   }
+
+   sweep
+}
+
+/// Splits an arc at the points where it crosses the circle axes, so every
+/// piece stays within one quadrant. Returns the piece end points in order.
+pub fn arc_pieces(start: &Point, end: &Point, center: &Point, radius: i64, clockwise: bool) -> Vec<Point> {
+   let a_start = angle(start, center);
+   let sweep = arc_sweep(start, end, center, clockwise);
+
+   let mut points = vec![*start];
+
+   let turns = a_start / FRAC_PI_2;
+   let (mut quarter, step) = if clockwise {
+      (turns.ceil() as i64 - 1, -1)
+   } else {
+      (turns.floor() as i64 + 1, 1)
+   };
+
+   loop {
+      let travelled = (quarter as f64 * FRAC_PI_2 - a_start).abs();
+      if travelled >= sweep - 1e-9 {
+         break;
+      }
+
+      let p = axis_point(center, radius, quarter);
+      if p != *points.last().unwrap() {
+         points.push(p);
+      }
+
+      quarter += step;
+   }
+
+   if *end != *points.last().unwrap() || points.len() == 1 {
+      points.push(*end);
+   }
+
+   points
+}
+
+/// Splits a Bézier curve given by its control points into lines, with
+/// enough steps to keep them within `tolerance` of the curve. Returns the
+/// line end points, without the start point.
+pub fn flatten_bezier(controls: &[Point], tolerance: f64) -> Vec<Point> {
+   let degree = controls.len() - 1;
+
+   let mut deviation: f64 = 0.;
+   for i in 0..degree - 1 {
+      let dx = controls[i].x - 2 * controls[i + 1].x + controls[i + 2].x;
+      let dy = controls[i].y - 2 * controls[i + 1].y + controls[i + 2].y;
+      deviation = deviation.max(((dx * dx + dy * dy) as f64).sqrt());
+   }
+
+   let factor = (degree * (degree - 1)) as f64 / 8.;
+   let steps = ((factor * deviation / tolerance.max(1.)).sqrt().ceil() as usize).max(1);
+
+   (1..steps + 1).map(|step| bezier_point(controls, step as f64 / steps as f64)).collect()
+}
+
+fn bezier_point(controls: &[Point], t: f64) -> Point {
+   let mut x: Vec<f64> = controls.iter().map(|p| p.x as f64).collect();
+   let mut y: Vec<f64> = controls.iter().map(|p| p.y as f64).collect();
+
+   for level in 1..controls.len() {
+      for i in 0..controls.len() - level {
+         x[i] += (x[i + 1] - x[i]) * t;
+         y[i] += (y[i + 1] - y[i]) * t;
+      }
+   }
+
+   Point::new(x[0].round() as i64, y[0].round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::point::Point;
+
+   use geom::affine::Affine;
+
+   use super::{Path, PathEdge, arc_pieces, flatten_bezier};
+
+   #[test]
+   fn test_arc_pieces() {
+      let center = Point::new(100, 100);
+
+      let pieces = arc_pieces(&Point::new(150, 100), &Point::new(150, 100), &center, 50, true);
+      assert_eq!(pieces, vec![
+         Point::new(150, 100),
+         Point::new(100, 50),
+         Point::new(50, 100),
+         Point::new(100, 150),
+         Point::new(150, 100),
+      ]);
+
+      let pieces = arc_pieces(&Point::new(130, 140), &Point::new(60, 130), &center, 50, false);
+      assert_eq!(pieces, vec![
+         Point::new(130, 140),
+         Point::new(100, 150),
+         Point::new(60, 130),
+      ]);
+   }
+
+   #[test]
+   fn test_path_contours() {
+      let mut path = Path::new();
+
+      path.line_to(10, 10);
+      path.line_to(20, 10);
+      path.arc_to(0, 11, 10, 10, false);
+      path.close();
+      path.line_to(10, 30);
+
+      assert_eq!(path.contours.len(), 2);
+
+      let contour = &path.contours[0];
+      assert_eq!(contour.start, Point::new(10, 10));
+      assert_eq!(contour.edges[1], PathEdge::Arc {
+         end: Point::new(0, 11),
+         center: Point::new(10, 10),
+         radius: 10,
+         clockwise: false,
+      });
+      assert_eq!(contour.edges[2], PathEdge::Line(Point::new(10, 10)));
+      assert!(contour.closed);
+
+      assert_eq!(path.contours[1].start, Point::new(10, 10));
+      assert!(!path.contours[1].closed);
+   }
+
+   #[test]
+   fn test_path_transform() {
+      let mut path = Path::new();
+      path.move_to(1000, 0);
+      path.arc_to(1000, 0, 0, 0, true);
+
+      let similar = path.transform(&Affine::scale(2., 2.).then(&Affine::translate(5000., 5000.)));
+      assert_eq!(similar.contours[0].start, Point::new(7000, 5000));
+      assert_eq!(similar.contours[0].edges, vec![PathEdge::Arc {
+         end: Point::new(7000, 5000),
+         center: Point::new(5000, 5000),
+         radius: 2000,
+         clockwise: true,
+      }]);
+
+      let mirrored = path.transform(&Affine::scale(-1., 1.));
+      assert!(mirrored.contours[0].clockwise());
+
+      let ellipse = path.transform(&Affine::scale(3., 1.));
+      let contour = &ellipse.contours[0];
+      assert!(contour.clockwise());
+      assert_eq!(contour.edges.len(), 4);
+
+      let mut from = contour.start;
+      for edge in &contour.edges {
+         match *edge {
+            PathEdge::Cubic { control1, control2, end } => {
+               for p in flatten_bezier(&[from, control1, control2, end], 1.) {
+                  let (x, y) = (p.x as f64 / 3000., p.y as f64 / 1000.);
+                  assert!(((x * x + y * y).sqrt() - 1.).abs() < 0.005);
+               }
+
+               from = end;
+            },
+            _ => panic!("Arc not converted to a curve: {:?}", edge),
+         }
+      }
+
+      assert_eq!(from, Point::new(3000, 0));
+   }
+}
@@ -1,18 +1,25 @@
 
+/// Filled shape made of the clockwise outer contour in edges `start..end`
+/// and any number of hole contours, given as anti-clockwise edge ranges
+/// inside the outer one. Polys are filled with `Scene::paints[paint]`.
 #[derive(Debug, Clone)]
 pub struct Poly {
    pub start: usize,
    pub end: usize,
-   pub color: usize,
+   pub paint: usize,
+   pub opacity: u8,
+   pub holes: Vec<(usize, usize)>,
 }
 
 impl Poly {
    #[inline]
-   pub fn new(start: usize, end: usize, color: usize) -> Self {
+   pub fn new(start: usize, end: usize, paint: usize) -> Self {
       Poly {
          start: start,
          end: end,
-         color: color,
+         paint: paint,
+         opacity: 255,
+         holes: Vec::new(),
       }
    }
 }
@@ -23,11 +30,13 @@ impl Default for Poly {
    }
 }
 
+/// Single contour of the scene poly `src`, as sliced by the rasterizer.
 #[derive(Debug, Clone, Copy)]
 pub struct PolyRef {
    pub start: usize,
    pub end: usize,
    pub src: usize,
+   pub hole: bool,
 }
 
 impl PolyRef {
@@ -37,6 +46,17 @@ impl PolyRef {
          start: start,
          end: end,
          src: src,
+         hole: false,
+      }
+   }
+
+   #[inline]
+   pub fn new_hole(start: usize, end: usize, src: usize) -> Self {
+      PolyRef {
+         start,
+         end,
+         src,
+         hole: true,
       }
    }
 }
@@ -1,30 +1,40 @@
-use std::cmp::{Ordering, min};
-use std::iter::repeat;
+use std::cmp::{Ordering, min, max};
 use std::i64;
+use std::ops::Range;
+use std::sync::Arc;
 
 use frame::Frame;
-use draw::RGB;
+use draw::{RGBA, GammaTable};
 use num::NumberOps;
 use geom::point::Point;
+use error::{RuntimeError, ErrorKind};
 
-use super::intersection::{IntersectionRef, RasterizerIntersection};
+use super::intersection::{Intersections, RasterizerIntersection, intersections_len};
 use super::pool::RasterizerPool;
 use super::edge::{Edge, EdgeType};
+use super::curve::Bezier;
 use super::scene::Scene;
+use super::poly::PolyRef;
+use super::lcd::{Lcd, LcdRow};
 
 
+/// Largest supported absolute point coordinate and circle radius, keeping
+/// the squared and area terms within `i64`.
+pub const MAX_COORD: i64 = 1 << 30;
+
+pub const MAX_DIV_PER_PIXEL: i64 = 1 << 20;
+
 macro_rules! debug_check {
    ($expr:expr) => (if cfg!(debug_assertions) { $expr; })
 }
 
 pub struct Rasterizer {
-   pub vert_intersections_ref: Vec<IntersectionRef>,
-   pub hori_intersections_ref: Vec<IntersectionRef>,
-   pub vert_intersections: Vec<i64>,
-   pub hori_intersections: Vec<i64>,
+   pub intersections: Arc<Intersections>,
 
    pub polys_len: usize,
    pub poly_to_pool: Vec<usize>,
+   pub poly_refs: Vec<PolyRef>,
+   pub poly_areas: Vec<i64>,
 
    pub upper_edges: Vec<Edge>,
    pub upper_edges_len: Vec<usize>,
@@ -54,45 +64,49 @@ pub struct Rasterizer {
    pub final_active_full: usize,
 
    pub div_per_pixel: i64,
+   pub div_per_row: i64,
    pub double_pixel_area: i64,
+
+   pub min_x: i64,
+   pub max_x: i64,
+
+   /// Mixes colors by area and composites them in linear light instead of
+   /// sRGB, which keeps anti-aliased edges from looking too dark and thin.
+   pub linear_light: bool,
 }
 
 impl Rasterizer {
    pub fn new() -> Self {
-      let vert_intersections_ref = create_default_vec(65536);
-      let hori_intersections_ref = create_default_vec(65536);
-      let vert_intersections = create_default_vec(65536);
-      let hori_intersections = create_default_vec(65536);
-
-      let poly_to_pool = create_default_vec(65536);
+      let poly_to_pool = Vec::new();
+      let poly_refs = Vec::new();
+      let poly_areas = Vec::new();
 
-      let upper_edges = create_default_vec(65536);
-      let upper_edges_len = create_default_vec(65536);
+      let upper_edges = Vec::new();
+      let upper_edges_len = Vec::new();
 
-      let upper_min_y = create_default_vec(65536);
-      let upper_max_y = create_default_vec(65536);
-      let upper_active = create_default_vec(65536);
+      let upper_min_y = Vec::new();
+      let upper_max_y = Vec::new();
+      let upper_active = Vec::new();
 
-      let lower_edges = create_default_vec(65536);
-      let lower_edges_len = create_default_vec(65536);
+      let lower_edges = Vec::new();
+      let lower_edges_len = Vec::new();
 
-      let lower_min_x = create_default_vec(65536);
-      let lower_max_x = create_default_vec(65536);
-      let lower_active = create_default_vec(65536);
+      let lower_min_x = Vec::new();
+      let lower_max_x = Vec::new();
+      let lower_active = Vec::new();
 
-      let final_edges = create_default_vec(65536);
-      let final_edges_len = create_default_vec(65536);
+      let final_edges = Vec::new();
+      let final_edges_len = Vec::new();
 
-      let final_active = create_default_vec(65536);
+      let final_active = Vec::new();
 
       Rasterizer {
-         vert_intersections_ref: vert_intersections_ref,
-         hori_intersections_ref: hori_intersections_ref,
-         vert_intersections: vert_intersections,
-         hori_intersections: hori_intersections,
+         intersections: Arc::default(),
 
          polys_len: 0,
          poly_to_pool: poly_to_pool,
+         poly_refs,
+         poly_areas,
 
          upper_edges: upper_edges,
          upper_edges_len: upper_edges_len,
@@ -122,13 +136,178 @@ impl Rasterizer {
          final_active_full: 0,
 
          div_per_pixel: 0,
+         div_per_row: 0,
          double_pixel_area: 0,
+
+         min_x: 0,
+         max_x: 0,
+
+         linear_light: false,
+      }
+   }
+
+   pub fn render(&mut self, scene: &Scene, frame: &mut Frame, div_per_pixel: i64) -> Result<(), RuntimeError> {
+      let (x_start, x_end, y_start, y_end) = match self.prepare(scene, div_per_pixel, frame.width as i64, frame.height as i64)? {
+         Some(bounds) => bounds,
+         None => return Ok(())
+      };
+
+      let linear_light = self.linear_light;
+
+      for y in y_start..y_end {
+         self.slice_row(y);
+
+         self.render_row(scene, y, x_start, x_end, |x, color| {
+            if linear_light {
+               frame.blend_pixel_linear(x as i32, y as i32, color);
+            } else {
+               frame.blend_pixel(x as i32, y as i32, color);
+            }
+         });
+      }
+
+      Ok(())
+   }
+
+   /// Renders the scene for an LCD panel, resolving coverage separately for
+   /// the three horizontal sub-pixels of every pixel. Columns are sliced at
+   /// a third of the pixel width, so `div_per_pixel` must be divisible by 3,
+   /// while rows keep the full pixel height.
+   pub fn render_lcd(&mut self, scene: &Scene, frame: &mut Frame, div_per_pixel: i64, lcd: &Lcd) -> Result<(), RuntimeError> {
+      if div_per_pixel % 3 != 0 {
+         return Err(invalid_scene(
+            format!("LCD division per pixel not divisible by 3: {}", div_per_pixel)
+         ));
       }
+
+      let (x_start, x_end, y_start, y_end) = match self.prepare_cells(scene, div_per_pixel / 3, div_per_pixel, frame.width as i64 * 3, frame.height as i64)? {
+         Some(bounds) => bounds,
+         None => return Ok(())
+      };
+
+      let mut row = LcdRow::new(frame.width as usize, self.linear_light);
+
+      for y in y_start..y_end {
+         self.slice_row(y);
+
+         self.render_row(scene, y, x_start, x_end, |x, color| {
+            row.add(x, color);
+         });
+
+         row.flush(frame, y, lcd);
+      }
+
+      Ok(())
+   }
+
+   /// Renders the pixel `rows` of a scene already prepared by `prepared`,
+   /// with the bounds it returned, into `buffer`, a `width` pixels wide band
+   /// where untouched pixels are left fully transparent. The prepared pools
+   /// are copied and sliced off below the band in one pass while the
+   /// intersections are shared, so the result matches `render` exactly.
+   pub fn render_band(
+      &mut self,
+      prepared: &Rasterizer,
+      scene: &Scene,
+      bounds: (i64, i64, i64, i64),
+      rows: Range<i64>, width: i64,
+      buffer: &mut Vec<RGBA>
+   ) -> Result<(), RuntimeError> {
+      let (y0, y1) = (rows.start, rows.end);
+
+      buffer.clear();
+      buffer.resize(((y1 - y0) * width) as usize, RGBA::new(0, 0, 0, 0));
+
+      let (x_start, x_end, y_start, y_end) = bounds;
+
+      let band_start = max(y_start, y0);
+      let band_end = min(y_end, y1);
+
+      if band_start >= band_end {
+         return Ok(());
+      }
+
+      self.copy_prepared(prepared, scene)?;
+
+      if band_start > y_start {
+         self.skip_rows(band_start);
+      }
+
+      for y in band_start..band_end {
+         self.slice_row(y);
+
+         let row_start = ((y - y0) * width) as usize;
+
+         self.render_row(scene, y, x_start, min(x_end, width), |x, color| {
+            buffer[row_start + x as usize] = *color;
+         });
+      }
+
+      // Dropping the shared intersections lets `prepared` update them in
+      // place for the next scene
+      self.intersections = Arc::default();
+
+      Ok(())
+   }
+
+   /// Takes over the sliced state of a prepared scene, sharing its
+   /// intersections.
+   fn copy_prepared(&mut self, prepared: &Rasterizer, scene: &Scene) -> Result<(), RuntimeError> {
+      self.div_per_pixel = prepared.div_per_pixel;
+      self.div_per_row = prepared.div_per_row;
+      self.double_pixel_area = prepared.double_pixel_area;
+      self.min_x = prepared.min_x;
+      self.max_x = prepared.max_x;
+      self.linear_light = prepared.linear_light;
+
+      self.grow_pools(scene)?;
+
+      let (polys_len, edges_len) = pools_len(scene);
+
+      self.polys_len = prepared.polys_len;
+
+      self.poly_to_pool[..polys_len].copy_from_slice(&prepared.poly_to_pool[..polys_len]);
+      self.poly_refs[..polys_len].copy_from_slice(&prepared.poly_refs[..polys_len]);
+
+      self.upper_edges[..edges_len].copy_from_slice(&prepared.upper_edges[..edges_len]);
+      self.upper_edges_len[..polys_len].copy_from_slice(&prepared.upper_edges_len[..polys_len]);
+      self.upper_min_y[..polys_len].copy_from_slice(&prepared.upper_min_y[..polys_len]);
+      self.upper_max_y[..polys_len].copy_from_slice(&prepared.upper_max_y[..polys_len]);
+      self.upper_active[..polys_len].copy_from_slice(&prepared.upper_active[..polys_len]);
+
+      self.upper_active_start = prepared.upper_active_start;
+      self.upper_active_end = prepared.upper_active_end;
+
+      self.intersections = prepared.intersections.clone();
+
+      Ok(())
+   }
+
+   /// Transfers the scene to the pools and computes its intersections.
+   /// Returns the `(x_start, x_end, y_start, y_end)` pixel bounds clipped to
+   /// `width` x `height` pixels or `None` when there is nothing to render.
+   /// Rows below the clipped bounds are already sliced off.
+   pub fn prepare(&mut self, scene: &Scene, div_per_pixel: i64, width: i64, height: i64) -> Result<Option<(i64, i64, i64, i64)>, RuntimeError> {
+      self.prepare_cells(scene, div_per_pixel, div_per_pixel, width, height)
    }
 
-   pub fn render(&mut self, scene: &Scene, frame: &mut Frame, div_per_pixel: i64) {
+   /// Prepares as `prepare` does with cells `div_per_pixel` wide and
+   /// `div_per_row` high, columns no wider than rows.
+   fn prepare_cells(&mut self, scene: &Scene, div_per_pixel: i64, div_per_row: i64, width: i64, height: i64) -> Result<Option<(i64, i64, i64, i64)>, RuntimeError> {
+      check_scene(scene, div_per_row)?;
+
+      debug_assert!(div_per_pixel > 0 && div_per_pixel <= div_per_row);
+
+      if scene.polys.is_empty() {
+         return Ok(None);
+      }
+
       self.div_per_pixel = div_per_pixel;
-      self.double_pixel_area = div_per_pixel * div_per_pixel * 2;
+      self.div_per_row = div_per_row;
+      self.double_pixel_area = div_per_pixel * div_per_row * 2;
+
+      self.grow_pools(scene)?;
+      self.grow_intersections(scene)?;
 
       self.transfer_scene(scene);
 
@@ -142,95 +321,179 @@ impl Rasterizer {
 
       debug_check!(self.check_min_max_x_y(min_x, min_y, max_x, max_y));
 
+      self.min_x = min_x;
+      self.max_x = max_x;
+
       self.update_upper_min_max_y();
 
       debug_check!(self.check_upper_min_max_y(min_y, max_y));
 
-      let x_start = self.to_px(min_x);
-      let x_end = self.to_px(max_x - 1) + 1;
-      let y_start = self.to_px(min_y);
-      let y_end = self.to_px(max_y - 1) + 1;
+      let x_start = max(self.to_px(min_x), 0);
+      let x_end = min(self.to_px(max_x - 1) + 1, width);
+      let y_start = max(self.to_row(min_y), 0);
+      let y_end = min(self.to_row(max_y - 1) + 1, height);
 
-      for y in y_start..y_end {
-         let y_world = self.from_px(y);
-         let y_slice = y_world + self.div_per_pixel;
+      if x_start >= x_end || y_start >= y_end {
+         return Ok(None);
+      }
 
-         self.lower_active_start = 0;
-         self.lower_active_end = 0;
-         self.lower_active_full = 0;
+      if self.from_row(y_start) > min_y {
+         self.skip_rows(y_start);
+      }
 
-         self.advance_upper_range(y_world, y_slice);
+      Ok(Some((x_start, x_end, y_start, y_end)))
+   }
+
+   /// Slices the scene at the bottom of pixel row `y` and drops everything
+   /// below, so that rendering can start at that row.
+   fn skip_rows(&mut self, y: i64) {
+      self.slice_row(y - 1);
+   }
 
-         debug_check!(self.check_upper_range(y_slice));
+   /// Slices the current row at the left of pixel column `x` and drops
+   /// everything to the left, so that rendering can start at that column.
+   fn skip_columns(&mut self, x: i64) {
+      let x_slice = self.from_px(x);
 
-         debug_check!(self.check_upper_pool());
+      self.final_active_full = 0;
 
-         self.h_slice(y_slice, y + 1);
+      self.advance_lower_range(x_slice - self.div_per_pixel, x_slice);
 
-         debug_check!(self.check_upper_bounds(y_slice));
+      debug_check!(self.check_lower_range(x_slice));
 
-         debug_check!(self.check_lower_initial_pool());
+      self.v_slice(x_slice, x);
 
-         debug_check!(self.check_lower_initial_bounds(y_slice));
+      self.final_active_full = 0;
+   }
 
-         self.update_lower_min_max_x();
+   fn slice_row(&mut self, y: i64) {
+      let y_world = self.from_row(y);
+      let y_slice = y_world + self.div_per_row;
 
-         debug_check!(self.check_lower_min_max_x(min_x, max_x));
+      self.lower_active_start = 0;
+      self.lower_active_end = 0;
+      self.lower_active_full = 0;
 
-         let mut x = x_start;
+      self.advance_upper_range(y_world, y_slice);
 
-         while x < x_end {
-            let x_world = self.from_px(x);
-            let x_slice = x_world + self.div_per_pixel;
+      debug_check!(self.check_upper_range(y_slice));
 
-            self.final_active_full = 0;
+      debug_check!(self.check_upper_pool());
 
-            self.advance_lower_range(x_world, x_slice);
+      self.h_slice(y_slice, y + 1);
 
-            debug_check!(self.check_lower_range(x_slice));
+      debug_check!(self.check_upper_bounds(y_slice));
 
-            debug_check!(self.check_lower_pool());
+      debug_check!(self.check_lower_initial_pool());
 
-            match self.can_advance_stripe(x_end) {
-               Some(x_delta) => {
-                  let poly_index = self.lower_active[self.lower_active_start];
+      debug_check!(self.check_lower_initial_bounds(y_slice));
+   }
 
-                  let x_delta_world = self.from_px(x_delta);
-                  self.v_slice_poly(poly_index, x_delta_world, x_delta);
+   fn render_row<F>(&mut self, scene: &Scene, y: i64, x_start: i64, x_end: i64, mut put_pixel: F) where F: FnMut(i64, &RGBA) {
+      self.update_lower_min_max_x();
 
-                  let ref color = scene.colors[scene.polys[poly_index].color];
+      debug_check!(self.check_lower_min_max_x(self.min_x, self.max_x));
 
-                  for fill_x in x..x_delta {
-                     frame.put_pixel(fill_x as i32, y as i32, &color);
-                  }
+      if self.from_px(x_start) > self.min_x {
+         self.skip_columns(x_start);
+      }
 
-                  x = x_delta;
-               },
-               None => {
-                  self.v_slice(x_slice, x + 1);
+      let mut x = x_start;
 
-                  debug_check!(self.check_lower_bounds(x_slice));
+      while x < x_end {
+         let x_world = self.from_px(x);
+         let x_slice = x_world + self.div_per_pixel;
 
-                  debug_check!(self.check_final_pool());
+         self.final_active_full = 0;
 
-                  debug_check!(self.check_final_bounds(x_slice));
+         self.advance_lower_range(x_world, x_slice);
 
-                  if self.final_active_full != 0 {
-                     let color = self.active_color(scene);
+         debug_check!(self.check_lower_range(x_slice));
 
-                     frame.put_pixel(x as i32, y as i32, &color);
-                  }
+         debug_check!(self.check_lower_pool());
+
+         match self.can_advance_stripe(x, y, x_end) {
+            Some(x_delta) => {
+               let poly_index = self.lower_active[self.lower_active_start];
+
+               let x_delta_world = self.from_px(x_delta);
+               self.v_slice_poly(poly_index, x_delta_world, x_delta);
+
+               for fill_x in x..x_delta {
+                  let color = self.poly_color(poly_index, scene, fill_x, y);
+                  put_pixel(fill_x, &color);
+               }
+
+               x = x_delta;
+            },
+            None => {
+               self.v_slice(x_slice, x + 1);
+
+               debug_check!(self.check_lower_bounds(x_slice));
+
+               debug_check!(self.check_final_pool());
+
+               debug_check!(self.check_final_bounds(x_slice));
 
-                  x += 1;
+               if self.final_active_full != 0 {
+                  let color = self.active_color(scene, x, y);
+
+                  put_pixel(x, &color);
                }
+
+               x += 1;
             }
          }
       }
    }
 
+   /// Grows all slicing pools to fit the scene. Pools are never shrunk, so
+   /// rendering scenes of similar size does not reallocate.
+   fn grow_pools(&mut self, scene: &Scene) -> Result<(), RuntimeError> {
+      let (polys_len, edges_len) = pools_len(scene);
+
+      grow_pool(&mut self.poly_to_pool, polys_len)?;
+      grow_pool(&mut self.poly_refs, polys_len)?;
+      grow_pool(&mut self.poly_areas, scene.polys.len())?;
+
+      grow_pool(&mut self.upper_edges, edges_len)?;
+      grow_pool(&mut self.upper_edges_len, polys_len)?;
+      grow_pool(&mut self.upper_min_y, polys_len)?;
+      grow_pool(&mut self.upper_max_y, polys_len)?;
+      grow_pool(&mut self.upper_active, polys_len)?;
+
+      grow_pool(&mut self.lower_edges, edges_len)?;
+      grow_pool(&mut self.lower_edges_len, polys_len)?;
+      grow_pool(&mut self.lower_min_x, polys_len)?;
+      grow_pool(&mut self.lower_max_x, polys_len)?;
+      grow_pool(&mut self.lower_active, polys_len)?;
+
+      grow_pool(&mut self.final_edges, edges_len)?;
+      grow_pool(&mut self.final_edges_len, polys_len)?;
+      grow_pool(&mut self.final_active, polys_len)?;
+
+      Ok(())
+   }
+
+   fn grow_intersections(&mut self, scene: &Scene) -> Result<(), RuntimeError> {
+      let segments_len = scene.segments.len();
+      let (vert_len, hori_len) = intersections_len(scene, self.div_per_pixel, self.div_per_row);
+
+      let intersections = Arc::make_mut(&mut self.intersections);
+
+      grow_pool(&mut intersections.vert_intersections_ref, segments_len)?;
+      grow_pool(&mut intersections.hori_intersections_ref, segments_len)?;
+      grow_pool(&mut intersections.vert_intersections, vert_len)?;
+      grow_pool(&mut intersections.hori_intersections, hori_len)?;
+      grow_pool(&mut intersections.vert_intersections_t, vert_len)?;
+      grow_pool(&mut intersections.hori_intersections_t, hori_len)?;
+
+      Ok(())
+   }
+
    #[inline]
    pub fn to_px(&self, v: i64) -> i64 {
-      v / self.div_per_pixel
+      v.div_euclid(self.div_per_pixel)
    }
 
    #[inline]
@@ -238,23 +501,54 @@ impl Rasterizer {
       v * self.div_per_pixel
    }
 
+   #[inline]
+   pub fn to_row(&self, v: i64) -> i64 {
+      v.div_euclid(self.div_per_row)
+   }
+
+   #[inline]
+   pub fn from_row(&self, v: i64) -> i64 {
+      v * self.div_per_row
+   }
+
    pub fn transfer_scene(&mut self, scene: &Scene) {
-      self.polys_len = scene.polys.len();
+      let mut ref_index = 0;
+      for (i, poly) in scene.polys.iter().enumerate() {
+         self.poly_refs[ref_index] = PolyRef::new(poly.start, poly.end, i);
+         ref_index += 1;
+
+         for &(start, end) in &poly.holes {
+            self.poly_refs[ref_index] = PolyRef::new_hole(start, end, i);
+            ref_index += 1;
+         }
+      }
+
+      self.polys_len = ref_index;
 
       let mut pool_index = 0;
       for i in 0..self.polys_len {
-         let ref poly = &scene.polys[i];
+         let poly_ref = self.poly_refs[i];
          self.poly_to_pool[i] = pool_index;
-         self.upper_edges_len[i] = poly.end - poly.start;
+         self.upper_edges_len[i] = poly_ref.end - poly_ref.start;
+
+         for j in poly_ref.start..poly_ref.end {
+            // Holes are turned clockwise, so they slice like any poly
+            let (edge, edge_type) = if poly_ref.hole {
+               let edge = &scene.edges[poly_ref.start + poly_ref.end - 1 - j];
+               (edge, edge.edge_type.reverse())
+            } else {
+               let edge = &scene.edges[j];
+               (edge, edge.edge_type)
+            };
 
-         for edge in &scene.edges[poly.start..poly.end] {
             let ref mut edge_ref = self.upper_edges[pool_index];
-            edge_ref.edge_type = edge.edge_type;
+            edge_ref.edge_type = edge_type;
             edge_ref.segment = edge.segment;
             edge_ref.circle = edge.circle;
+            edge_ref.curve = edge.curve;
 
             let ref segment = scene.segments[edge.segment];
-            let (ref p1, ref p2) = if edge.reversed() {
+            let (ref p1, ref p2) = if edge_type.reversed() {
                (scene.points[segment.p2], scene.points[segment.p1])
             } else {
                (scene.points[segment.p1], scene.points[segment.p2])
@@ -263,6 +557,14 @@ impl Rasterizer {
             edge_ref.p1 = *p1;
             edge_ref.p2 = *p2;
 
+            if edge_type.reversed() {
+               edge_ref.t1 = 1.;
+               edge_ref.t2 = 0.;
+            } else {
+               edge_ref.t1 = 0.;
+               edge_ref.t2 = 1.;
+            }
+
             pool_index += 1;
          }
 
@@ -291,8 +593,8 @@ impl Rasterizer {
          let s_min_x = min(p1.x, p2.x);
          let s_min_y = min(p1.y, p2.y);
 
-         let s_max_x = min(p1.x, p2.x);
-         let s_max_y = min(p1.y, p2.y);
+         let s_max_x = max(p1.x, p2.x);
+         let s_max_y = max(p1.y, p2.y);
 
          if s_min_x < min_x {
             min_x = s_min_x;
@@ -749,12 +1051,14 @@ impl Rasterizer {
    }
 
    fn h_slice_poly(&mut self, poly_index: usize, y: i64, y_px: i64) {
-      let p1;
+      let mut p1;
       let mut p2 = Point::default();
 
       let poly_start = self.poly_to_pool[poly_index];
       let poly_end = poly_start + self.upper_edges_len[poly_index];
 
+      rotate_to_min(&mut self.upper_edges[poly_start..poly_end], |p| p.y);
+
       let mut i = poly_start;
       let mut upper_i = poly_start;
       let mut lower_i = poly_start;
@@ -764,7 +1068,8 @@ impl Rasterizer {
 
          match edge.edge_type {
             EdgeType::LTR | EdgeType::LTL | EdgeType::LVT | EdgeType::CTR |
-            EdgeType::CTL | EdgeType::ATR | EdgeType::ATL => {
+            EdgeType::CTL | EdgeType::ATR | EdgeType::ATL | EdgeType::QTR |
+            EdgeType::QTL | EdgeType::KTR | EdgeType::KTL => {
                let y2 = edge.p2.y;
                if y2 < y {
                   self.lower_edges[lower_i] = edge;
@@ -776,6 +1081,12 @@ impl Rasterizer {
 
                   let mut upper_edge = edge.clone();
 
+                  if edge.edge_type.bezier() {
+                     let t = self.h_intersection_t(&edge, y_px);
+                     edge.t2 = t;
+                     upper_edge.t1 = t;
+                  }
+
                   edge.p2 = p1;
                   self.lower_edges[lower_i] = edge;
                   lower_i += 1;
@@ -791,6 +1102,15 @@ impl Rasterizer {
                   self.lower_edges[lower_i] = edge;
                   lower_i += 1;
 
+                  // Edges running on along the slice line stay below it
+                  while i + 1 < poly_end && self.upper_edges[i + 1].edge_type == EdgeType::LHR {
+                     i += 1;
+                     p1 = self.upper_edges[i].p2;
+
+                     self.lower_edges[lower_i] = self.upper_edges[i];
+                     lower_i += 1;
+                  }
+
                   break;
                }
             },
@@ -812,7 +1132,8 @@ impl Rasterizer {
 
          match edge.edge_type {
             EdgeType::LBR | EdgeType::LBL | EdgeType::LVB | EdgeType::CBR |
-            EdgeType::CBL | EdgeType::ABR | EdgeType::ABL => {
+            EdgeType::CBL | EdgeType::ABR | EdgeType::ABL | EdgeType::QBR |
+            EdgeType::QBL | EdgeType::KBR | EdgeType::KBL => {
                let y2 = edge.p2.y;
                if y2 > y {
                   self.upper_edges[upper_i] = edge;
@@ -824,6 +1145,12 @@ impl Rasterizer {
 
                   let mut lower_edge = edge.clone();
 
+                  if edge.edge_type.bezier() {
+                     let t = self.h_intersection_t(&edge, y_px);
+                     edge.t2 = t;
+                     lower_edge.t1 = t;
+                  }
+
                   edge.p2 = p2;
                   self.upper_edges[upper_i] = edge;
                   upper_i += 1;
@@ -842,6 +1169,15 @@ impl Rasterizer {
                   self.upper_edges[upper_i] = edge;
                   upper_i += 1;
 
+                  // Edges running back along the slice line stay above it
+                  while i + 1 < poly_end && self.upper_edges[i + 1].edge_type == EdgeType::LHL {
+                     i += 1;
+                     p2 = self.upper_edges[i].p2;
+
+                     self.upper_edges[upper_i] = self.upper_edges[i];
+                     upper_i += 1;
+                  }
+
                   self.lower_edges[lower_i] = Edge::hori_right(p1, p2);
                   lower_i += 1;
 
@@ -869,8 +1205,7 @@ impl Rasterizer {
          lower_i += 1;
       }
 
-      let last_upper = Edge::hori_left(p2, p1);
-      self.upper_edges[upper_i] = last_upper;
+      self.upper_edges[upper_i] = Edge::hori_left(p2, p1);
       upper_i += 1;
 
       self.upper_edges_len[poly_index] = upper_i - poly_start;
@@ -890,12 +1225,14 @@ impl Rasterizer {
    }
 
    fn v_slice_poly(&mut self, poly_index: usize, x: i64, x_px: i64) {
-      let p1;
+      let mut p1;
       let mut p2 = Point::default();
 
       let poly_start = self.poly_to_pool[poly_index];
       let poly_end = poly_start + self.lower_edges_len[poly_index];
 
+      rotate_to_min(&mut self.lower_edges[poly_start..poly_end], |p| p.x);
+
       let mut i = poly_start;
       let mut lower_i = poly_start;
       let mut final_i = poly_start;
@@ -905,7 +1242,8 @@ impl Rasterizer {
 
          match edge.edge_type {
             EdgeType::LTR | EdgeType::LBR | EdgeType::LHR | EdgeType::CTR |
-            EdgeType::CBR | EdgeType::ATR | EdgeType::ABR => {
+            EdgeType::CBR | EdgeType::ATR | EdgeType::ABR | EdgeType::QTR |
+            EdgeType::QBR | EdgeType::KTR | EdgeType::KBR => {
                let x2 = edge.p2.x;
                if x2 < x {
                   self.final_edges[final_i] = edge;
@@ -917,12 +1255,18 @@ impl Rasterizer {
 
                   let mut lower_edge = edge.clone();
 
+                  if edge.edge_type.bezier() {
+                     let t = self.v_intersection_t(&edge, x_px);
+                     edge.t2 = t;
+                     lower_edge.t1 = t;
+                  }
+
                   edge.p2 = p1;
-                  self.final_edges[final_i] = edge;
+                  self.final_edges[final_i] = flattened(edge);
                   final_i += 1;
 
                   lower_edge.p1 = p1;
-                  self.lower_edges[lower_i] = lower_edge;
+                  self.lower_edges[lower_i] = flattened(lower_edge);
                   lower_i += 1;
 
                   break;
@@ -932,6 +1276,15 @@ impl Rasterizer {
                   self.final_edges[final_i] = edge;
                   final_i += 1;
 
+                  // Edges running on along the slice line stay left of it
+                  while i + 1 < poly_end && self.lower_edges[i + 1].edge_type == EdgeType::LVB {
+                     i += 1;
+                     p1 = self.lower_edges[i].p2;
+
+                     self.final_edges[final_i] = self.lower_edges[i];
+                     final_i += 1;
+                  }
+
                   break;
                }
             },
@@ -955,7 +1308,8 @@ impl Rasterizer {
 
          match edge.edge_type {
             EdgeType::LTL | EdgeType::LBL | EdgeType::LHL | EdgeType::CTL |
-            EdgeType::CBL | EdgeType::ATL | EdgeType::ABL => {
+            EdgeType::CBL | EdgeType::ATL | EdgeType::ABL | EdgeType::QTL |
+            EdgeType::QBL | EdgeType::KTL | EdgeType::KBL => {
                let x2 = edge.p2.x;
                if x2 > x {
                   self.lower_edges[lower_i] = edge;
@@ -967,15 +1321,21 @@ impl Rasterizer {
 
                   let mut final_edge = edge.clone();
 
+                  if edge.edge_type.bezier() {
+                     let t = self.v_intersection_t(&edge, x_px);
+                     edge.t2 = t;
+                     final_edge.t1 = t;
+                  }
+
                   edge.p2 = p2;
-                  self.lower_edges[lower_i] = edge;
+                  self.lower_edges[lower_i] = flattened(edge);
                   lower_i += 1;
 
                   self.final_edges[final_i] = Edge::vert_bottom(p1, p2);
                   final_i += 1;
 
                   final_edge.p1 = p2;
-                  self.final_edges[final_i] = final_edge;
+                  self.final_edges[final_i] = flattened(final_edge);
                   final_i += 1;
 
                   break;
@@ -985,6 +1345,15 @@ impl Rasterizer {
                   self.lower_edges[lower_i] = edge;
                   lower_i += 1;
 
+                  // Edges running back along the slice line stay right of it
+                  while i + 1 < poly_end && self.lower_edges[i + 1].edge_type == EdgeType::LVT {
+                     i += 1;
+                     p2 = self.lower_edges[i].p2;
+
+                     self.lower_edges[lower_i] = self.lower_edges[i];
+                     lower_i += 1;
+                  }
+
                   self.final_edges[final_i] = Edge::vert_bottom(p1, p2);
                   final_i += 1;
 
@@ -1012,8 +1381,7 @@ impl Rasterizer {
          final_i += 1;
       }
 
-      let last_lower = Edge::vert_top(p2, p1);
-      self.lower_edges[lower_i] = last_lower;
+      self.lower_edges[lower_i] = Edge::vert_top(p2, p1);
       lower_i += 1;
 
       self.lower_edges_len[poly_index] = lower_i - poly_start;
@@ -1021,7 +1389,7 @@ impl Rasterizer {
    }
 
    #[inline]
-   fn can_advance_stripe(&self, x_end: i64) -> Option<i64> {
+   fn can_advance_stripe(&self, x: i64, y: i64, x_end: i64) -> Option<i64> {
       if self.final_active_full != 0 {
          return None;
       }
@@ -1032,52 +1400,142 @@ impl Rasterizer {
          return None;
       }
 
-      if self.lower_active_end == self.lower_active_full {
-         return Some(x_end);
+      let poly_index = self.lower_active[self.lower_active_start];
+
+      let x_world = self.from_px(x);
+      if self.lower_min_x[poly_index] > x_world {
+         return None;
       }
 
-      let poly_index = self.lower_active[self.lower_active_end];
+      let y_bottom = self.from_row(y);
+      let y_top = y_bottom + self.div_per_row;
+
+      let poly_max_x = self.lower_max_x[poly_index];
+      let mut covered_x = poly_max_x;
 
-      Some(self.lower_min_x[poly_index] / self.div_per_pixel)
+      let poly_start = self.poly_to_pool[poly_index];
+      let poly_end = poly_start + self.lower_edges_len[poly_index];
+
+      // Only pixels the poly covers over the whole row height are filled
+      for edge in &self.lower_edges[poly_start..poly_end] {
+         let on_bounds = match edge.edge_type {
+            EdgeType::LVT | EdgeType::LVB => true,
+            EdgeType::LHR | EdgeType::LHL => edge.p1.y == y_bottom || edge.p1.y == y_top,
+            _ => false,
+         };
+
+         if !on_bounds && max(edge.p1.x, edge.p2.x) > x_world {
+            covered_x = min(covered_x, min(edge.p1.x, edge.p2.x));
+         }
+      }
+
+      // The poly is sliced at the stripe end, which must lie inside it
+      let mut stripe_end = self.to_px(min(covered_x, poly_max_x - 1));
+
+      stripe_end = min(stripe_end, x_end);
+
+      if self.lower_active_end != self.lower_active_full {
+         let next_index = self.lower_active[self.lower_active_end];
+         stripe_end = min(stripe_end, self.to_px(self.lower_min_x[next_index]));
+      }
+
+      if stripe_end > x {
+         Some(stripe_end)
+      } else {
+         None
+      }
    }
 
    #[inline]
-   pub fn active_color(&self, scene: &Scene) -> RGB {
+   fn poly_color(&self, poly_index: usize, scene: &Scene, x: i64, y: i64) -> RGBA {
+      let poly = &scene.polys[self.poly_refs[poly_index].src];
+
+      // Paints are evaluated at the pixel center
+      let x_center = self.from_px(x) as f64 + self.div_per_pixel as f64 / 2.;
+      let y_center = self.from_row(y) as f64 + self.div_per_row as f64 / 2.;
+      let mut color = scene.paints[poly.paint].color_at(x_center, y_center);
+      color.a = (color.a as u32 * poly.opacity as u32 / 255) as u8;
+
+      color
+   }
+
+   #[inline]
+   pub fn active_color(&mut self, scene: &Scene, x: i64, y: i64) -> RGBA {
       let mut r: i64 = 0;
       let mut g: i64 = 0;
       let mut b: i64 = 0;
+      let mut a: i64 = 0;
 
-      let mut total_area: i64 = 0;
-
-      for active_index in 0..self.final_active_full - 1 {
+      // Holes subtract from the area of their poly
+      for active_index in 0..self.final_active_full {
          let poly_index = self.final_active[active_index];
+         let poly_ref = self.poly_refs[poly_index];
 
          let area = self.double_area(poly_index, scene);
 
-         let ref color = scene.colors[scene.polys[poly_index].color];
+         if poly_ref.hole {
+            self.poly_areas[poly_ref.src] -= area;
+         } else {
+            self.poly_areas[poly_ref.src] += area;
+         }
+      }
 
-         r += (color.r as i64) * area;
-         g += (color.g as i64) * area;
-         b += (color.b as i64) * area;
+      // Polys need not cover the whole pixel, the uncovered rest stays
+      // transparent
+      for active_index in 0..self.final_active_full {
+         let poly_index = self.final_active[active_index];
+         let src = self.poly_refs[poly_index].src;
 
-         total_area += area;
-      }
+         // Taken once per poly, with any contour of it
+         let area = max(0, min(self.poly_areas[src], self.double_pixel_area));
+         self.poly_areas[src] = 0;
+
+         if area == 0 {
+            continue;
+         }
+
+         let color = self.poly_color(poly_index, scene, x, y);
+
+         let weight = area * color.a as i64;
 
-      let poly_index = self.final_active[self.final_active_full - 1];
+         if self.linear_light {
+            let gamma = GammaTable::get();
 
-      let area = self.double_pixel_area - total_area;
+            r += gamma.linear(color.r) as i64 * weight;
+            g += gamma.linear(color.g) as i64 * weight;
+            b += gamma.linear(color.b) as i64 * weight;
+         } else {
+            r += (color.r as i64) * weight;
+            g += (color.g as i64) * weight;
+            b += (color.b as i64) * weight;
+         }
+
+         a += weight;
+      }
+
+      if a == 0 {
+         return RGBA::new(0, 0, 0, 0);
+      }
 
-      let ref color = scene.colors[scene.polys[poly_index].color];
+      let alpha = ((a + self.double_pixel_area / 2) / self.double_pixel_area).min(255) as u8;
 
-      r += (color.r as i64) * area;
-      g += (color.g as i64) * area;
-      b += (color.b as i64) * area;
+      if self.linear_light {
+         let gamma = GammaTable::get();
 
-      r /= self.double_pixel_area;
-      g /= self.double_pixel_area;
-      b /= self.double_pixel_area;
+         return RGBA::new(
+            gamma.srgb(((r + a / 2) / a) as u32),
+            gamma.srgb(((g + a / 2) / a) as u32),
+            gamma.srgb(((b + a / 2) / a) as u32),
+            alpha
+         );
+      }
 
-      RGB::new(r as u8, g as u8, b as u8)
+      RGBA::new(
+         (r / a) as u8,
+         (g / a) as u8,
+         (b / a) as u8,
+         alpha
+      )
    }
 
    #[inline]
@@ -1094,12 +1552,11 @@ impl Rasterizer {
             EdgeType::LHR | EdgeType::LHL => {
                area += (edge.p2.x - edge.p1.x) * 2 * edge.p1.y;
             },
-            EdgeType::LTR | EdgeType::LBR | EdgeType::CTR => {
+            EdgeType::LTR | EdgeType::LBR | EdgeType::LTL | EdgeType::LBL => {
                area += (edge.p2.x - edge.p1.x) * (edge.p1.y + edge.p2.y);
             },
-            EdgeType::CBR | EdgeType::ATR | EdgeType::ABR |
-            EdgeType::LTL | EdgeType::LBL | EdgeType::CTL |
-            EdgeType::CBL | EdgeType::ATL | EdgeType::ABL => {
+            EdgeType::CTR | EdgeType::CBR | EdgeType::ATR | EdgeType::ABR |
+            EdgeType::CTL | EdgeType::CBL | EdgeType::ATL | EdgeType::ABL => {
                let dx = edge.p2.x - edge.p1.x;
                let dy = edge.p2.y - edge.p1.y;
 
@@ -1112,13 +1569,23 @@ impl Rasterizer {
 
                let alpha = 2. * (side as f64 / (2 * radius) as f64).asin();
 
-               let segment_area = ((radius * radius) as f64 * (alpha - alpha.sin())) as i64;
+               let segment_area = bounded_bulge(((radius * radius) as f64 * (alpha - alpha.sin())) as i64, dx, dy);
 
                match edge.edge_type {
                   EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL => area += segment_area,
                   _ => area -= segment_area
                }
             },
+            EdgeType::QTR | EdgeType::QBR | EdgeType::KTR | EdgeType::KBR |
+            EdgeType::QTL | EdgeType::QBL | EdgeType::KTL | EdgeType::KBL => {
+               area += (edge.p2.x - edge.p1.x) * (edge.p1.y + edge.p2.y);
+
+               let bezier = Bezier::from_edge(edge.edge_type, edge.segment, edge.curve, scene);
+
+               let bulge = bezier.double_bulge_area(edge.t1, edge.t2).round() as i64;
+
+               area += bulge.signum() * bounded_bulge(bulge.abs(), edge.p2.x - edge.p1.x, edge.p2.y - edge.p1.y);
+            },
             _ => {}
          }
       }
@@ -1129,7 +1596,610 @@ impl Rasterizer {
    }
 }
 
-fn create_default_vec<T>(capacity: usize) -> Vec<T> where T: Default + Clone {
-   repeat(T::default()).take(capacity).collect()
+/// Double area between a monotone curve piece and its chord. The piece stays
+/// within the box of its rounded end points, as intersections are clamped to
+/// it, and bulges thinner than rounding are left to the chord.
+#[inline]
+fn bounded_bulge(bulge: i64, dx: i64, dy: i64) -> i64 {
+   let side = (dx * dx + dy * dy).sqrt();
+
+   if bulge < side {
+      0
+   } else {
+      min(bulge, (dx * dy).abs())
+   }
+}
+
+fn check_scene(scene: &Scene, div_per_pixel: i64) -> Result<(), RuntimeError> {
+   if div_per_pixel <= 0 || div_per_pixel > MAX_DIV_PER_PIXEL {
+      return Err(invalid_scene(
+         format!("Division per pixel out of range: {}", div_per_pixel)
+      ));
+   }
+
+   for (i, point) in scene.points.iter().enumerate() {
+      if point.x.abs() > MAX_COORD || point.y.abs() > MAX_COORD {
+         return Err(invalid_scene(
+            format!("Point {} out of range: ({}, {})", i, point.x, point.y)
+         ));
+      }
+   }
+
+   let points_len = scene.points.len();
+
+   for (i, segment) in scene.segments.iter().enumerate() {
+      if segment.p1 >= points_len || segment.p2 >= points_len {
+         return Err(invalid_scene(format!("Segment {} references missing point", i)));
+      }
+   }
+
+   for (i, circle) in scene.circles.iter().enumerate() {
+      if circle.center >= points_len {
+         return Err(invalid_scene(format!("Circle {} references missing center", i)));
+      }
+
+      if circle.radius <= 0 || circle.radius > MAX_COORD {
+         return Err(invalid_scene(
+            format!("Circle {} radius out of range: {}", i, circle.radius)
+         ));
+      }
+   }
+
+   for (i, curve) in scene.curves.iter().enumerate() {
+      if curve.c1 >= points_len {
+         return Err(invalid_scene(format!("Curve {} references missing control point", i)));
+      }
+   }
+
+   for (i, edge) in scene.edges.iter().enumerate() {
+      if edge.segment >= scene.segments.len() {
+         return Err(invalid_scene(
+            format!("Edge {} references missing segment {}", i, edge.segment)
+         ));
+      }
+
+      if edge.edge_type.circular() && edge.circle >= scene.circles.len() {
+         return Err(invalid_scene(
+            format!("Edge {} references missing circle {}", i, edge.circle)
+         ));
+      }
+
+      if edge.edge_type.bezier() {
+         if edge.curve >= scene.curves.len() {
+            return Err(invalid_scene(
+               format!("Edge {} references missing curve {}", i, edge.curve)
+            ));
+         }
+
+         if edge.edge_type.cubic() && scene.curves[edge.curve].c2 >= points_len {
+            return Err(invalid_scene(
+               format!("Edge {} references cubic curve {} without second control point", i, edge.curve)
+            ));
+         }
+      }
+   }
+
+   for (i, poly) in scene.polys.iter().enumerate() {
+      if poly.start > poly.end || poly.end > scene.edges.len() {
+         return Err(invalid_scene(
+            format!("Poly {} edge range out of bounds: {}..{}", i, poly.start, poly.end)
+         ));
+      }
+
+      if poly.end - poly.start < 3 {
+         return Err(invalid_scene(
+            format!("Insufficient edge count in poly {}: {}", i, poly.end - poly.start)
+         ));
+      }
+
+      if poly.paint >= scene.paints.len() {
+         return Err(invalid_scene(
+            format!("Poly {} references missing paint {}", i, poly.paint)
+         ));
+      }
+
+      for &(start, end) in &poly.holes {
+         if start > end || end > scene.edges.len() {
+            return Err(invalid_scene(
+               format!("Poly {} hole edge range out of bounds: {}..{}", i, start, end)
+            ));
+         }
+
+         if end - start < 3 {
+            return Err(invalid_scene(
+               format!("Insufficient edge count in poly {} hole: {}", i, end - start)
+            ));
+         }
+      }
+   }
+
+   Ok(())
+}
+
+/// Rotates a convex poly's edge ring so that it starts at the point with the
+/// lowest `key`, which the slice passes expect to be on their lower side.
+#[inline]
+fn rotate_to_min<F>(edges: &mut [Edge], key: F) where F: Fn(&Point) -> i64 {
+   let mut min_i = 0;
+   let mut min_key = i64::MAX;
+
+   for (i, edge) in edges.iter().enumerate() {
+      let k = key(&edge.p1);
+      if k < min_key {
+         min_key = k;
+         min_i = i;
+      }
+   }
+
+   edges.rotate_left(min_i);
+}
+
+/// Piece of a sloped edge, which the rounded y of a vertical cut can leave
+/// flat, as a horizontal line so it keeps the ordering of its type.
+#[inline]
+fn flattened(edge: Edge) -> Edge {
+   if edge.p1.y != edge.p2.y || edge.edge_type == EdgeType::LHR || edge.edge_type == EdgeType::LHL {
+      edge
+   } else if edge.p1.x < edge.p2.x {
+      Edge::hori_right(edge.p1, edge.p2)
+   } else {
+      Edge::hori_left(edge.p1, edge.p2)
+   }
+}
+
+#[inline]
+fn invalid_scene(description: String) -> RuntimeError {
+   RuntimeError::new(ErrorKind::InvalidInput, description)
+}
+
+/// Number of polys and edge pool entries needed to slice the scene.
+fn pools_len(scene: &Scene) -> (usize, usize) {
+   // Every contour is sliced as a poly of its own
+   let polys_len = scene.polys.iter().fold(0, |sum, poly| {
+      sum + 1 + poly.holes.len()
+   });
+
+   // 4 extra positions per contour, see `transfer_scene`
+   let edges_len = scene.polys.iter().fold(0, |sum, poly| {
+      poly.holes.iter().fold(sum + poly.end - poly.start + 4, |sum, &(start, end)| {
+         sum + end - start + 4
+      })
+   });
+
+   (polys_len, edges_len)
+}
+
+fn grow_pool<T>(pool: &mut Vec<T>, len: usize) -> Result<(), RuntimeError> where T: Default + Clone {
+   if pool.len() >= len {
+      return Ok(());
+   }
+
+   let additional = len - pool.len();
+
+   if pool.try_reserve(additional).is_err() {
+      return Err(RuntimeError::new(
+         ErrorKind::Other,
+         format!("Cannot allocate {} rasterizer pool entries", len)
+      ));
+   }
+
+   pool.resize(len, T::default());
+
+   Ok(())
 }
 
+#[cfg(test)]
+pub mod tests {
+   use std::usize;
+
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+   use geom::point::Point;
+
+   use raster::{Scene, Segment, Circle, Curve, EdgeType, EdgeSrc, Poly, Paint};
+
+   use super::{Rasterizer, MAX_COORD};
+
+   pub fn add_rect(scene: &mut Scene, x1: i64, y1: i64, x2: i64, y2: i64, color: RGBA) {
+      let p = scene.points.len();
+      scene.points.extend_from_slice(&[
+         Point::new(x1, y1),
+         Point::new(x2, y1),
+         Point::new(x2, y2),
+         Point::new(x1, y2),
+      ]);
+
+      let s = scene.segments.len();
+      scene.segments.extend_from_slice(&[
+         Segment::new(p, p + 3),
+         Segment::new(p + 3, p + 2),
+         Segment::new(p + 1, p + 2),
+         Segment::new(p, p + 1),
+      ]);
+
+      let e = scene.edges.len();
+      scene.edges.extend_from_slice(&[
+         EdgeSrc::new(EdgeType::LVT, s, usize::MAX),
+         EdgeSrc::new(EdgeType::LHR, s + 1, usize::MAX),
+         EdgeSrc::new(EdgeType::LVB, s + 2, usize::MAX),
+         EdgeSrc::new(EdgeType::LHL, s + 3, usize::MAX),
+      ]);
+
+      scene.polys.push(Poly::new(e, e + 4, scene.paints.len()));
+      scene.paints.push(Paint::Solid(color));
+   }
+
+   pub fn empty_scene() -> Scene {
+      Scene {
+         points: vec![],
+         segments: vec![],
+         circles: vec![],
+         curves: vec![],
+         edges: vec![],
+         polys: vec![],
+         paints: vec![],
+      }
+   }
+
+   #[test]
+   fn test_render_headless_rect() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 2000, 5000, 6000, RGBA::new(200, 100, 50, 255));
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(200, 100, 50));
+      assert_eq!(frame.get_pixel(4, 5), RGB::new(200, 100, 50));
+      assert_eq!(frame.get_pixel(0, 2), RGB::default());
+      assert_eq!(frame.get_pixel(5, 5), RGB::default());
+      assert_eq!(frame.get_pixel(3, 6), RGB::default());
+   }
+
+   #[test]
+   fn test_render_headless_partial_pixels() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 1500, 4000, RGBA::new(0, 0, 0, 255));
+      add_rect(&mut scene, 1500, 1000, 4500, 4000, RGBA::new(200, 200, 200, 255));
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      // Pixels 1 and 4 are half covered by the gray rect, the stripe
+      // through 2 and 3 must stop before 4
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(100, 100, 100));
+      assert_eq!(frame.get_pixel(2, 2), RGB::new(200, 200, 200));
+      assert_eq!(frame.get_pixel(3, 3), RGB::new(200, 200, 200));
+      assert_eq!(frame.get_pixel(4, 2), RGB::new(100, 100, 100));
+      assert_eq!(frame.get_pixel(5, 2), RGB::default());
+   }
+
+   #[test]
+   fn test_render_linear_light() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+      rasterizer.linear_light = true;
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 1500, 4000, RGBA::new(0, 0, 0, 255));
+      add_rect(&mut scene, 1500, 1000, 4500, 4000, RGBA::new(255, 255, 255, 255));
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(188, 188, 188));
+      assert_eq!(frame.get_pixel(2, 2), RGB::new(255, 255, 255));
+      assert_eq!(frame.get_pixel(4, 2), RGB::new(188, 188, 188));
+   }
+
+   #[test]
+   fn test_render_translucent_over_frame() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 0, 0, 8000, 8000, RGBA::new(0, 0, 200, 255));
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 1500, 4000, RGBA::new(0, 0, 0, 0));
+      add_rect(&mut scene, 1500, 1000, 4000, 4000, RGBA::new(250, 0, 0, 102));
+      add_rect(&mut scene, 4000, 1000, 6000, 4000, RGBA::new(0, 250, 0, 255));
+      scene.polys[2].opacity = 51;
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(0, 2), RGB::new(0, 0, 200));
+      assert_eq!(frame.get_pixel(2, 2), RGB::new(100, 0, 120));
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(50, 0, 160));
+      assert_eq!(frame.get_pixel(5, 3), RGB::new(0, 50, 160));
+      assert_eq!(frame.get_pixel(2, 4), RGB::new(0, 0, 200));
+   }
+
+   pub fn arcs_scene(scale: i64) -> Scene {
+      let points = vec![
+         Point::new(0, 0),
+         Point::new(8, 0),
+         Point::new(12, 0),
+         Point::new(12, 4),
+         Point::new(0, 8),
+         Point::new(0, 12),
+         Point::new(4, 12),
+         Point::new(12, 12),
+         Point::new(8, 4),
+         Point::new(4, 8),
+      ];
+
+      let segments = vec![
+         Segment::new(0, 1),
+         Segment::new(1, 2),
+         Segment::new(0, 4),
+         Segment::new(1, 3),
+         Segment::new(2, 3),
+         Segment::new(3, 7),
+         Segment::new(4, 5),
+         Segment::new(4, 6),
+         Segment::new(5, 6),
+         Segment::new(6, 7),
+      ];
+
+      let edges = vec![
+         EdgeSrc::new(EdgeType::LVT, 2, usize::MAX),
+         EdgeSrc::new(EdgeType::CTR, 7, 1),
+         EdgeSrc::new(EdgeType::LHR, 9, usize::MAX),
+         EdgeSrc::new(EdgeType::LVB, 5, usize::MAX),
+         EdgeSrc::new(EdgeType::CBL, 3, 0),
+         EdgeSrc::new(EdgeType::LHL, 0, usize::MAX),
+         EdgeSrc::new(EdgeType::ATR, 3, 0),
+         EdgeSrc::new(EdgeType::LVB, 4, usize::MAX),
+         EdgeSrc::new(EdgeType::LHL, 1, usize::MAX),
+         EdgeSrc::new(EdgeType::LVT, 6, usize::MAX),
+         EdgeSrc::new(EdgeType::LHR, 8, usize::MAX),
+         EdgeSrc::new(EdgeType::ABL, 7, 1),
+      ];
+
+      let mut scene = Scene {
+         points,
+         segments,
+         circles: vec![Circle::new(8, 4), Circle::new(9, 4)],
+         curves: vec![],
+         edges,
+         polys: vec![Poly::new(0, 6, 0), Poly::new(6, 9, 1), Poly::new(9, 12, 2)],
+         paints: vec![
+            Paint::Solid(RGBA::new(255, 0, 0, 255)),
+            Paint::Solid(RGBA::new(0, 255, 0, 255)),
+            Paint::Solid(RGBA::new(0, 0, 255, 255)),
+         ],
+      };
+
+      for point in &mut scene.points {
+         point.mul_assign(scale);
+      }
+
+      for circle in &mut scene.circles {
+         circle.radius *= scale;
+      }
+
+      scene
+   }
+
+   #[test]
+   fn test_render_anti_clockwise_arcs() {
+      let mut frame = Frame::new(60, 60);
+      let mut rasterizer = Rasterizer::new();
+
+      let scene = arcs_scene(5000);
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(59, 0), RGB::new(0, 255, 0));
+      assert_eq!(frame.get_pixel(0, 59), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(45, 15), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(30, 30), RGB::new(255, 0, 0));
+
+      let (mut r, mut g, mut b) = (0., 0., 0.);
+      for y in 0..60 {
+         for x in 0..60 {
+            let color = frame.get_pixel(x, y);
+            r += color.r as f64 / 255.;
+            g += color.g as f64 / 255.;
+            b += color.b as f64 / 255.;
+         }
+      }
+
+      // Concave corner pieces are a 20x20 px square minus a quarter circle
+      let corner = 400. - 100. * ::std::f64::consts::PI;
+
+      assert!((g - corner).abs() < 1.);
+      assert!((b - corner).abs() < 1.);
+      assert!((r - (3600. - 2. * corner)).abs() < 2.);
+   }
+
+   #[test]
+   fn test_render_anti_clockwise_arcs_only() {
+      let mut frame = Frame::new(60, 60);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = arcs_scene(5000);
+      scene.edges = scene.edges[6..].to_vec();
+      scene.polys = vec![Poly::new(0, 3, 1), Poly::new(3, 6, 2)];
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(59, 0), RGB::new(0, 255, 0));
+      assert_eq!(frame.get_pixel(58, 8), RGB::new(0, 255, 0));
+      assert_eq!(frame.get_pixel(0, 59), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(8, 58), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(45, 15), RGB::default());
+      assert_eq!(frame.get_pixel(55, 12), RGB::default());
+      assert_eq!(frame.get_pixel(30, 30), RGB::default());
+   }
+
+   #[test]
+   fn test_render_partly_off_screen() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, -2500, -1500, 2500, 3500, RGBA::new(200, 200, 200, 255));
+      add_rect(&mut scene, 6500, 7000, 9000, 12000, RGBA::new(0, 200, 0, 255));
+      add_rect(&mut scene, -9000, 4000, -1000, 5000, RGBA::new(0, 0, 200, 255));
+
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(200, 200, 200));
+      assert_eq!(frame.get_pixel(1, 2), RGB::new(200, 200, 200));
+      assert_eq!(frame.get_pixel(2, 0), RGB::new(100, 100, 100));
+      assert_eq!(frame.get_pixel(0, 3), RGB::new(100, 100, 100));
+      assert_eq!(frame.get_pixel(3, 0), RGB::default());
+      assert_eq!(frame.get_pixel(7, 7), RGB::new(0, 200, 0));
+      assert_eq!(frame.get_pixel(6, 7), RGB::new(0, 100, 0));
+      assert_eq!(frame.get_pixel(0, 4), RGB::default());
+
+      // The quarter of the arcs scene around its center matches the
+      // corresponding quarter of the full render
+      let mut full = Frame::new(60, 60);
+      rasterizer.render(&arcs_scene(5000), &mut full, 1000).unwrap();
+
+      let mut scene = arcs_scene(5000);
+      for point in &mut scene.points {
+         point.x -= 30000;
+         point.y -= 30000;
+      }
+
+      let mut frame = Frame::new(30, 30);
+      rasterizer.render(&scene, &mut frame, 1000).unwrap();
+
+      for y in 0..30 {
+         for x in 0..30 {
+            assert_eq!(frame.get_pixel(x, y), full.get_pixel(x + 30, y + 30));
+         }
+      }
+   }
+
+   /// Square split by a curve from the top-left to the bottom-right corner
+   /// bulging towards the top-right, quadratic with control in that corner
+   /// or the equivalent degree elevated cubic.
+   fn curves_scene(size: i64, cubic: bool) -> Scene {
+      let mut scene = empty_scene();
+
+      scene.points = vec![
+         Point::new(0, 0),
+         Point::new(0, size),
+         Point::new(size, 0),
+         Point::new(size, size),
+         Point::new(size, 2 * size / 3),
+         Point::new(2 * size / 3, size),
+      ];
+
+      scene.segments = vec![
+         Segment::new(0, 1),
+         Segment::new(2, 1),
+         Segment::new(0, 2),
+         Segment::new(1, 3),
+         Segment::new(2, 3),
+      ];
+
+      let (down, up, curve) = if cubic {
+         (EdgeType::KBR, EdgeType::KTL, Curve::new(4, 5))
+      } else {
+         (EdgeType::QBR, EdgeType::QTL, Curve::quadratic(3))
+      };
+
+      scene.curves = vec![curve];
+
+      scene.edges = vec![
+         EdgeSrc::new(EdgeType::LVT, 0, usize::MAX),
+         EdgeSrc::new_curve(down, 1, 0),
+         EdgeSrc::new(EdgeType::LHL, 2, usize::MAX),
+         EdgeSrc::new_curve(up, 1, 0),
+         EdgeSrc::new(EdgeType::LHR, 3, usize::MAX),
+         EdgeSrc::new(EdgeType::LVB, 4, usize::MAX),
+      ];
+
+      scene.polys = vec![Poly::new(0, 3, 0), Poly::new(3, 6, 1)];
+      scene.paints = vec![Paint::Solid(RGBA::new(255, 0, 0, 255)), Paint::Solid(RGBA::new(0, 255, 0, 255))];
+
+      scene
+   }
+
+   #[test]
+   fn test_render_bezier_curves() {
+      for cubic in &[false, true] {
+         let mut frame = Frame::new(40, 40);
+         let mut rasterizer = Rasterizer::new();
+
+         rasterizer.render(&curves_scene(40000, *cubic), &mut frame, 1000).unwrap();
+
+         assert_eq!(frame.get_pixel(0, 0), RGB::new(255, 0, 0));
+         assert_eq!(frame.get_pixel(20, 20), RGB::new(255, 0, 0));
+         assert_eq!(frame.get_pixel(39, 39), RGB::new(0, 255, 0));
+
+         let (mut r, mut g) = (0., 0.);
+         for y in 0..40 {
+            for x in 0..40 {
+               let color = frame.get_pixel(x, y);
+               r += color.r as f64 / 255.;
+               g += color.g as f64 / 255.;
+            }
+         }
+
+         // The area between a quadratic and its chord is 2/3 of the control
+         // triangle, so the curve cuts off 1/6 of the square
+         assert!((g - 1600. / 6.).abs() < 1.);
+         assert!((r - 1600. * 5. / 6.).abs() < 1.);
+      }
+   }
+
+   #[test]
+   fn test_render_large_scene() {
+      let size = 260;
+
+      let mut frame = Frame::new(size, size);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      for y in 0..size as i64 {
+         for x in 0..size as i64 {
+            let color = RGBA::new(x as u8, y as u8, 7, 255);
+            add_rect(&mut scene, x * 100, y * 100, x * 100 + 100, y * 100 + 100, color);
+         }
+      }
+
+      assert!(scene.polys.len() > 65536);
+
+      rasterizer.render(&scene, &mut frame, 100).unwrap();
+
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(0, 0, 7));
+      assert_eq!(frame.get_pixel(17, 250), RGB::new(17, 250, 7));
+      assert_eq!(frame.get_pixel(259, 259), RGB::new(3, 3, 7));
+   }
+
+   #[test]
+   fn test_render_invalid_scene() {
+      let mut frame = Frame::new(8, 8);
+      let mut rasterizer = Rasterizer::new();
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 2000, 2000, RGBA::new(1, 2, 3, 255));
+      scene.polys[0].paint = 1;
+      assert!(rasterizer.render(&scene, &mut frame, 1000).is_err());
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 2000, 2000, RGBA::new(1, 2, 3, 255));
+      scene.edges[2].segment = 4;
+      assert!(rasterizer.render(&scene, &mut frame, 1000).is_err());
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, -MAX_COORD - 1, 1000, 2000, 2000, RGBA::new(1, 2, 3, 255));
+      assert!(rasterizer.render(&scene, &mut frame, 1000).is_err());
+
+      let mut scene = empty_scene();
+      add_rect(&mut scene, 1000, 1000, 2000, 2000, RGBA::new(1, 2, 3, 255));
+      assert!(rasterizer.render(&scene, &mut frame, 0).is_err());
+
+      assert!(rasterizer.render(&empty_scene(), &mut frame, 1000).is_ok());
+   }
+}
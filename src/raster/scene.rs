@@ -1,17 +1,821 @@
+use std::collections::HashMap;
+
 use geom::point::Point;
-use draw::RGB;
+use draw::RGBA;
+use error::{RuntimeError, ErrorKind};
 
 use super::segment::Segment;
 use super::circle::Circle;
+use super::curve::{Curve, quadratic_monotone_pieces, cubic_monotone_pieces, curve_edge_type};
 use super::poly::Poly;
-use super::edge::EdgeSrc;
+use super::edge::{EdgeType, EdgeSrc};
+use super::path::{Path, PathEdge, Contour, arc_pieces};
+use super::paint::Paint;
+use super::compile::{BooleanOp, boolean};
 
 
 pub struct Scene {
    pub points: Vec<Point>,
    pub segments: Vec<Segment>,
    pub circles: Vec<Circle>,
+   pub curves: Vec<Curve>,
    pub edges: Vec<EdgeSrc>,
    pub polys: Vec<Poly>,
-   pub colors: Vec<RGB>,
+   pub paints: Vec<Paint>,
+}
+
+impl Scene {
+   #[inline]
+   pub fn new() -> Self {
+      Scene {
+         points: Vec::new(),
+         segments: Vec::new(),
+         circles: Vec::new(),
+         curves: Vec::new(),
+         edges: Vec::new(),
+         polys: Vec::new(),
+         paints: Vec::new(),
+      }
+   }
+
+   /// Outline of `poly` with its holes, filled by the non-zero rule.
+   pub fn poly_path(&self, poly: &Poly) -> Path {
+      let mut path = Path::new();
+
+      path.contours.push(self.contour(poly.start, poly.end));
+
+      for &(start, end) in &poly.holes {
+         path.contours.push(self.contour(start, end));
+      }
+
+      path
+   }
+
+   fn contour(&self, start: usize, end: usize) -> Contour {
+      let ends = |edge: &EdgeSrc| {
+         let segment = &self.segments[edge.segment];
+         let (p1, p2) = (self.points[segment.p1], self.points[segment.p2]);
+         if edge.reversed() { (p2, p1) } else { (p1, p2) }
+      };
+
+      let mut contour = Contour::new(ends(&self.edges[start]).0);
+
+      for edge in &self.edges[start..end] {
+         let (from, to) = ends(edge);
+
+         contour.edges.push(if edge.edge_type.circular() {
+            let circle = &self.circles[edge.circle];
+            let clockwise = matches!(
+               edge.edge_type, EdgeType::CTR | EdgeType::CTL | EdgeType::CBR | EdgeType::CBL
+            );
+
+            PathEdge::Arc {
+               end: to,
+               center: self.points[circle.center],
+               radius: circle.radius,
+               clockwise,
+            }
+         } else if edge.edge_type.bezier() {
+            // Controls follow the segment from its lower point
+            let curve = &self.curves[edge.curve];
+            let (c1, c2) = if edge.edge_type.cubic() && from > to {
+               (curve.c2, curve.c1)
+            } else {
+               (curve.c1, curve.c2)
+            };
+
+            if edge.edge_type.cubic() {
+               PathEdge::Cubic { control1: self.points[c1], control2: self.points[c2], end: to }
+            } else {
+               PathEdge::Quad { control: self.points[c1], end: to }
+            }
+         } else {
+            PathEdge::Line(to)
+         });
+      }
+
+      contour.closed = true;
+      contour
+   }
+}
+
+impl Default for Scene {
+   fn default() -> Scene {
+      Scene::new()
+   }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum SegmentShape {
+   Line,
+   Arc(usize),
+   Curve(usize),
+}
+
+/// Builds a `Scene` from paths. Edges are split at their x and y extrema and
+/// classified by direction, while points, segments, circles and curves are
+/// shared between all paths, so neighbouring polys use the same intersections
+/// along a common border. Paths with contours that are not monotone in both
+/// x and y are first resolved into monotone faces by their fill rule, as
+/// `SceneCompiler` does, which flattens their Bézier curves.
+pub struct SceneBuilder {
+   scene: Scene,
+   points: HashMap<Point, usize>,
+   segments: HashMap<(usize, usize, SegmentShape), usize>,
+   circles: HashMap<(usize, i64), usize>,
+   curves: HashMap<(usize, usize), usize>,
+}
+
+impl SceneBuilder {
+   pub fn new() -> Self {
+      SceneBuilder {
+         scene: Scene::new(),
+         points: HashMap::new(),
+         segments: HashMap::new(),
+         circles: HashMap::new(),
+         curves: HashMap::new(),
+      }
+   }
+
+   /// Adds one poly per clockwise contour of `path`, all filled with
+   /// `color`. Anti-clockwise contours become holes of the poly before them,
+   /// or polys of their own when there is none. Contours enclosing no area
+   /// are skipped. Paths with contours not monotone in x and y are filled by
+   /// their fill rule instead.
+   pub fn add_path(&mut self, path: &Path, color: RGBA) -> Result<(), RuntimeError> {
+      self.add_path_paint(path, Paint::Solid(color))
+   }
+
+   /// Adds `path` as `add_path` does, filled with `paint`.
+   pub fn add_path_paint(&mut self, path: &Path, paint: Paint) -> Result<(), RuntimeError> {
+      let paint_index = self.add_paint(paint);
+
+      self.add_contours(path, paint_index)
+   }
+
+   fn add_contours(&mut self, path: &Path, paint_index: usize) -> Result<(), RuntimeError> {
+      let (polys, edges) = (self.scene.polys.len(), self.scene.edges.len());
+
+      if self.add_monotone_contours(path, paint_index).is_ok() {
+         return Ok(());
+      }
+
+      // Drop the polys added so far and add the faces of the path instead,
+      // which are all monotone
+      self.scene.polys.truncate(polys);
+      self.scene.edges.truncate(edges);
+
+      let faces = boolean(path, &Path::new(), BooleanOp::Union);
+
+      self.add_monotone_contours(&faces, paint_index)
+   }
+
+   fn add_monotone_contours(&mut self, path: &Path, paint_index: usize) -> Result<(), RuntimeError> {
+      let mut outer = None;
+
+      for contour in &path.contours {
+         match outer {
+            Some(poly_index) if !contour.clockwise() => {
+               self.add_hole(contour, poly_index)?;
+            },
+            _ => {
+               if self.add_contour(contour, paint_index)? {
+                  outer = Some(self.scene.polys.len() - 1);
+               }
+            },
+         }
+      }
+
+      Ok(())
+   }
+
+   #[inline]
+   pub fn add_paint(&mut self, paint: Paint) -> usize {
+      self.scene.paints.push(paint);
+      self.scene.paints.len() - 1
+   }
+
+   /// Adds `contour` as a poly filled with the paint at `paint_index`,
+   /// turned clockwise if needed. Returns whether the poly was added.
+   pub fn add_contour(&mut self, contour: &Contour, paint_index: usize) -> Result<bool, RuntimeError> {
+      let range = if contour.clockwise() {
+         self.add_edges(contour)?
+      } else {
+         self.add_edges(&contour.reversed())?
+      };
+
+      match range {
+         Some((start, end)) => {
+            self.scene.polys.push(Poly::new(start, end, paint_index));
+            Ok(true)
+         },
+         None => Ok(false),
+      }
+   }
+
+   /// Adds `contour` as a hole of the poly at `poly_index`, turned
+   /// anti-clockwise if needed.
+   pub fn add_hole(&mut self, contour: &Contour, poly_index: usize) -> Result<(), RuntimeError> {
+      let range = if contour.clockwise() {
+         self.add_edges(&contour.reversed())?
+      } else {
+         self.add_edges(contour)?
+      };
+
+      if let Some(range) = range {
+         self.scene.polys[poly_index].holes.push(range);
+      }
+
+      Ok(())
+   }
+
+   fn add_edges(&mut self, contour: &Contour) -> Result<Option<(usize, usize)>, RuntimeError> {
+      let start = self.scene.edges.len();
+
+      self.add_contour_edges(contour);
+
+      // Polys need three edges, so a single curve closed by another edge
+      // is cut in two
+      if self.scene.edges.len() - start == 2 {
+         self.scene.edges.truncate(start);
+         self.add_contour_edges(&contour.split_first());
+      }
+
+      let end = self.scene.edges.len();
+
+      if end - start < 3 {
+         self.scene.edges.truncate(start);
+         return Ok(None);
+      }
+
+      if !monotone(&self.scene.edges[start..end]) {
+         self.scene.edges.truncate(start);
+         return Err(RuntimeError::new(
+            ErrorKind::InvalidInput,
+            format!("Contour starting at ({}, {}) is not monotone in x and y", contour.start.x, contour.start.y)
+         ));
+      }
+
+      Ok(Some((start, end)))
+   }
+
+   fn add_contour_edges(&mut self, contour: &Contour) {
+      let mut from = contour.start;
+
+      for edge in &contour.edges {
+         self.add_edge(&from, edge);
+         from = edge.end();
+      }
+
+      if from != contour.start {
+         self.add_line(&from, &contour.start);
+      }
+   }
+
+   #[inline]
+   pub fn build(self) -> Scene {
+      self.scene
+   }
+
+   fn add_edge(&mut self, from: &Point, edge: &PathEdge) {
+      match *edge {
+         PathEdge::Line(ref end) => {
+            self.add_line(from, end);
+         },
+         PathEdge::Arc { ref end, ref center, radius, clockwise } => {
+            let points = arc_pieces(from, end, center, radius, clockwise);
+
+            for piece in points.windows(2) {
+               self.add_arc(&piece[0], &piece[1], center, radius, clockwise);
+            }
+         },
+         PathEdge::Quad { ref control, ref end } => {
+            for piece in quadratic_monotone_pieces(from, control, end) {
+               self.add_curve(&piece[0], &piece[2], &piece[1], None);
+            }
+         },
+         PathEdge::Cubic { ref control1, ref control2, ref end } => {
+            for piece in cubic_monotone_pieces(from, control1, control2, end) {
+               self.add_curve(&piece[0], &piece[3], &piece[1], Some(&piece[2]));
+            }
+         },
+      }
+   }
+
+   fn add_line(&mut self, from: &Point, to: &Point) {
+      if from == to {
+         return;
+      }
+
+      let up = to.y > from.y;
+      let right = to.x > from.x;
+
+      let edge_type = if from.y == to.y {
+         if right { EdgeType::LHR } else { EdgeType::LHL }
+      } else if from.x == to.x {
+         if up { EdgeType::LVT } else { EdgeType::LVB }
+      } else {
+         match (up, right) {
+            (true, true) => EdgeType::LTR,
+            (true, false) => EdgeType::LTL,
+            (false, true) => EdgeType::LBR,
+            (false, false) => EdgeType::LBL,
+         }
+      };
+
+      let segment = self.segment(from, to, SegmentShape::Line);
+
+      self.scene.edges.push(EdgeSrc::new(edge_type, segment, usize::MAX));
+   }
+
+   fn add_arc(&mut self, from: &Point, to: &Point, center: &Point, radius: i64, clockwise: bool) {
+      if from == to {
+         return;
+      }
+
+      // Pieces lie within one quadrant, so the chord middle tells which
+      let left = from.x + to.x < 2 * center.x;
+      let upper = from.y + to.y > 2 * center.y;
+
+      let edge_type = match (clockwise, left, upper) {
+         (true, true, true) => EdgeType::CTR,
+         (true, true, false) => EdgeType::CTL,
+         (true, false, true) => EdgeType::CBR,
+         (true, false, false) => EdgeType::CBL,
+         (false, true, true) => EdgeType::ABL,
+         (false, true, false) => EdgeType::ABR,
+         (false, false, true) => EdgeType::ATL,
+         (false, false, false) => EdgeType::ATR,
+      };
+
+      // Rounded ends of short pieces can run flat or against the quadrant,
+      // those pieces are kept as lines
+      let dx = (to.x - from.x).signum() as i32;
+      let dy = (to.y - from.y).signum() as i32;
+
+      if dy != vertical_direction(edge_type) || (dx != 0 && dx != horizontal_direction(edge_type)) {
+         self.add_line(from, to);
+         return;
+      }
+
+      let center_index = self.point(center);
+
+      let circle = match self.circles.get(&(center_index, radius)) {
+         Some(&index) => index,
+         None => {
+            let index = self.scene.circles.len();
+            self.scene.circles.push(Circle::new(center_index, radius));
+            self.circles.insert((center_index, radius), index);
+            index
+         }
+      };
+
+      let segment = self.segment(from, to, SegmentShape::Arc(circle));
+
+      self.scene.edges.push(EdgeSrc::new(edge_type, segment, circle));
+   }
+
+   fn add_curve(&mut self, from: &Point, to: &Point, c1: &Point, c2: Option<&Point>) {
+      let edge_type = curve_edge_type(c2.is_some(), from, to);
+
+      if !edge_type.bezier() {
+         self.add_line(from, to);
+         return;
+      }
+
+      // Curve controls follow the segment, which runs from the lower point
+      let (c1, c2) = match c2 {
+         Some(c2) if from > to => (self.point(c2), self.point(c1)),
+         Some(c2) => (self.point(c1), self.point(c2)),
+         None => (self.point(c1), usize::MAX),
+      };
+
+      let curve = match self.curves.get(&(c1, c2)) {
+         Some(&index) => index,
+         None => {
+            let index = self.scene.curves.len();
+            self.scene.curves.push(Curve::new(c1, c2));
+            self.curves.insert((c1, c2), index);
+            index
+         }
+      };
+
+      let segment = self.segment(from, to, SegmentShape::Curve(curve));
+
+      self.scene.edges.push(EdgeSrc::new_curve(edge_type, segment, curve));
+   }
+
+   fn point(&mut self, point: &Point) -> usize {
+      if let Some(&index) = self.points.get(point) {
+         return index;
+      }
+
+      let index = self.scene.points.len();
+      self.scene.points.push(*point);
+      self.points.insert(*point, index);
+
+      index
+   }
+
+   fn segment(&mut self, from: &Point, to: &Point, shape: SegmentShape) -> usize {
+      let (p1, p2) = if from < to { (from, to) } else { (to, from) };
+
+      let key = (self.point(p1), self.point(p2), shape);
+
+      if let Some(&index) = self.segments.get(&key) {
+         return index;
+      }
+
+      let index = self.scene.segments.len();
+      self.scene.segments.push(Segment::new(key.0, key.1));
+      self.segments.insert(key, index);
+
+      index
+   }
+}
+
+impl Default for SceneBuilder {
+   fn default() -> SceneBuilder {
+      SceneBuilder::new()
+   }
+}
+
+/// Whether a closed run of edges turns back at most once vertically and at
+/// most once horizontally, as the rasterizer slices polys.
+fn monotone(edges: &[EdgeSrc]) -> bool {
+   let vertical: Vec<i32> = edges.iter().map(|edge| vertical_direction(edge.edge_type)).collect();
+   let horizontal: Vec<i32> = edges.iter().map(|edge| horizontal_direction(edge.edge_type)).collect();
+
+   direction_changes(&vertical) <= 2 && direction_changes(&horizontal) <= 2
+}
+
+fn direction_changes(directions: &[i32]) -> usize {
+   let directions: Vec<i32> = directions.iter().cloned().filter(|&d| d != 0).collect();
+
+   let mut changes = 0;
+   for i in 0..directions.len() {
+      if directions[i] != directions[(i + 1) % directions.len()] {
+         changes += 1;
+      }
+   }
+
+   changes
+}
+
+fn vertical_direction(edge_type: EdgeType) -> i32 {
+   match edge_type {
+      EdgeType::LHR | EdgeType::LHL => 0,
+      _ if edge_type.reversed() => -1,
+      _ => 1,
+   }
+}
+
+fn horizontal_direction(edge_type: EdgeType) -> i32 {
+   match edge_type {
+      EdgeType::LVT | EdgeType::LVB => 0,
+      EdgeType::LTR | EdgeType::LBR | EdgeType::LHR | EdgeType::CTR |
+      EdgeType::CBR | EdgeType::ATR | EdgeType::ABR | EdgeType::QTR |
+      EdgeType::QBR | EdgeType::KTR | EdgeType::KBR => 1,
+      _ => -1,
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+
+   use geom::point::Point;
+   use raster::{Rasterizer, EdgeType, Path, Paint, Gradient};
+   use raster::rasterizer::tests::arcs_scene;
+
+   use super::SceneBuilder;
+
+   #[test]
+   fn test_builder_shares_segments() {
+      let mut left = Path::new();
+      left.move_to(0, 0);
+      left.line_to(0, 10);
+      left.line_to(10, 10);
+      left.line_to(10, 0);
+      left.close();
+
+      let mut right = Path::new();
+      right.move_to(10, 0);
+      right.line_to(10, 10);
+      right.line_to(20, 10);
+      right.line_to(20, 0);
+      right.close();
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&left, RGBA::new(255, 0, 0, 255)).unwrap();
+      builder.add_path(&right, RGBA::new(0, 255, 0, 255)).unwrap();
+      let scene = builder.build();
+
+      assert_eq!(scene.points.len(), 6);
+      assert_eq!(scene.segments.len(), 7);
+      assert_eq!(scene.polys.len(), 2);
+      assert_eq!(scene.edges[2].segment, scene.edges[4].segment);
+      assert_eq!(scene.edges[2].edge_type, EdgeType::LVB);
+      assert_eq!(scene.edges[4].edge_type, EdgeType::LVT);
+   }
+
+   #[test]
+   fn test_builder_splits_circle() {
+      let mut path = Path::new();
+      path.move_to(300, 200);
+      path.arc_to(300, 200, 200, 200, true);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&path, RGBA::new(255, 0, 0, 255)).unwrap();
+      let scene = builder.build();
+
+      let edge_types: Vec<EdgeType> = scene.edges.iter().map(|edge| edge.edge_type).collect();
+
+      assert_eq!(edge_types, vec![EdgeType::CBL, EdgeType::CTL, EdgeType::CTR, EdgeType::CBR]);
+      assert_eq!(scene.circles.len(), 1);
+      assert_eq!(scene.polys[0].end, 4);
+   }
+
+   #[test]
+   fn test_builder_circle_coverage() {
+      let mut path = Path::new();
+      path.move_to(6000, 5000);
+      path.arc_to(6000, 5000, 5000, 5000, true);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&path, RGBA::new(255, 255, 255, 255)).unwrap();
+      let scene = builder.build();
+
+      for &div_per_pixel in &[100, 1000] {
+         let size = 10000 / div_per_pixel;
+
+         let mut frame = Frame::new(size as u32, size as u32);
+         Rasterizer::new().render(&scene, &mut frame, div_per_pixel).unwrap();
+
+         let mut covered = 0;
+         for y in 0..size {
+            for x in 0..size {
+               covered += frame.get_pixel(x as i32, y as i32).r as i64;
+            }
+         }
+
+         let area = covered * div_per_pixel * div_per_pixel / 255;
+         assert!((area - 3141593).abs() < 10000, "Circle area {}", area);
+      }
+   }
+
+   #[test]
+   fn test_builder_reverses_anticlockwise() {
+      let points = [(500, 500), (9500, 2500), (3500, 9500)];
+
+      let mut frames = Vec::new();
+
+      for order in &[[0, 1, 2], [2, 1, 0]] {
+         let mut path = Path::new();
+         path.move_to(points[order[0]].0, points[order[0]].1);
+         path.line_to(points[order[1]].0, points[order[1]].1);
+         path.line_to(points[order[2]].0, points[order[2]].1);
+         path.close();
+
+         let mut builder = SceneBuilder::new();
+         builder.add_path(&path, RGBA::new(255, 0, 0, 255)).unwrap();
+
+         let mut frame = Frame::new(10, 10);
+         Rasterizer::new().render(&builder.build(), &mut frame, 1000).unwrap();
+         frames.push(frame);
+      }
+
+      assert_eq!(frames[0].get_pixel(4, 4), RGB::new(255, 0, 0));
+
+      for y in 0..10 {
+         for x in 0..10 {
+            assert_eq!(frames[0].get_pixel(x, y), frames[1].get_pixel(x, y));
+         }
+      }
+   }
+
+   #[test]
+   fn test_builder_orthogonally_convex() {
+      let mut corner = Path::new();
+      corner.move_to(1000, 1000);
+      corner.line_to(1000, 7000);
+      corner.line_to(7000, 7000);
+      corner.line_to(7000, 5000);
+      corner.line_to(3000, 5000);
+      corner.line_to(3000, 1000);
+      corner.close();
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&corner, RGBA::new(255, 0, 0, 255)).unwrap();
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&builder.build(), &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(2, 4), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(3, 4), RGB::new(0, 0, 0));
+      assert_eq!(frame.get_pixel(5, 3), RGB::new(0, 0, 0));
+      assert_eq!(frame.get_pixel(3, 5), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(6, 6), RGB::new(255, 0, 0));
+   }
+
+   #[test]
+   fn test_builder_splits_concave() {
+      let mut cup = Path::new();
+      cup.move_to(1000, 1000);
+      cup.line_to(1000, 9000);
+      cup.line_to(3000, 9000);
+      cup.line_to(3000, 3000);
+      cup.line_to(7000, 3000);
+      cup.line_to(7000, 9000);
+      cup.line_to(9000, 9000);
+      cup.line_to(9000, 1000);
+      cup.close();
+
+      // Five pointed star, whose middle is filled by the non-zero rule
+      let mut star = Path::new();
+      star.move_to(5000, 9500);
+      star.line_to(7600, 1400);
+      star.line_to(700, 6400);
+      star.line_to(9300, 6400);
+      star.line_to(2400, 1400);
+      star.close();
+
+      let red = RGB::new(255, 0, 0);
+      let black = RGB::new(0, 0, 0);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&cup, RGBA::new(255, 0, 0, 255)).unwrap();
+
+      let scene = builder.build();
+      assert!(scene.polys.len() >= 3);
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 5), red);
+      assert_eq!(frame.get_pixel(5, 2), red);
+      assert_eq!(frame.get_pixel(5, 5), black);
+      assert_eq!(frame.get_pixel(8, 8), red);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&star, RGBA::new(255, 0, 0, 255)).unwrap();
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&builder.build(), &mut frame, 1000).unwrap();
+      assert_eq!(frame.get_pixel(5, 5), red);
+      assert_eq!(frame.get_pixel(4, 4), red);
+      assert_eq!(frame.get_pixel(3, 5), red);
+      assert_eq!(frame.get_pixel(5, 1), black);
+      assert_eq!(frame.get_pixel(1, 2), black);
+   }
+
+   #[test]
+   fn test_poly_path() {
+      let mut path = Path::new();
+      path.move_to(1000, 1000);
+      path.cubic_to(1000, 4000, 3000, 6000, 6000, 6000);
+      path.quad_to(9000, 6000, 9000, 1000);
+      path.arc_to(5000, 1000, 7000, 1000, true);
+      path.close();
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&path, RGBA::new(255, 0, 0, 255)).unwrap();
+      let scene = builder.build();
+
+      let mut rebuilt = SceneBuilder::new();
+      rebuilt.add_path(&scene.poly_path(&scene.polys[0]), RGBA::new(255, 0, 0, 255)).unwrap();
+      let rebuilt = rebuilt.build();
+
+      assert_eq!(rebuilt.points, scene.points);
+      assert_eq!(rebuilt.edges.len(), scene.edges.len());
+
+      for (edge, rebuilt_edge) in scene.edges.iter().zip(&rebuilt.edges) {
+         assert_eq!(edge.edge_type, rebuilt_edge.edge_type);
+         assert_eq!(edge.segment, rebuilt_edge.segment);
+      }
+   }
+
+   #[test]
+   fn test_builder_splits_two_edge_contour() {
+      let mut segment = Path::new();
+      segment.move_to(1000, 5000);
+      segment.arc_to(5000, 9000, 5000, 5000, true);
+      segment.close();
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&segment, RGBA::new(255, 0, 0, 255)).unwrap();
+      let scene = builder.build();
+
+      assert_eq!(scene.polys.len(), 1);
+      assert_eq!(scene.polys[0].end - scene.polys[0].start, 3);
+   }
+
+   #[test]
+   fn test_builder_matches_manual_scene() {
+      let s = 5000;
+
+      let mut red = Path::new();
+      red.move_to(0, 0);
+      red.line_to(0, 8 * s);
+      red.arc_to(4 * s, 12 * s, 4 * s, 8 * s, true);
+      red.line_to(12 * s, 12 * s);
+      red.line_to(12 * s, 4 * s);
+      red.arc_to(8 * s, 0, 8 * s, 4 * s, true);
+      red.close();
+
+      let mut green = Path::new();
+      green.move_to(8 * s, 0);
+      green.arc_to(12 * s, 4 * s, 8 * s, 4 * s, false);
+      green.line_to(12 * s, 0);
+
+      let mut blue = Path::new();
+      blue.move_to(4 * s, 12 * s);
+      blue.arc_to(0, 8 * s, 4 * s, 8 * s, false);
+      blue.line_to(0, 12 * s);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&red, RGBA::new(255, 0, 0, 255)).unwrap();
+      builder.add_path(&green, RGBA::new(0, 255, 0, 255)).unwrap();
+      builder.add_path(&blue, RGBA::new(0, 0, 255, 255)).unwrap();
+      let scene = builder.build();
+
+      assert_eq!(scene.circles.len(), 2);
+      assert_eq!(scene.segments.len(), 10);
+
+      let mut frame = Frame::new(60, 60);
+      let mut expected = Frame::new(60, 60);
+
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+      Rasterizer::new().render(&arcs_scene(s), &mut expected, 1000).unwrap();
+
+      for y in 0..60 {
+         for x in 0..60 {
+            assert_eq!(frame.get_pixel(x, y), expected.get_pixel(x, y));
+         }
+      }
+   }
+
+   #[test]
+   fn test_builder_holes() {
+      let mut ring = Path::new();
+      ring.move_to(9000, 5000);
+      ring.arc_to(9000, 5000, 5000, 5000, true);
+      ring.move_to(7000, 5000);
+      ring.arc_to(7000, 5000, 5000, 5000, false);
+
+      let mut lake = Path::new();
+      lake.move_to(6500, 5000);
+      lake.arc_to(6500, 5000, 5000, 5000, true);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&ring, RGBA::new(255, 0, 0, 255)).unwrap();
+      builder.add_path(&lake, RGBA::new(0, 0, 255, 255)).unwrap();
+      let scene = builder.build();
+
+      assert_eq!(scene.polys.len(), 2);
+      assert_eq!(scene.polys[0].holes, vec![(4, 8)]);
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      // Pixels whose corners all lie between the radii 2000 and 4000 are
+      // fully red, those within 1500 of the center fully blue
+      assert_eq!(frame.get_pixel(2, 4), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(4, 7), RGB::new(255, 0, 0));
+      assert_eq!(frame.get_pixel(4, 4), RGB::new(0, 0, 255));
+      assert_eq!(frame.get_pixel(5, 5), RGB::new(0, 0, 255));
+
+      // Between the hole and the lake nothing is drawn
+      let pixel = frame.get_pixel(6, 3);
+      assert!(pixel.r > 0 && pixel.b > 0);
+      assert!((pixel.r as i64 + pixel.b as i64) < 250);
+   }
+
+   #[test]
+   fn test_builder_gradient() {
+      let mut path = Path::new();
+      path.move_to(0, 0);
+      path.line_to(0, 2000);
+      path.line_to(10000, 2000);
+      path.line_to(10000, 0);
+
+      let mut gradient = Gradient::linear(Point::new(0, 0), Point::new(10000, 0));
+      gradient.add_stop(0., RGBA::new(0, 0, 0, 255));
+      gradient.add_stop(1., RGBA::new(200, 100, 250, 255));
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path_paint(&path, Paint::Gradient(gradient)).unwrap();
+      let scene = builder.build();
+
+      assert_eq!(scene.polys.len(), 1);
+      assert_eq!(scene.paints.len(), 1);
+
+      let mut frame = Frame::new(10, 2);
+      Rasterizer::new().render(&scene, &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(0, 1), RGB::new(10, 5, 13));
+      assert_eq!(frame.get_pixel(5, 0), RGB::new(110, 55, 138));
+      assert_eq!(frame.get_pixel(9, 1), RGB::new(190, 95, 238));
+   }
 }
@@ -0,0 +1,856 @@
+use std::mem;
+
+use geom::point::Point;
+
+use super::path::{Path, PathEdge, Contour, angle, arc_sweep, arc_pieces, circle_point, flatten_bezier};
+use super::dash::{Dash, DashCursor};
+
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LineJoin {
+   Miter,
+   Round,
+   Bevel,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LineCap {
+   Butt,
+   Round,
+   Square,
+}
+
+/// Stroke parameters in world coordinates. Stroking a path produces another
+/// path made of clockwise convex pieces (segment bodies, joins and caps)
+/// whose union is the stroke outline; round joins and caps use circular arcs.
+#[derive(Debug, Clone)]
+pub struct Stroke {
+   pub width: i64,
+   pub join: LineJoin,
+   pub cap: LineCap,
+   /// Largest miter length to stroke width ratio before a miter join falls
+   /// back to a bevel.
+   pub miter_limit: f64,
+   /// Maximum distance between a Bézier curve and its flattened stroke.
+   pub tolerance: f64,
+   /// Splits every contour into dashes before stroking.
+   pub dash: Option<Dash>,
+}
+
+impl Stroke {
+   #[inline]
+   pub fn new(width: i64) -> Self {
+      Stroke {
+         width,
+         join: LineJoin::Miter,
+         cap: LineCap::Butt,
+         miter_limit: 4.,
+         tolerance: 100.,
+         dash: None,
+      }
+   }
+
+   /// Outline of `path` stroked. The pieces overlap, so the outline is
+   /// meant for `SceneCompiler`, which fills their union under the
+   /// non-zero rule; `SceneBuilder` would blend the overlaps once per piece.
+   pub fn stroke(&self, path: &Path) -> Path {
+      let mut outline = Path::new();
+
+      let half = self.width as f64 / 2.;
+      if half <= 0. {
+         return outline;
+      }
+
+      for contour in &path.contours {
+         let segments = self.stroke_segments(contour);
+
+         if segments.is_empty() {
+            // Zero length subpaths other than a lone move show their caps
+            // as a dot, unless it falls in a dash gap
+            let drawn = !contour.edges.is_empty() || contour.closed;
+            let on = match self.dash.as_ref().and_then(DashCursor::new) {
+               Some(mut cursor) => !cursor.intervals(0.).is_empty(),
+               None => true,
+            };
+
+            if drawn && on {
+               self.add_dot(&mut outline, &contour.start, (1., 0.), half);
+            }
+
+            continue;
+         }
+
+         match self.dash.as_ref().and_then(DashCursor::new) {
+            Some(cursor) => self.stroke_dashed(&mut outline, &segments, cursor, contour.closed, half),
+            None => self.stroke_run(&mut outline, &segments, contour.closed, half),
+         }
+      }
+
+      for contour in outline.contours.iter_mut() {
+         if !contour.clockwise() {
+            *contour = contour.reversed();
+         }
+      }
+
+      outline
+   }
+
+   fn stroke_run(&self, outline: &mut Path, segments: &[StrokeSegment], closed: bool, half: f64) {
+      for segment in segments {
+         segment.add_body(outline, half);
+      }
+
+      for pair in segments.windows(2) {
+         self.add_join(outline, &pair[0], &pair[1], half);
+      }
+
+      let first = &segments[0];
+      let last = &segments[segments.len() - 1];
+
+      if closed {
+         self.add_join(outline, last, first, half);
+      } else {
+         let start_tangent = first.start_tangent();
+         let end_tangent = last.end_tangent();
+
+         self.add_cap(outline, &first.from(), (-start_tangent.0, -start_tangent.1), half);
+         self.add_cap(outline, &last.to(), end_tangent, half);
+      }
+   }
+
+   /// Strokes the dashes of a contour as open runs. A dash reaching the end
+   /// of a segment carries on into the next one, keeping the joins between.
+   /// On closed contours a dash running through the start continues the one
+   /// ending there, joined rather than capped at the start point.
+   fn stroke_dashed(&self, outline: &mut Path, segments: &[StrokeSegment], mut cursor: DashCursor, closed: bool, half: f64) {
+      let mut runs: Vec<Vec<StrokeSegment>> = Vec::new();
+      let mut run: Vec<StrokeSegment> = Vec::new();
+      let mut open = false;
+      let mut from_start = false;
+
+      for (index, segment) in segments.iter().enumerate() {
+         let length = segment.length();
+
+         for (start, end) in cursor.intervals(length) {
+            let continues = open && start == 0.;
+
+            if !continues && !run.is_empty() {
+               runs.push(mem::take(&mut run));
+            }
+
+            if end > start {
+               let part = segment.part(start, end);
+
+               // Parts shorter than a unit would turn arcs into full circles
+               if part.from() != part.to() || (start == 0. && end == length) {
+                  from_start |= index == 0 && start == 0.;
+                  run.push(part);
+               }
+            } else if !continues {
+               let (point, tangent) = segment.point_at(start);
+               self.add_dot(outline, &point, tangent, half);
+            }
+
+            open = end == length;
+         }
+
+         if !open && !run.is_empty() {
+            runs.push(mem::take(&mut run));
+         }
+      }
+
+      if closed && from_start && !run.is_empty() {
+         if runs.is_empty() {
+            // One dash around the whole contour
+            self.stroke_run(outline, &run, true, half);
+            return;
+         }
+
+         run.append(&mut runs[0]);
+         runs[0] = run;
+      } else if !run.is_empty() {
+         runs.push(run);
+      }
+
+      for run in &runs {
+         self.stroke_run(outline, run, false, half);
+      }
+   }
+
+   fn stroke_segments(&self, contour: &Contour) -> Vec<StrokeSegment> {
+      let mut segments = Vec::new();
+      let mut from = contour.start;
+
+      for edge in &contour.edges {
+         match *edge {
+            PathEdge::Line(to) => {
+               push_line(&mut segments, from, to, false);
+            },
+            PathEdge::Arc { end, center, radius, clockwise } => {
+               segments.push(StrokeSegment::Arc {
+                  from,
+                  to: end,
+                  center,
+                  radius,
+                  clockwise,
+               });
+            },
+            PathEdge::Quad { control, end } => {
+               let controls = [from, control, end];
+               self.flatten(&mut segments, &controls);
+            },
+            PathEdge::Cubic { control1, control2, end } => {
+               let controls = [from, control1, control2, end];
+               self.flatten(&mut segments, &controls);
+            },
+         }
+
+         from = edge.end();
+      }
+
+      segments
+   }
+
+   /// Approximates a Bézier curve with lines, the joins between them are
+   /// marked smooth so they are always mitered.
+   fn flatten(&self, segments: &mut Vec<StrokeSegment>, controls: &[Point]) {
+      let mut from = controls[0];
+
+      for (i, to) in flatten_bezier(controls, self.tolerance).into_iter().enumerate() {
+         push_line(segments, from, to, i > 0);
+
+         from = to;
+      }
+   }
+
+   fn add_join(&self, outline: &mut Path, incoming: &StrokeSegment, outgoing: &StrokeSegment, half: f64) {
+      let vertex = incoming.to();
+
+      let t1 = incoming.end_tangent();
+      let t2 = outgoing.start_tangent();
+
+      let cross = t1.0 * t2.1 - t1.1 * t2.0;
+      let dot = t1.0 * t2.0 + t1.1 * t2.1;
+
+      if cross.abs() < 1e-9 && dot > 0. {
+         return;
+      }
+
+      // The outer side of a right turn is on the left
+      let right_turn = cross < 0.;
+
+      let n1 = outer_normal(t1, right_turn);
+      let n2 = outer_normal(t2, right_turn);
+
+      let a = offset(&vertex, n1, half);
+      let b = offset(&vertex, n2, half);
+
+      if a == b {
+         return;
+      }
+
+      let join = if outgoing.smooth() { LineJoin::Miter } else { self.join };
+
+      match join {
+         LineJoin::Bevel => {
+            add_polygon(outline, &[vertex, a, b]);
+         },
+         LineJoin::Miter => {
+            let cos = n1.0 * n2.0 + n1.1 * n2.1;
+            let ratio = (2. / (1. + cos)).sqrt();
+
+            if ratio > self.miter_limit && !outgoing.smooth() {
+               add_polygon(outline, &[vertex, a, b]);
+            } else {
+               let scale = half / (1. + cos);
+               let tip = offset(&vertex, (n1.0 + n2.0, n1.1 + n2.1), scale);
+
+               add_polygon(outline, &[vertex, a, tip, b]);
+            }
+         },
+         LineJoin::Round => {
+            // The arc around the outside turns with the path, clockwise for
+            // right turns, so the wedge is traced in whichever order keeps
+            // the arc clockwise
+            let (first, second) = if right_turn { (a, b) } else { (b, a) };
+
+            outline.move_to(vertex.x, vertex.y);
+            outline.line_to(first.x, first.y);
+            outline.arc_to(second.x, second.y, vertex.x, vertex.y, true);
+            outline.close();
+         },
+      }
+   }
+
+   /// Caps facing both ways, for dashes and subpaths of zero length.
+   fn add_dot(&self, outline: &mut Path, point: &Point, direction: (f64, f64), half: f64) {
+      self.add_cap(outline, point, direction, half);
+      self.add_cap(outline, point, (-direction.0, -direction.1), half);
+   }
+
+   fn add_cap(&self, outline: &mut Path, end: &Point, direction: (f64, f64), half: f64) {
+      let normal = (-direction.1, direction.0);
+
+      let left = offset(end, normal, half);
+      let right = offset(end, normal, -half);
+
+      match self.cap {
+         LineCap::Butt => {},
+         LineCap::Square => {
+            let left_out = offset(&left, direction, half);
+            let right_out = offset(&right, direction, half);
+
+            add_polygon(outline, &[left, left_out, right_out, right]);
+         },
+         LineCap::Round => {
+            outline.move_to(left.x, left.y);
+            outline.arc_to(right.x, right.y, end.x, end.y, true);
+            outline.close();
+         },
+      }
+   }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StrokeSegment {
+   Line {
+      from: Point,
+      to: Point,
+      smooth: bool,
+   },
+   Arc {
+      from: Point,
+      to: Point,
+      center: Point,
+      radius: i64,
+      clockwise: bool,
+   },
+}
+
+impl StrokeSegment {
+   #[inline]
+   fn from(&self) -> Point {
+      match *self {
+         StrokeSegment::Line { from, .. } => from,
+         StrokeSegment::Arc { from, .. } => from,
+      }
+   }
+
+   #[inline]
+   fn to(&self) -> Point {
+      match *self {
+         StrokeSegment::Line { to, .. } => to,
+         StrokeSegment::Arc { to, .. } => to,
+      }
+   }
+
+   /// Whether the join before this segment lies inside a flattened curve.
+   #[inline]
+   fn smooth(&self) -> bool {
+      match *self {
+         StrokeSegment::Line { smooth, .. } => smooth,
+         StrokeSegment::Arc { .. } => false,
+      }
+   }
+
+   #[inline]
+   fn start_tangent(&self) -> (f64, f64) {
+      self.tangent(&self.from())
+   }
+
+   #[inline]
+   fn end_tangent(&self) -> (f64, f64) {
+      self.tangent(&self.to())
+   }
+
+   fn tangent(&self, at: &Point) -> (f64, f64) {
+      match *self {
+         StrokeSegment::Line { from, to, .. } => direction(&from, &to),
+         StrokeSegment::Arc { center, clockwise, .. } => {
+            let u = direction(&center, at);
+
+            if clockwise {
+               (u.1, -u.0)
+            } else {
+               (-u.1, u.0)
+            }
+         },
+      }
+   }
+
+   fn length(&self) -> f64 {
+      match *self {
+         StrokeSegment::Line { from, to, .. } => distance(&from, &to),
+         StrokeSegment::Arc { from, to, center, radius, clockwise } => {
+            radius as f64 * arc_sweep(&from, &to, &center, clockwise)
+         },
+      }
+   }
+
+   /// Point and tangent at `distance` along the segment.
+   fn point_at(&self, distance: f64) -> (Point, (f64, f64)) {
+      let point = match *self {
+         StrokeSegment::Line { from, to, .. } => {
+            let t = direction(&from, &to);
+            offset(&from, t, distance)
+         },
+         StrokeSegment::Arc { from, center, radius, clockwise, .. } => {
+            let turn = distance / radius as f64;
+            let a = angle(&from, &center) + if clockwise { -turn } else { turn };
+
+            circle_point(&center, radius, a)
+         },
+      };
+
+      if point == self.from() || point == self.to() {
+         // Ends keep their exact tangent
+         let tangent = if point == self.to() { self.end_tangent() } else { self.start_tangent() };
+         return (point, tangent);
+      }
+
+      (point, self.tangent(&point))
+   }
+
+   /// Part of the segment between two distances along it.
+   fn part(&self, start: f64, end: f64) -> StrokeSegment {
+      let from = if start == 0. { self.from() } else { self.point_at(start).0 };
+      let to = if end >= self.length() { self.to() } else { self.point_at(end).0 };
+
+      match *self {
+         StrokeSegment::Line { smooth, .. } => {
+            StrokeSegment::Line { from, to, smooth: smooth && start == 0. }
+         },
+         StrokeSegment::Arc { center, radius, clockwise, .. } => {
+            StrokeSegment::Arc { from, to, center, radius, clockwise }
+         },
+      }
+   }
+
+   fn add_body(&self, outline: &mut Path, half: f64) {
+      match *self {
+         StrokeSegment::Line { from, to, .. } => {
+            let t = direction(&from, &to);
+            let normal = (-t.1, t.0);
+
+            add_polygon(outline, &[
+               offset(&from, normal, half),
+               offset(&to, normal, half),
+               offset(&to, normal, -half),
+               offset(&from, normal, -half),
+            ]);
+         },
+         StrokeSegment::Arc { from, to, center, radius, clockwise } => {
+            let points = arc_pieces(&from, &to, &center, radius, clockwise);
+
+            for piece in points.windows(2) {
+               self.add_arc_piece(outline, &piece[0], &piece[1], half);
+            }
+         },
+      }
+   }
+
+   /// Annular sector around one quadrant piece of an arc, traced with the
+   /// outer arc clockwise. Strokes wider than the diameter become sectors.
+   fn add_arc_piece(&self, outline: &mut Path, p: &Point, q: &Point, half: f64) {
+      let (center, radius, clockwise) = match *self {
+         StrokeSegment::Arc { center, radius, clockwise, .. } => (center, radius, clockwise),
+         _ => return,
+      };
+
+      let normal = |at: &Point| {
+         let t = self.tangent(at);
+         (-t.1, t.0)
+      };
+
+      let (p_left, q_left) = (offset(p, normal(p), half), offset(q, normal(q), half));
+      let (p_right, q_right) = (offset(p, normal(p), -half), offset(q, normal(q), -half));
+
+      // Left is outside for clockwise arcs
+      let (outer_start, outer_end, inner_start, inner_end) = if clockwise {
+         (p_left, q_left, q_right, p_right)
+      } else {
+         (q_right, p_right, p_left, q_left)
+      };
+
+      outline.move_to(outer_start.x, outer_start.y);
+      outline.arc_to(outer_end.x, outer_end.y, center.x, center.y, true);
+
+      if half < radius as f64 {
+         outline.line_to(inner_start.x, inner_start.y);
+         outline.arc_to(inner_end.x, inner_end.y, center.x, center.y, false);
+      } else {
+         outline.line_to(center.x, center.y);
+      }
+
+      outline.close();
+   }
+}
+
+#[inline]
+fn push_line(segments: &mut Vec<StrokeSegment>, from: Point, to: Point, smooth: bool) {
+   if from != to {
+      segments.push(StrokeSegment::Line { from, to, smooth });
+   }
+}
+
+#[inline]
+fn distance(p1: &Point, p2: &Point) -> f64 {
+   let dx = (p2.x - p1.x) as f64;
+   let dy = (p2.y - p1.y) as f64;
+
+   (dx * dx + dy * dy).sqrt()
+}
+
+#[inline]
+fn direction(from: &Point, to: &Point) -> (f64, f64) {
+   let dx = (to.x - from.x) as f64;
+   let dy = (to.y - from.y) as f64;
+
+   let len = (dx * dx + dy * dy).sqrt();
+
+   (dx / len, dy / len)
+}
+
+#[inline]
+fn outer_normal(tangent: (f64, f64), right_turn: bool) -> (f64, f64) {
+   if right_turn {
+      (-tangent.1, tangent.0)
+   } else {
+      (tangent.1, -tangent.0)
+   }
+}
+
+#[inline]
+fn offset(p: &Point, direction: (f64, f64), distance: f64) -> Point {
+   Point::new(
+      (p.x as f64 + direction.0 * distance).round() as i64,
+      (p.y as f64 + direction.1 * distance).round() as i64,
+   )
+}
+
+/// Adds a convex polygon as a clockwise contour, skipping empty ones.
+fn add_polygon(outline: &mut Path, points: &[Point]) {
+   let mut area = 0;
+   for i in 0..points.len() {
+      let p1 = &points[i];
+      let p2 = &points[(i + 1) % points.len()];
+      area += (p2.x - p1.x) * (p1.y + p2.y);
+   }
+
+   if area == 0 {
+      return;
+   }
+
+   let mut ordered = points.to_vec();
+   if area < 0 {
+      ordered.reverse();
+   }
+
+   outline.move_to(ordered[0].x, ordered[0].y);
+   for p in &ordered[1..] {
+      outline.line_to(p.x, p.y);
+   }
+   outline.close();
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::point::Point;
+
+   use frame::Frame;
+   use draw::{RGB, RGBA};
+
+   use raster::{Path, SceneBuilder, SceneCompiler, Rasterizer, EdgeType};
+   use raster::path::PathEdge;
+
+   use raster::Dash;
+
+   use super::{Stroke, LineJoin, LineCap};
+
+   fn polyline(points: &[(i64, i64)]) -> Path {
+      let mut path = Path::new();
+      path.move_to(points[0].0, points[0].1);
+      for p in &points[1..] {
+         path.line_to(p.0, p.1);
+      }
+      path
+   }
+
+   fn corners(path: &Path, contour: usize) -> Vec<Point> {
+      let contour = &path.contours[contour];
+      let mut points = vec![contour.start];
+      for edge in &contour.edges[..contour.edges.len() - 1] {
+         points.push(edge.end());
+      }
+      points
+   }
+
+   #[test]
+   fn test_stroke_line_caps() {
+      let line = polyline(&[(1000, 1000), (5000, 1000)]);
+
+      let mut stroke = Stroke::new(400);
+      let outline = stroke.stroke(&line);
+
+      assert_eq!(outline.contours.len(), 1);
+      assert_eq!(corners(&outline, 0), vec![
+         Point::new(1000, 1200),
+         Point::new(5000, 1200),
+         Point::new(5000, 800),
+         Point::new(1000, 800),
+      ]);
+
+      stroke.cap = LineCap::Square;
+      let outline = stroke.stroke(&line);
+
+      assert_eq!(outline.contours.len(), 3);
+      assert!(corners(&outline, 2).contains(&Point::new(5200, 1200)));
+
+      stroke.cap = LineCap::Round;
+      let outline = stroke.stroke(&line);
+
+      assert_eq!(outline.contours.len(), 3);
+      assert_eq!(outline.contours[2].start, Point::new(5000, 1200));
+      assert_eq!(outline.contours[2].edges[0], PathEdge::Arc {
+         end: Point::new(5000, 800),
+         center: Point::new(5000, 1000),
+         radius: 200,
+         clockwise: true,
+      });
+   }
+
+   #[test]
+   fn test_stroke_joins() {
+      let corner = polyline(&[(1000, 1000), (1000, 5000), (5000, 5000)]);
+
+      let mut stroke = Stroke::new(400);
+      let outline = stroke.stroke(&corner);
+
+      assert_eq!(outline.contours.len(), 3);
+      assert!(corners(&outline, 2).contains(&Point::new(800, 5200)));
+
+      stroke.join = LineJoin::Bevel;
+      let outline = stroke.stroke(&corner);
+
+      assert_eq!(corners(&outline, 2).len(), 3);
+
+      stroke.join = LineJoin::Round;
+      let outline = stroke.stroke(&corner);
+
+      let mut builder = SceneBuilder::new();
+      builder.add_path(&outline, RGBA::new(0, 0, 0, 255)).unwrap();
+      let scene = builder.build();
+
+      assert!(scene.edges.iter().any(|edge| edge.edge_type == EdgeType::CTR));
+
+      let sharp = polyline(&[(1000, 1000), (3000, 5000), (5000, 1000)]);
+
+      stroke.join = LineJoin::Miter;
+      stroke.miter_limit = 2.;
+      assert_eq!(corners(&stroke.stroke(&sharp), 2).len(), 3);
+
+      stroke.miter_limit = 10.;
+      assert_eq!(corners(&stroke.stroke(&sharp), 2).len(), 4);
+   }
+
+   #[test]
+   fn test_stroke_arc() {
+      let mut path = Path::new();
+      path.move_to(2000, 4000);
+      path.arc_to(4000, 2000, 2000, 2000, true);
+
+      let outline = Stroke::new(1000).stroke(&path);
+
+      assert_eq!(outline.contours.len(), 1);
+
+      let contour = &outline.contours[0];
+      assert_eq!(contour.start, Point::new(2000, 4500));
+      assert_eq!(contour.edges[0].end(), Point::new(4500, 2000));
+      assert_eq!(contour.edges[1].end(), Point::new(3500, 2000));
+      assert_eq!(contour.edges[2].end(), Point::new(2000, 3500));
+   }
+
+   #[test]
+   fn test_stroke_renders() {
+      let mut open = Path::new();
+      open.move_to(10000, 10000);
+      open.line_to(10000, 30000);
+      open.arc_to(20000, 40000, 20000, 30000, true);
+      open.quad_to(40000, 40000, 40000, 20000);
+      open.cubic_to(40000, 10000, 30000, 15000, 25000, 5000);
+
+      let mut closed = open.clone();
+      closed.close();
+
+      for path in &[open, closed] {
+         for &(join, cap) in &[
+            (LineJoin::Miter, LineCap::Butt),
+            (LineJoin::Round, LineCap::Round),
+            (LineJoin::Bevel, LineCap::Square),
+         ] {
+            let mut stroke = Stroke::new(2000);
+            stroke.join = join;
+            stroke.cap = cap;
+
+            let mut compiler = SceneCompiler::new();
+            compiler.add_path(&stroke.stroke(path), RGBA::new(255, 255, 255, 255));
+
+            let mut frame = Frame::new(50, 50);
+            Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+            assert_eq!(frame.get_pixel(10, 20), RGB::new(255, 255, 255));
+            assert_eq!(frame.get_pixel(20, 20), RGB::default());
+         }
+      }
+
+      // Bodies and the miter overlap around the corner, which still blends
+      // once
+      let corner = polyline(&[(5000, 5000), (5000, 15000), (15000, 15000)]);
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&Stroke::new(2000).stroke(&corner), RGBA::new(255, 255, 255, 128));
+
+      let mut frame = Frame::new(20, 20);
+      Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+      let body = frame.get_pixel(10, 14);
+      assert!(body.r > 100 && body.r < 155);
+      assert_eq!(frame.get_pixel(5, 14), body);
+      assert_eq!(frame.get_pixel(4, 15), body);
+      assert_eq!(frame.get_pixel(4, 10), body);
+   }
+
+   #[test]
+   fn test_stroke_dashes() {
+      let line = polyline(&[(1000, 1000), (11000, 1000)]);
+
+      let mut stroke = Stroke::new(400);
+      stroke.dash = Some(Dash::new(vec![2000., 1000.], 0.));
+
+      let outline = stroke.stroke(&line);
+
+      assert_eq!(outline.contours.len(), 4);
+      assert_eq!(corners(&outline, 3), vec![
+         Point::new(10000, 1200),
+         Point::new(11000, 1200),
+         Point::new(11000, 800),
+         Point::new(10000, 800),
+      ]);
+
+      stroke.dash = Some(Dash::new(vec![0., 2000.], 0.));
+      stroke.cap = LineCap::Round;
+
+      let outline = stroke.stroke(&polyline(&[(1000, 1000), (6000, 1000)]));
+
+      assert_eq!(outline.contours.len(), 6);
+      assert_eq!(outline.contours[2].start, Point::new(3000, 1200));
+   }
+
+   #[test]
+   fn test_stroke_closed_dashes() {
+      let mut square = polyline(&[(1000, 1000), (5000, 1000), (5000, 5000), (1000, 5000)]);
+      square.close();
+
+      // The dash from 14500 on wraps around to 1500, mitered at the start
+      let mut stroke = Stroke::new(400);
+      stroke.dash = Some(Dash::new(vec![3000., 1000.], 1500.));
+
+      let outline = stroke.stroke(&square);
+
+      assert_eq!(outline.contours.len(), 12);
+      assert!((0..12).any(|i| corners(&outline, i).contains(&Point::new(800, 800))));
+
+      // Without caps at the start point
+      stroke.cap = LineCap::Square;
+      assert_eq!(stroke.stroke(&square).contours.len(), 20);
+
+      // A dash all around the contour is stroked as if undashed
+      stroke.dash = Some(Dash::new(vec![20000., 1000.], 0.));
+      assert_eq!(stroke.stroke(&square).contours.len(), 8);
+
+      // A gap before the end keeps the cap at the start, four dashes along
+      // the sides with two caps each
+      stroke.dash = Some(Dash::new(vec![3000., 1000.], 0.));
+      assert_eq!(stroke.stroke(&square).contours.len(), 12);
+   }
+
+   #[test]
+   fn test_stroke_zero_length() {
+      let mut path = polyline(&[(1000, 1000), (1000, 1000)]);
+      path.move_to(5000, 1000);
+      path.close();
+      path.move_to(9000, 1000);
+
+      let mut stroke = Stroke::new(400);
+      assert!(stroke.stroke(&path).contours.is_empty());
+
+      // Two half discs, the lone move draws nothing
+      stroke.cap = LineCap::Round;
+      let outline = stroke.stroke(&path);
+
+      assert_eq!(outline.contours.len(), 4);
+      assert_eq!(outline.contours[0].start, Point::new(1000, 1200));
+      assert_eq!(outline.contours[1].start, Point::new(1000, 800));
+      assert_eq!(outline.contours[2].edges[0], PathEdge::Arc {
+         end: Point::new(5000, 800),
+         center: Point::new(5000, 1000),
+         radius: 200,
+         clockwise: true,
+      });
+
+      // A square aligned with the x axis
+      stroke.cap = LineCap::Square;
+      let outline = stroke.stroke(&path);
+
+      assert_eq!(outline.contours.len(), 4);
+
+      let mut square = corners(&outline, 0);
+      square.extend(corners(&outline, 1));
+      for corner in &[(800, 800), (1200, 800), (800, 1200), (1200, 1200)] {
+         assert!(square.contains(&Point::new(corner.0, corner.1)));
+      }
+
+      // Dots falling in a dash gap are left out
+      stroke.dash = Some(Dash::new(vec![100., 100.], 150.));
+      assert!(stroke.stroke(&path).contours.is_empty());
+
+      stroke.dash = Some(Dash::new(vec![100., 100.], 50.));
+      assert_eq!(stroke.stroke(&path).contours.len(), 4);
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&stroke.stroke(&path), RGBA::new(255, 255, 255, 255));
+
+      let mut frame = Frame::new(12, 3);
+      Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+      // Each 0.4 pixel square covers the corners of four pixels
+      assert_eq!(frame.get_pixel(0, 0), RGB::new(10, 10, 10));
+      assert_eq!(frame.get_pixel(5, 1), RGB::new(10, 10, 10));
+      assert_eq!(frame.get_pixel(9, 0), RGB::default());
+      assert_eq!(frame.get_pixel(3, 0), RGB::default());
+   }
+
+   #[test]
+   fn test_stroke_dash_into_arc() {
+      let mut path = Path::new();
+      path.move_to(1000, 5000);
+      path.line_to(5000, 5000);
+      path.arc_to(7000, 3000, 5000, 3000, true);
+
+      let mut stroke = Stroke::new(400);
+      stroke.dash = Some(Dash::new(vec![6000., 1000.], 0.));
+
+      let outline = stroke.stroke(&path);
+
+      assert_eq!(outline.contours.len(), 3);
+
+      let arc = &outline.contours[1];
+      assert_eq!(arc.start, Point::new(5000, 5200));
+
+      // The first dash leaves the line 2000 units, or one radian, into the arc
+      let end = arc.edges[0].end();
+      assert!((end.x - 6851).abs() <= 1 && (end.y - 4189).abs() <= 1);
+
+      assert_eq!(outline.contours[2].edges[0].end(), Point::new(7200, 3000));
+   }
+}
@@ -0,0 +1,977 @@
+use std::fs;
+
+use error::{RuntimeError, ErrorKind};
+use geom::affine::Affine;
+use draw::RGBA;
+use frame::Frame;
+use raster::{Path, FillRule, Stroke, LineJoin, LineCap, Dash, SceneCompiler, Rasterizer};
+
+use super::path::{PathCommand, parse_path, build_path, Scanner};
+use super::xml::{Element, parse_xml};
+
+
+/// Size of documents giving neither a size nor a view box.
+const DEFAULT_WIDTH: f64 = 300.;
+const DEFAULT_HEIGHT: f64 = 150.;
+
+const NAMED_COLORS: &[(&str, u32)] = &[
+   ("black", 0x000000), ("silver", 0xc0c0c0), ("gray", 0x808080), ("grey", 0x808080),
+   ("white", 0xffffff), ("maroon", 0x800000), ("red", 0xff0000), ("purple", 0x800080),
+   ("fuchsia", 0xff00ff), ("magenta", 0xff00ff), ("green", 0x008000), ("lime", 0x00ff00),
+   ("olive", 0x808000), ("yellow", 0xffff00), ("navy", 0x000080), ("blue", 0x0000ff),
+   ("teal", 0x008080), ("aqua", 0x00ffff), ("cyan", 0x00ffff), ("orange", 0xffa500),
+   ("brown", 0xa52a2a), ("pink", 0xffc0cb), ("gold", 0xffd700), ("darkgray", 0xa9a9a9),
+   ("darkgrey", 0xa9a9a9), ("lightgray", 0xd3d3d3), ("lightgrey", 0xd3d3d3),
+];
+
+/// Outline of a document element in its own user units, with the fill and
+/// stroke it is painted with.
+#[derive(Debug, Clone)]
+pub struct Shape {
+   pub commands: Vec<PathCommand>,
+   /// Maps the shape's user units to those of the root element.
+   pub transform: Affine,
+   pub fill: Option<RGBA>,
+   pub fill_rule: FillRule,
+   pub stroke: Option<RGBA>,
+   pub stroke_width: f64,
+   pub join: LineJoin,
+   pub cap: LineCap,
+   pub miter_limit: f64,
+   pub dash: Option<Dash>,
+}
+
+impl Shape {
+   /// Fill and stroke outline of the shape in painting order, `transform`
+   /// mapping root user units to world coordinates.
+   pub fn paths(&self, transform: &Affine) -> Vec<(Path, RGBA)> {
+      let transform = self.transform.then(transform);
+
+      let mut path = build_path(&self.commands, &transform);
+      path.fill_rule = self.fill_rule;
+
+      let mut paths = Vec::new();
+
+      if let Some(color) = self.stroke {
+         // Stroke lengths follow the average scale of a non-uniform transform
+         let scale = transform.determinant().abs().sqrt();
+
+         let width = (self.stroke_width * scale).round() as i64;
+
+         if width > 0 {
+            let mut stroke = Stroke::new(width);
+            stroke.join = self.join;
+            stroke.cap = self.cap;
+            stroke.miter_limit = self.miter_limit;
+            stroke.dash = self.dash.as_ref().map(|dash| Dash::new(
+               dash.array.iter().map(|len| len * scale).collect(),
+               dash.offset * scale
+            ));
+
+            paths.push((stroke.stroke(&path), color));
+         }
+      }
+
+      if let Some(color) = self.fill {
+         paths.insert(0, (path, color));
+      }
+
+      paths
+   }
+
+   pub fn add_to(&self, compiler: &mut SceneCompiler, transform: &Affine) {
+      for (path, color) in self.paths(transform) {
+         compiler.add_path(&path, color);
+      }
+   }
+}
+
+/// Static SVG document flattened into its shapes in painting order.
+///
+/// Covers the basic shapes, paths and groups with transforms, solid colors
+/// with opacity, and strokes with joins, caps and dashes, set either as
+/// attributes or in `style`. Group opacity is multiplied into each shape
+/// rather than composited as a layer. Nested `svg` elements map their view
+/// box into their viewport without clipping to it, a missing size taking
+/// that of the view box. Gradients and patterns paint with their fallback
+/// color; text, images, `use`, clipping and masking are left out.
+#[derive(Debug, Clone)]
+pub struct Document {
+   pub width: f64,
+   pub height: f64,
+   /// Area of user space shown as `[x, y, width, height]`.
+   pub view_box: [f64; 4],
+   pub shapes: Vec<Shape>,
+}
+
+impl Document {
+   pub fn parse(text: &str) -> Result<Self, RuntimeError> {
+      let root = parse_xml(text)?;
+
+      if local_name(&root.name) != "svg" {
+         return Err(invalid_svg(format!("Root element {} is not svg", root.name)));
+      }
+
+      let view_box = view_box(&root)?;
+
+      let (default_width, default_height) = match view_box {
+         Some(view_box) => (view_box[2], view_box[3]),
+         None => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+      };
+
+      let width = root.attribute("width").and_then(length).unwrap_or(default_width);
+      let height = root.attribute("height").and_then(length).unwrap_or(default_height);
+
+      let mut shapes = Vec::new();
+      add_element(&root, &Style::default(), &Affine::identity(), true, &mut shapes)?;
+
+      Ok(Document {
+         width,
+         height,
+         view_box: view_box.unwrap_or([0., 0., width, height]),
+         shapes,
+      })
+   }
+
+   pub fn from_file(path: &str) -> Result<Self, RuntimeError> {
+      match fs::read_to_string(path) {
+         Ok(text) => Document::parse(&text),
+         Err(err) => Err(RuntimeError::new(
+            ErrorKind::Other,
+            format!("Cannot read SVG {}: {}", path, err)
+         )),
+      }
+   }
+
+   /// Transform showing the view box centered in the world rectangle with
+   /// bottom left corner `(x, y)`, scaled uniformly to fit and flipped so
+   /// that y points up.
+   pub fn fit(&self, x: f64, y: f64, width: f64, height: f64) -> Affine {
+      let [view_x, view_y, view_width, view_height] = self.view_box;
+
+      let scale = (width / view_width).min(height / view_height);
+
+      let left = x + (width - view_width * scale) / 2.;
+      let top = y + (height + view_height * scale) / 2.;
+
+      Affine::translate(-view_x, -view_y)
+         .then(&Affine::scale(scale, -scale))
+         .then(&Affine::translate(left, top))
+   }
+
+   /// Adds all shapes, `transform` mapping root user units to world
+   /// coordinates. The compiler only shows the topmost shape at any point,
+   /// so translucent shapes hide what lies beneath them.
+   pub fn add_to(&self, compiler: &mut SceneCompiler, transform: &Affine) {
+      for shape in &self.shapes {
+         shape.add_to(compiler, transform);
+      }
+   }
+
+   /// Renders the document over the frame contents, fitted to the frame.
+   /// Everything below a translucent path is rendered before it, so that
+   /// it blends with the frame.
+   pub fn render(&self, frame: &mut Frame, div_per_pixel: i64) -> Result<(), RuntimeError> {
+      let dpp = div_per_pixel as f64;
+      let transform = self.fit(0., 0., frame.width as f64 * dpp, frame.height as f64 * dpp);
+
+      let mut rasterizer = Rasterizer::new();
+      let mut compiler = SceneCompiler::new();
+      let mut pending = false;
+
+      for shape in &self.shapes {
+         for (path, color) in shape.paths(&transform) {
+            if color.a < 255 && pending {
+               rasterizer.render(&compiler.compile()?, frame, div_per_pixel)?;
+               compiler = SceneCompiler::new();
+            }
+
+            compiler.add_path(&path, color);
+            pending = true;
+         }
+      }
+
+      if pending {
+         rasterizer.render(&compiler.compile()?, frame, div_per_pixel)?;
+      }
+
+      Ok(())
+   }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PaintValue {
+   None,
+   CurrentColor,
+   Color(RGBA),
+}
+
+/// Presentation properties of an element after inheritance.
+#[derive(Debug, Clone)]
+struct Style {
+   fill: PaintValue,
+   fill_opacity: f64,
+   fill_rule: FillRule,
+   stroke: PaintValue,
+   stroke_opacity: f64,
+   stroke_width: f64,
+   join: LineJoin,
+   cap: LineCap,
+   miter_limit: f64,
+   dash: Vec<f64>,
+   dash_offset: f64,
+   color: RGBA,
+   /// Opacity of the element itself, not inherited.
+   opacity: f64,
+   /// Product of the opacities of the enclosing groups.
+   group_opacity: f64,
+   hidden: bool,
+}
+
+impl Default for Style {
+   fn default() -> Style {
+      Style {
+         fill: PaintValue::Color(RGBA::new(0, 0, 0, 255)),
+         fill_opacity: 1.,
+         fill_rule: FillRule::NonZero,
+         stroke: PaintValue::None,
+         stroke_opacity: 1.,
+         stroke_width: 1.,
+         join: LineJoin::Miter,
+         cap: LineCap::Butt,
+         miter_limit: 4.,
+         dash: Vec::new(),
+         dash_offset: 0.,
+         color: RGBA::new(0, 0, 0, 255),
+         opacity: 1.,
+         group_opacity: 1.,
+         hidden: false,
+      }
+   }
+}
+
+impl Style {
+   /// Style of a child element before its own properties are applied.
+   fn inherit(&self) -> Style {
+      let mut style = self.clone();
+      style.group_opacity *= self.opacity;
+      style.opacity = 1.;
+      style.hidden = false;
+      style
+   }
+
+   /// Applies presentation attributes, then the declarations in `style`
+   /// which take precedence over them.
+   fn apply(&mut self, element: &Element) {
+      for (name, value) in &element.attributes {
+         self.set(name, value);
+      }
+
+      if let Some(style) = element.attribute("style") {
+         for declaration in style.split(';') {
+            if let Some((name, value)) = declaration.split_once(':') {
+               self.set(name.trim(), value);
+            }
+         }
+      }
+   }
+
+   /// Sets one property, ignoring unknown properties and invalid values.
+   fn set(&mut self, name: &str, value: &str) {
+      let value = value.trim();
+
+      match name {
+         "fill" => if let Some(paint) = parse_paint(value) {
+            self.fill = paint;
+         },
+         "stroke" => if let Some(paint) = parse_paint(value) {
+            self.stroke = paint;
+         },
+         "color" => if let Some(color) = parse_color(value) {
+            self.color = color;
+         },
+         "fill-opacity" => if let Some(opacity) = parse_opacity(value) {
+            self.fill_opacity = opacity;
+         },
+         "stroke-opacity" => if let Some(opacity) = parse_opacity(value) {
+            self.stroke_opacity = opacity;
+         },
+         "opacity" => if let Some(opacity) = parse_opacity(value) {
+            self.opacity = opacity;
+         },
+         "fill-rule" => match value {
+            "nonzero" => self.fill_rule = FillRule::NonZero,
+            "evenodd" => self.fill_rule = FillRule::EvenOdd,
+            _ => {},
+         },
+         "stroke-width" => match length(value) {
+            Some(width) if width >= 0. => self.stroke_width = width,
+            _ => {},
+         },
+         "stroke-linejoin" => match value {
+            "miter" | "miter-clip" | "arcs" => self.join = LineJoin::Miter,
+            "round" => self.join = LineJoin::Round,
+            "bevel" => self.join = LineJoin::Bevel,
+            _ => {},
+         },
+         "stroke-linecap" => match value {
+            "butt" => self.cap = LineCap::Butt,
+            "round" => self.cap = LineCap::Round,
+            "square" => self.cap = LineCap::Square,
+            _ => {},
+         },
+         "stroke-miterlimit" => match value.parse::<f64>() {
+            Ok(limit) if limit >= 1. => self.miter_limit = limit,
+            _ => {},
+         },
+         "stroke-dasharray" => if value == "none" {
+            self.dash.clear();
+         } else {
+            let lengths: Option<Vec<f64>> = value
+               .split(|ch: char| ch == ',' || ch.is_whitespace())
+               .filter(|part| !part.is_empty())
+               .map(length)
+               .collect();
+
+            if let Some(lengths) = lengths {
+               self.dash = lengths;
+            }
+         },
+         "stroke-dashoffset" => if let Some(offset) = length(value) {
+            self.dash_offset = offset;
+         },
+         "display" => self.hidden = value == "none",
+         _ => {},
+      }
+   }
+
+   fn paint(&self, paint: PaintValue, opacity: f64) -> Option<RGBA> {
+      let color = match paint {
+         PaintValue::None => return None,
+         PaintValue::CurrentColor => self.color,
+         PaintValue::Color(color) => color,
+      };
+
+      let alpha = (color.a as f64 * opacity * self.opacity * self.group_opacity).round() as u8;
+
+      if alpha == 0 {
+         None
+      } else {
+         Some(RGBA::new(color.r, color.g, color.b, alpha))
+      }
+   }
+
+   fn shape(&self, commands: Vec<PathCommand>, transform: Affine, fillable: bool) -> Shape {
+      Shape {
+         commands,
+         transform,
+         fill: if fillable { self.paint(self.fill, self.fill_opacity) } else { None },
+         fill_rule: self.fill_rule,
+         stroke: self.paint(self.stroke, self.stroke_opacity),
+         stroke_width: self.stroke_width,
+         join: self.join,
+         cap: self.cap,
+         miter_limit: self.miter_limit,
+         dash: if self.dash.is_empty() { None } else { Some(Dash::new(self.dash.clone(), self.dash_offset)) },
+      }
+   }
+}
+
+/// Adds the shapes of an element and its descendants. Elements other than
+/// shapes and containers are skipped along with their content. The view box
+/// of the root element is left to `Document::fit`.
+fn add_element(
+   element: &Element, parent: &Style, parent_transform: &Affine, root: bool, shapes: &mut Vec<Shape>
+) -> Result<(), RuntimeError> {
+   let name = local_name(&element.name);
+
+   match name {
+      "svg" | "g" | "a" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" | "path" => {},
+      _ => return Ok(()),
+   }
+
+   let mut style = parent.inherit();
+   style.apply(element);
+
+   if style.hidden {
+      return Ok(());
+   }
+
+   let transform = match element.attribute("transform") {
+      Some(value) => parse_transform(value)?.then(parent_transform),
+      None => *parent_transform,
+   };
+
+   let transform = match name {
+      "svg" if !root => match viewport_transform(element)? {
+         Some(viewport) => viewport.then(&transform),
+         None => return Ok(()),
+      },
+      _ => transform,
+   };
+
+   match name {
+      "svg" | "g" | "a" => {
+         for child in &element.children {
+            add_element(child, &style, &transform, false, shapes)?;
+         }
+      },
+      _ => if let Some(commands) = shape_commands(name, element)? {
+         // Lines enclose no area to fill
+         shapes.push(style.shape(commands, transform, name != "line"));
+      },
+   }
+
+   Ok(())
+}
+
+/// Outline of a basic shape or path, or `None` when it has nothing to draw.
+fn shape_commands(name: &str, element: &Element) -> Result<Option<Vec<PathCommand>>, RuntimeError> {
+   let number = |attribute: &str| element.attribute(attribute).and_then(length);
+   let value = |attribute: &str| number(attribute).unwrap_or(0.);
+
+   let commands = match name {
+      "rect" => {
+         let (x, y, width, height) = (value("x"), value("y"), value("width"), value("height"));
+
+         if width <= 0. || height <= 0. {
+            return Ok(None);
+         }
+
+         let (rx, ry) = match (number("rx"), number("ry")) {
+            (Some(rx), Some(ry)) => (rx, ry),
+            (Some(radius), None) | (None, Some(radius)) => (radius, radius),
+            (None, None) => (0., 0.),
+         };
+
+         let rx = rx.max(0.).min(width / 2.);
+         let ry = ry.max(0.).min(height / 2.);
+
+         if rx == 0. || ry == 0. {
+            vec![
+               PathCommand::MoveTo(x, y),
+               PathCommand::LineTo(x + width, y),
+               PathCommand::LineTo(x + width, y + height),
+               PathCommand::LineTo(x, y + height),
+               PathCommand::Close,
+            ]
+         } else {
+            let (right, bottom) = (x + width, y + height);
+
+            vec![
+               PathCommand::MoveTo(x + rx, y),
+               PathCommand::LineTo(right - rx, y),
+               positive_arc(rx, ry, right, y + ry),
+               PathCommand::LineTo(right, bottom - ry),
+               positive_arc(rx, ry, right - rx, bottom),
+               PathCommand::LineTo(x + rx, bottom),
+               positive_arc(rx, ry, x, bottom - ry),
+               PathCommand::LineTo(x, y + ry),
+               positive_arc(rx, ry, x + rx, y),
+               PathCommand::Close,
+            ]
+         }
+      },
+      "circle" | "ellipse" => {
+         let (cx, cy) = (value("cx"), value("cy"));
+
+         let (rx, ry) = if name == "circle" {
+            (value("r"), value("r"))
+         } else {
+            (value("rx"), value("ry"))
+         };
+
+         if rx <= 0. || ry <= 0. {
+            return Ok(None);
+         }
+
+         vec![
+            PathCommand::MoveTo(cx + rx, cy),
+            positive_arc(rx, ry, cx - rx, cy),
+            positive_arc(rx, ry, cx + rx, cy),
+            PathCommand::Close,
+         ]
+      },
+      "line" => vec![
+         PathCommand::MoveTo(value("x1"), value("y1")),
+         PathCommand::LineTo(value("x2"), value("y2")),
+      ],
+      "polyline" | "polygon" => {
+         let points = numbers(element.attribute("points").unwrap_or(""))?;
+
+         if points.len() < 2 {
+            return Ok(None);
+         }
+
+         let mut commands = vec![PathCommand::MoveTo(points[0], points[1])];
+
+         for point in points[2..].chunks_exact(2) {
+            commands.push(PathCommand::LineTo(point[0], point[1]));
+         }
+
+         if name == "polygon" {
+            commands.push(PathCommand::Close);
+         }
+
+         commands
+      },
+      _ => match element.attribute("d") {
+         Some(data) => parse_path(data)?,
+         None => Vec::new(),
+      },
+   };
+
+   if commands.is_empty() {
+      Ok(None)
+   } else {
+      Ok(Some(commands))
+   }
+}
+
+/// Parses the `viewBox` attribute of an `svg` element.
+fn view_box(element: &Element) -> Result<Option<[f64; 4]>, RuntimeError> {
+   match element.attribute("viewBox") {
+      Some(value) => match numbers(value)?[..] {
+         [x, y, width, height] if width > 0. && height > 0. => Ok(Some([x, y, width, height])),
+         _ => Err(invalid_svg(format!("Invalid viewBox {}", value))),
+      },
+      None => Ok(None),
+   }
+}
+
+/// Maps the user units of a nested `svg` element to those of its parent,
+/// its view box scaled uniformly and centered in the viewport. `None` when
+/// the viewport is empty and nothing is drawn.
+fn viewport_transform(element: &Element) -> Result<Option<Affine>, RuntimeError> {
+   let number = |attribute: &str| element.attribute(attribute).and_then(length);
+   let (x, y) = (number("x").unwrap_or(0.), number("y").unwrap_or(0.));
+   let (width, height) = (number("width"), number("height"));
+
+   if width.unwrap_or(1.) <= 0. || height.unwrap_or(1.) <= 0. {
+      return Ok(None);
+   }
+
+   let transform = match view_box(element)? {
+      Some([view_x, view_y, view_width, view_height]) => {
+         let (width, height) = (width.unwrap_or(view_width), height.unwrap_or(view_height));
+         let scale = (width / view_width).min(height / view_height);
+
+         Affine::translate(-view_x, -view_y)
+            .then(&Affine::scale(scale, scale))
+            .then(&Affine::translate(
+               x + (width - view_width * scale) / 2.,
+               y + (height - view_height * scale) / 2.
+            ))
+      },
+      None => Affine::translate(x, y),
+   };
+
+   Ok(Some(transform))
+}
+
+/// Arc turning in the positive angle direction, a quarter of an ellipse in
+/// rounded rectangles and half of one in ellipses.
+#[inline]
+fn positive_arc(rx: f64, ry: f64, x: f64, y: f64) -> PathCommand {
+   PathCommand::ArcTo {
+      rx,
+      ry,
+      rotation: 0.,
+      large_arc: false,
+      sweep: true,
+      x,
+      y,
+   }
+}
+
+/// Parses a `transform` attribute, a list of transform functions applied
+/// from the last to the first.
+fn parse_transform(value: &str) -> Result<Affine, RuntimeError> {
+   let mut transform = Affine::identity();
+   let mut rest = value;
+
+   loop {
+      rest = rest.trim_start_matches(|ch: char| ch == ',' || ch.is_whitespace());
+
+      if rest.is_empty() {
+         return Ok(transform);
+      }
+
+      let (open, close) = match (rest.find('('), rest.find(')')) {
+         (Some(open), Some(close)) if open < close => (open, close),
+         _ => return Err(invalid_svg(format!("Invalid transform {}", value))),
+      };
+
+      let args = numbers(&rest[open + 1..close])?;
+
+      let function = match (rest[..open].trim(), &args[..]) {
+         ("matrix", &[a, b, c, d, e, f]) => Affine::new(a, b, c, d, e, f),
+         ("translate", &[x]) => Affine::translate(x, 0.),
+         ("translate", &[x, y]) => Affine::translate(x, y),
+         ("scale", &[scale]) => Affine::scale(scale, scale),
+         ("scale", &[x, y]) => Affine::scale(x, y),
+         ("rotate", &[angle]) => Affine::rotate(angle.to_radians()),
+         ("rotate", &[angle, x, y]) => Affine::translate(-x, -y)
+            .then(&Affine::rotate(angle.to_radians()))
+            .then(&Affine::translate(x, y)),
+         ("skewX", &[angle]) => Affine::new(1., 0., angle.to_radians().tan(), 1., 0., 0.),
+         ("skewY", &[angle]) => Affine::new(1., angle.to_radians().tan(), 0., 1., 0., 0.),
+         _ => return Err(invalid_svg(format!("Invalid transform {}", value))),
+      };
+
+      transform = function.then(&transform);
+      rest = &rest[close + 1..];
+   }
+}
+
+/// Paint of `fill` or `stroke`. References to paint servers use their
+/// fallback color, or paint nothing without one.
+fn parse_paint(value: &str) -> Option<PaintValue> {
+   match value {
+      "none" => Some(PaintValue::None),
+      "currentColor" => Some(PaintValue::CurrentColor),
+      _ if value.starts_with("url(") => {
+         let end = value.find(')')?;
+         Some(parse_paint(value[end + 1..].trim()).unwrap_or(PaintValue::None))
+      },
+      _ => parse_color(value).map(PaintValue::Color),
+   }
+}
+
+/// Hex, `rgb()`, `rgba()` or basic named color.
+fn parse_color(value: &str) -> Option<RGBA> {
+   if let Some(hex) = value.strip_prefix('#') {
+      let digits: Vec<u8> = hex.chars()
+         .map(|ch| ch.to_digit(16).map(|digit| digit as u8))
+         .collect::<Option<_>>()?;
+
+      return match digits.len() {
+         3 | 4 => {
+            let alpha = digits.get(3).map_or(255, |digit| digit * 17);
+            Some(RGBA::new(digits[0] * 17, digits[1] * 17, digits[2] * 17, alpha))
+         },
+         6 | 8 => {
+            let byte = |i: usize| digits[2 * i] << 4 | digits[2 * i + 1];
+            let alpha = if digits.len() == 8 { byte(3) } else { 255 };
+            Some(RGBA::new(byte(0), byte(1), byte(2), alpha))
+         },
+         _ => None,
+      };
+   }
+
+   let lower = value.to_ascii_lowercase();
+
+   if let Some(args) = lower.strip_prefix("rgba(").or_else(|| lower.strip_prefix("rgb(")) {
+      let parts: Vec<&str> = args.strip_suffix(')')?
+         .split(|ch: char| ch == ',' || ch == '/' || ch.is_whitespace())
+         .filter(|part| !part.is_empty())
+         .collect();
+
+      let channel = |part: &str| -> Option<u8> {
+         let value = match part.strip_suffix('%') {
+            Some(percent) => percent.parse::<f64>().ok()? * 2.55,
+            None => part.parse::<f64>().ok()?,
+         };
+         Some(value.round().clamp(0., 255.) as u8)
+      };
+
+      if parts.len() != 3 && parts.len() != 4 {
+         return None;
+      }
+
+      let alpha = match parts.get(3) {
+         Some(part) => parse_opacity(part)?,
+         None => 1.,
+      };
+
+      return Some(RGBA::new(
+         channel(parts[0])?,
+         channel(parts[1])?,
+         channel(parts[2])?,
+         (alpha * 255.).round() as u8
+      ));
+   }
+
+   if lower == "transparent" {
+      return Some(RGBA::new(0, 0, 0, 0));
+   }
+
+   NAMED_COLORS.iter()
+      .find(|named| named.0 == lower)
+      .map(|named| RGBA::new((named.1 >> 16) as u8, (named.1 >> 8) as u8, named.1 as u8, 255))
+}
+
+/// Opacity as a number or percentage, clamped to `[0, 1]`.
+fn parse_opacity(value: &str) -> Option<f64> {
+   let opacity = match value.strip_suffix('%') {
+      Some(percent) => percent.parse::<f64>().ok()? / 100.,
+      None => value.parse::<f64>().ok()?,
+   };
+
+   if opacity.is_nan() {
+      None
+   } else {
+      Some(opacity.clamp(0., 1.))
+   }
+}
+
+/// Length in user units. Percentages and font relative units are not
+/// supported.
+fn length(value: &str) -> Option<f64> {
+   let value = value.trim();
+   let number = value.trim_end_matches(|ch: char| ch.is_ascii_alphabetic());
+
+   let unit = match &value[number.len()..] {
+      "" | "px" => 1.,
+      "pt" => 96. / 72.,
+      "pc" => 16.,
+      "mm" => 96. / 25.4,
+      "cm" => 96. / 2.54,
+      "in" => 96.,
+      _ => return None,
+   };
+
+   match number.parse::<f64>() {
+      Ok(length) if length.is_finite() => Some(length * unit),
+      _ => None,
+   }
+}
+
+/// Numbers separated by whitespace or commas, as in `points` and `viewBox`.
+fn numbers(value: &str) -> Result<Vec<f64>, RuntimeError> {
+   let mut scanner = Scanner::new(value);
+   let mut numbers = Vec::new();
+
+   loop {
+      scanner.skip_separators();
+
+      if scanner.at_end() {
+         return Ok(numbers);
+      }
+
+      match scanner.number() {
+         Ok(number) => numbers.push(number),
+         Err(_) => return Err(invalid_svg(format!("Invalid number list {}", value))),
+      }
+   }
+}
+
+/// Element name without its namespace prefix.
+#[inline]
+fn local_name(name: &str) -> &str {
+   name.rsplit(':').next().unwrap_or(name)
+}
+
+#[inline]
+fn invalid_svg(description: String) -> RuntimeError {
+   RuntimeError::new(ErrorKind::InvalidInput, description)
+}
+
+#[cfg(test)]
+mod tests {
+   use draw::{RGB, RGBA};
+   use frame::Frame;
+   use geom::affine::Affine;
+
+   use super::{Document, parse_color, parse_transform, length};
+
+   const TEST_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="20px" height="20" viewBox="0 0 40 40">
+   <rect width="40" height="40" fill="#00f"/>
+   <g transform="translate(20 0)" style="fill: rgb(255, 0, 0)" opacity="0.5">
+      <rect width="10" height="10" transform="scale(2)"/>
+   </g>
+   <circle cx="10" cy="30" r="6" fill="none" stroke="lime" stroke-width="4"/>
+   <polygon points="20,20 40,20 40,40" fill="white"/>
+   <path d="M0 0H40V40H0Z" fill="white" style="display: none"/>
+</svg>"##;
+
+   #[test]
+   fn test_render_document() {
+      let document = Document::parse(TEST_SVG).unwrap();
+
+      assert_eq!((document.width, document.height), (20., 20.));
+      assert_eq!(document.view_box, [0., 0., 40., 40.]);
+      assert_eq!(document.shapes.len(), 4);
+      assert_eq!(document.shapes[2].fill, None);
+      assert_eq!(document.shapes[2].stroke_width, 4.);
+
+      let mut frame = Frame::new(20, 20);
+      document.render(&mut frame, 1000).unwrap();
+
+      let blue = RGB::new(0, 0, 255);
+
+      // Frame rows count up from the bottom of the picture
+      assert_eq!(frame.get_pixel(0, 19), blue);
+      assert_eq!(frame.get_pixel(15, 15), RGB::new(128, 0, 127));
+      assert_eq!(frame.get_pixel(2, 4), RGB::new(0, 255, 0));
+      assert_eq!(frame.get_pixel(4, 4), blue);
+      assert_eq!(frame.get_pixel(18, 7), RGB::new(255, 255, 255));
+      assert_eq!(frame.get_pixel(11, 1), blue);
+
+      assert!(Document::parse("<html/>").is_err());
+      assert!(Document::parse(r#"<svg viewBox="0 0 10"/>"#).is_err());
+      assert!(Document::parse(r#"<svg><g transform="spin(3)"/></svg>"#).is_err());
+   }
+
+   #[test]
+   fn test_render_skewed_path() {
+      let document = Document::parse(r#"<svg width="40" height="20">
+         <rect x="5" y="5" width="10" height="10" transform="skewX(45)" fill="red"/>
+      </svg>"#).unwrap();
+
+      let red = RGB::new(255, 0, 0);
+      let black = RGB::new(0, 0, 0);
+
+      for &div_per_pixel in &[1000, 100, 7] {
+         let mut frame = Frame::new(40, 20);
+         document.render(&mut frame, div_per_pixel).unwrap();
+
+         // Rows of the rectangle shift right by their distance from the top
+         let pixel = |x: i32, y: i32| frame.get_pixel(x, 19 - y);
+
+         assert_eq!(pixel(12, 6), red);
+         assert_eq!(pixel(25, 13), red);
+         assert_eq!(pixel(10, 13), black);
+         assert_eq!(pixel(25, 6), black);
+         assert_eq!(pixel(20, 3), black);
+         assert_eq!(pixel(20, 16), black);
+
+         let count = (0..40).map(|x| (0..20).filter(|&y| pixel(x, y) != black).count()).sum::<usize>();
+         assert!((100..=120).contains(&count));
+      }
+
+      // Every path command under a skewing matrix renders alike at any resolution
+      let document = Document::parse(r#"<svg><g transform="matrix(1 0 3 1 5 5) scale(2)">
+         <path d="M10,10 l5-5 h3 v-2 C1 2 3 4 5 6 s1 1 2 2 Q1 1 2 2 t3 3 A5 5 30 1 1 20 20 a3 4 0 0 0 1 1 z" fill="red"/>
+      </g></svg>"#).unwrap();
+
+      let renders: Vec<Vec<RGB>> = [1000, 100, 7].iter().map(|&div_per_pixel| {
+         let mut frame = Frame::new(300, 150);
+         document.render(&mut frame, div_per_pixel).unwrap();
+
+         (0..150).flat_map(|y| (0..300).map(move |x| (x, y))).map(|(x, y)| frame.get_pixel(x, y)).collect()
+      }).collect();
+
+      // Painted area in pixels, from the coverage of the red channel
+      let areas: Vec<f64> = renders.iter().map(|render| {
+         render.iter().map(|color| color.r as f64 / 255.).sum::<f64>()
+      }).collect();
+
+      assert!(renders.iter().all(|render| render.iter().all(|color| color.g == 0 && color.b == 0)));
+      assert!(areas[0] > 500., "{:?}", areas);
+
+      // Seven divisions per pixel round the outline coarsely
+      assert!((areas[1] - areas[0]).abs() < areas[0] * 0.02, "{:?}", areas);
+      assert!((areas[2] - areas[0]).abs() < areas[0] * 0.1, "{:?}", areas);
+   }
+
+   #[test]
+   fn test_nested_svg() {
+      let document = Document::parse(r#"<svg width="40" height="20">
+         <svg x="20" y="0" width="20" height="20" viewBox="0 0 10 5">
+            <rect width="10" height="5" fill="red"/>
+         </svg>
+         <svg x="2" y="2"><rect width="4" height="4" fill="lime"/></svg>
+         <svg width="0" viewBox="0 0 1 1"><rect width="40" height="20" fill="white"/></svg>
+      </svg>"#).unwrap();
+
+      assert_eq!(document.view_box, [0., 0., 40., 20.]);
+      assert_eq!(document.shapes.len(), 2);
+
+      let mut frame = Frame::new(40, 20);
+      document.render(&mut frame, 100).unwrap();
+
+      let pixel = |x: i32, y: i32| frame.get_pixel(x, 19 - y);
+
+      // The 2:1 view box fills a band 10 units high centered in the viewport
+      assert_eq!(pixel(30, 10), RGB::new(255, 0, 0));
+      assert_eq!(pixel(21, 6), RGB::new(255, 0, 0));
+      assert_eq!(pixel(30, 3), RGB::new(0, 0, 0));
+      assert_eq!(pixel(30, 16), RGB::new(0, 0, 0));
+
+      assert_eq!(pixel(3, 3), RGB::new(0, 255, 0));
+      assert_eq!(pixel(1, 1), RGB::new(0, 0, 0));
+      assert_eq!(pixel(10, 10), RGB::new(0, 0, 0));
+   }
+
+   #[test]
+   fn test_parse_color() {
+      assert_eq!(parse_color("#f80"), Some(RGBA::new(255, 136, 0, 255)));
+      assert_eq!(parse_color("#f808"), Some(RGBA::new(255, 136, 0, 136)));
+      assert_eq!(parse_color("#1a2B3c"), Some(RGBA::new(0x1a, 0x2b, 0x3c, 255)));
+      assert_eq!(parse_color("#11223344"), Some(RGBA::new(0x11, 0x22, 0x33, 0x44)));
+      assert_eq!(parse_color("rgb(10, 20, 30)"), Some(RGBA::new(10, 20, 30, 255)));
+      assert_eq!(parse_color("RGBA(100%, 0%, 20%, 0.5)"), Some(RGBA::new(255, 0, 51, 128)));
+      assert_eq!(parse_color("rgb(10 20 300 / 25%)"), Some(RGBA::new(10, 20, 255, 64)));
+      assert_eq!(parse_color("Teal"), Some(RGBA::new(0, 128, 128, 255)));
+      assert_eq!(parse_color("transparent"), Some(RGBA::new(0, 0, 0, 0)));
+
+      assert_eq!(parse_color("#12345"), None);
+      assert_eq!(parse_color("#ggg"), None);
+      assert_eq!(parse_color("rgb(1, 2)"), None);
+      assert_eq!(parse_color("rgb(1, 2, 3"), None);
+      assert_eq!(parse_color("rgb(1, x, 3)"), None);
+      assert_eq!(parse_color("ultraviolet"), None);
+   }
+
+   #[test]
+   fn test_parse_transform() {
+      let close = |transform: &Affine, x: f64, y: f64, expected: (f64, f64)| {
+         let (x, y) = transform.apply(x, y);
+         assert!((x - expected.0).abs() < 1e-9 && (y - expected.1).abs() < 1e-9, "{} {}", x, y);
+      };
+
+      // Rotation about (10, 5) keeps that point in place
+      let rotate = parse_transform("rotate(90 10 5)").unwrap();
+      close(&rotate, 10., 5., (10., 5.));
+      close(&rotate, 12., 5., (10., 7.));
+      close(&rotate, 10., 6., (9., 5.));
+
+      let skew_x = parse_transform("skewX(45)").unwrap();
+      close(&skew_x, 0., 2., (2., 2.));
+      close(&skew_x, 3., 0., (3., 0.));
+
+      let skew_y = parse_transform("skewY(-45)").unwrap();
+      close(&skew_y, 2., 0., (2., -2.));
+      close(&skew_y, 0., 3., (0., 3.));
+
+      // The last function applies first
+      let list = parse_transform(" translate(10) , scale(2 3)").unwrap();
+      close(&list, 1., 1., (12., 3.));
+
+      assert!(parse_transform("rotate(1 2)").is_err());
+      assert!(parse_transform("skewX(1").is_err());
+   }
+
+   #[test]
+   fn test_length() {
+      assert_eq!(length("12"), Some(12.));
+      assert_eq!(length(" 5px "), Some(5.));
+      assert_eq!(length("2in"), Some(192.));
+      assert_eq!(length("72pt"), Some(96.));
+      assert_eq!(length("1pc"), Some(16.));
+      assert_eq!(length("2.54cm"), Some(96.));
+      assert_eq!(length("-1e1mm").map(|length| length.round()), Some(-38.));
+
+      assert_eq!(length("10%"), None);
+      assert_eq!(length("2em"), None);
+      assert_eq!(length("px"), None);
+      assert_eq!(length("inf"), None);
+   }
+
+   #[test]
+   fn test_fit() {
+      let document = Document::parse(r#"<svg viewBox="10 20 40 20"/>"#).unwrap();
+      assert_eq!((document.width, document.height), (40., 20.));
+
+      // Scaled by 2.5 to the full width and centered vertically, y up
+      let transform = document.fit(0., 0., 100., 100.);
+      assert_eq!(transform.apply(10., 20.), (0., 75.));
+      assert_eq!(transform.apply(50., 40.), (100., 25.));
+
+      // Scaled by 1 to the full height and centered horizontally
+      let transform = document.fit(5., 10., 100., 20.);
+      assert_eq!(transform.apply(10., 20.), (35., 30.));
+      assert_eq!(transform.apply(50., 40.), (75., 10.));
+   }
+}
@@ -0,0 +1,7 @@
+pub mod path;
+pub mod xml;
+pub mod document;
+
+pub use self::path::{PathCommand, parse_path, build_path, path_from_data};
+pub use self::xml::{Element, parse_xml};
+pub use self::document::{Document, Shape};
@@ -0,0 +1,530 @@
+use std::f64::consts::{PI, FRAC_PI_2};
+
+use error::{RuntimeError, ErrorKind};
+use geom::affine::Affine;
+use raster::Path;
+
+
+/// Path data command with absolute coordinates. Shorthand commands are
+/// expanded, arcs keep their SVG endpoint parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand {
+   MoveTo(f64, f64),
+   LineTo(f64, f64),
+   QuadTo(f64, f64, f64, f64),
+   CubicTo(f64, f64, f64, f64, f64, f64),
+   ArcTo {
+      rx: f64,
+      ry: f64,
+      rotation: f64,
+      large_arc: bool,
+      sweep: bool,
+      x: f64,
+      y: f64,
+   },
+   Close,
+}
+
+/// Parses the `d` attribute of an SVG path.
+pub fn parse_path(data: &str) -> Result<Vec<PathCommand>, RuntimeError> {
+   let mut scanner = Scanner::new(data);
+   let mut commands = Vec::new();
+
+   let (mut x, mut y) = (0., 0.);
+   let (mut start_x, mut start_y) = (0., 0.);
+
+   // Second control point of the previous curve for the smooth shorthands
+   let mut last_cubic: Option<(f64, f64)> = None;
+   let mut last_quad: Option<(f64, f64)> = None;
+
+   let mut command = None;
+
+   loop {
+      scanner.skip_separators();
+
+      if scanner.at_end() {
+         break;
+      }
+
+      let name = match scanner.command() {
+         Some(name) => name,
+         None => match command {
+            // Coordinates after a move are implicit lines
+            Some('M') => 'L',
+            Some('m') => 'l',
+            Some(name) if name != 'Z' && name != 'z' => name,
+            _ => return Err(scanner.error()),
+         },
+      };
+
+      command = Some(name);
+
+      let relative = name.is_ascii_lowercase();
+      let (dx, dy) = if relative { (x, y) } else { (0., 0.) };
+
+      let mut cubic = None;
+      let mut quad = None;
+
+      match name.to_ascii_uppercase() {
+         'M' => {
+            x = dx + scanner.number()?;
+            y = dy + scanner.number()?;
+            start_x = x;
+            start_y = y;
+            commands.push(PathCommand::MoveTo(x, y));
+         },
+         'L' => {
+            x = dx + scanner.number()?;
+            y = dy + scanner.number()?;
+            commands.push(PathCommand::LineTo(x, y));
+         },
+         'H' => {
+            x = dx + scanner.number()?;
+            commands.push(PathCommand::LineTo(x, y));
+         },
+         'V' => {
+            y = dy + scanner.number()?;
+            commands.push(PathCommand::LineTo(x, y));
+         },
+         'C' | 'S' => {
+            let (c1x, c1y) = if name == 'C' || name == 'c' {
+               (dx + scanner.number()?, dy + scanner.number()?)
+            } else {
+               reflect(last_cubic, x, y)
+            };
+
+            let c2x = dx + scanner.number()?;
+            let c2y = dy + scanner.number()?;
+            x = dx + scanner.number()?;
+            y = dy + scanner.number()?;
+
+            commands.push(PathCommand::CubicTo(c1x, c1y, c2x, c2y, x, y));
+            cubic = Some((c2x, c2y));
+         },
+         'Q' | 'T' => {
+            let (cx, cy) = if name == 'Q' || name == 'q' {
+               (dx + scanner.number()?, dy + scanner.number()?)
+            } else {
+               reflect(last_quad, x, y)
+            };
+
+            x = dx + scanner.number()?;
+            y = dy + scanner.number()?;
+
+            commands.push(PathCommand::QuadTo(cx, cy, x, y));
+            quad = Some((cx, cy));
+         },
+         'A' => {
+            let rx = scanner.number()?.abs();
+            let ry = scanner.number()?.abs();
+            let rotation = scanner.number()?;
+            let large_arc = scanner.flag()?;
+            let sweep = scanner.flag()?;
+            x = dx + scanner.number()?;
+            y = dy + scanner.number()?;
+
+            commands.push(PathCommand::ArcTo { rx, ry, rotation, large_arc, sweep, x, y });
+         },
+         'Z' => {
+            x = start_x;
+            y = start_y;
+            commands.push(PathCommand::Close);
+         },
+         _ => return Err(scanner.error()),
+      }
+
+      last_cubic = cubic;
+      last_quad = quad;
+   }
+
+   match commands.first() {
+      None | Some(&PathCommand::MoveTo(..)) => Ok(commands),
+      _ => Err(RuntimeError::new(ErrorKind::InvalidInput, "Path data must start with a move".to_string())),
+   }
+}
+
+/// Parses path data into a path mapped through `transform`, which usually
+/// flips y and scales SVG user units to world units.
+pub fn path_from_data(data: &str, transform: &Affine) -> Result<Path, RuntimeError> {
+   Ok(build_path(&parse_path(data)?, transform))
+}
+
+/// Path of the commands mapped through `transform`, rounded to the integer
+/// grid. Arcs with equal radii become circular arcs when the transform
+/// keeps circles circular, other arcs are approximated with cubic curves.
+pub fn build_path(commands: &[PathCommand], transform: &Affine) -> Path {
+   let mut path = Path::new();
+
+   let point = |x: f64, y: f64| {
+      let (x, y) = transform.apply(x, y);
+      (x.round() as i64, y.round() as i64)
+   };
+
+   let (mut x, mut y) = (0., 0.);
+   let (mut start_x, mut start_y) = (0., 0.);
+
+   for command in commands {
+      match *command {
+         PathCommand::MoveTo(to_x, to_y) => {
+            let (px, py) = point(to_x, to_y);
+            path.move_to(px, py);
+
+            x = to_x;
+            y = to_y;
+            start_x = x;
+            start_y = y;
+         },
+         PathCommand::LineTo(to_x, to_y) => {
+            let (px, py) = point(to_x, to_y);
+            path.line_to(px, py);
+
+            x = to_x;
+            y = to_y;
+         },
+         PathCommand::QuadTo(cx, cy, to_x, to_y) => {
+            let (cx, cy) = point(cx, cy);
+            let (px, py) = point(to_x, to_y);
+            path.quad_to(cx, cy, px, py);
+
+            x = to_x;
+            y = to_y;
+         },
+         PathCommand::CubicTo(c1x, c1y, c2x, c2y, to_x, to_y) => {
+            let (c1x, c1y) = point(c1x, c1y);
+            let (c2x, c2y) = point(c2x, c2y);
+            let (px, py) = point(to_x, to_y);
+            path.cubic_to(c1x, c1y, c2x, c2y, px, py);
+
+            x = to_x;
+            y = to_y;
+         },
+         PathCommand::ArcTo { rx, ry, rotation, large_arc, sweep, x: to_x, y: to_y } => {
+            add_arc(&mut path, transform, (x, y), (rx, ry), rotation, large_arc, sweep, (to_x, to_y));
+
+            x = to_x;
+            y = to_y;
+         },
+         PathCommand::Close => {
+            path.close();
+
+            x = start_x;
+            y = start_y;
+         },
+      }
+   }
+
+   path
+}
+
+#[inline]
+fn reflect(control: Option<(f64, f64)>, x: f64, y: f64) -> (f64, f64) {
+   match control {
+      Some((cx, cy)) => (2. * x - cx, 2. * y - cy),
+      None => (x, y),
+   }
+}
+
+/// Appends an SVG arc after converting it from endpoint to center
+/// parameterization.
+#[allow(clippy::too_many_arguments)]
+fn add_arc(
+   path: &mut Path, transform: &Affine, from: (f64, f64), radii: (f64, f64), rotation: f64,
+   large_arc: bool, sweep: bool, to: (f64, f64)
+) {
+   let point = |x: f64, y: f64| {
+      let (x, y) = transform.apply(x, y);
+      (x.round() as i64, y.round() as i64)
+   };
+
+   if from == to {
+      return;
+   }
+
+   let (mut rx, mut ry) = radii;
+   if rx == 0. || ry == 0. {
+      let (px, py) = point(to.0, to.1);
+      path.line_to(px, py);
+      return;
+   }
+
+   let (sin, cos) = (rotation * PI / 180.).sin_cos();
+
+   let hx = (from.0 - to.0) / 2.;
+   let hy = (from.1 - to.1) / 2.;
+   let x1 = cos * hx + sin * hy;
+   let y1 = -sin * hx + cos * hy;
+
+   // Radii too small to reach the end point are scaled up
+   let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+   if lambda > 1. {
+      rx *= lambda.sqrt();
+      ry *= lambda.sqrt();
+   }
+
+   let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+   let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+
+   let mut coef = (numerator / denominator).max(0.).sqrt();
+   if large_arc == sweep {
+      coef = -coef;
+   }
+
+   let cx1 = coef * rx * y1 / ry;
+   let cy1 = -coef * ry * x1 / rx;
+
+   let cx = cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.;
+   let cy = sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.;
+
+   if rx == ry && transform.is_similarity() {
+      // Sweeping in the positive angle direction is anticlockwise unless
+      // the transform mirrors
+      let clockwise = sweep == (transform.determinant() < 0.);
+
+      let (px, py) = point(to.0, to.1);
+      let (pcx, pcy) = point(cx, cy);
+      path.arc_to(px, py, pcx, pcy, clockwise);
+      return;
+   }
+
+   let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+   let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+
+   let mut delta = end - start;
+   if sweep && delta < 0. {
+      delta += 2. * PI;
+   } else if !sweep && delta > 0. {
+      delta -= 2. * PI;
+   }
+
+   let segments = (delta.abs() / FRAC_PI_2 - 1e-9).ceil().max(1.) as usize;
+   let step = delta / segments as f64;
+   let k = 4. / 3. * (step / 4.).tan();
+
+   let ellipse = |angle: f64| {
+      let (sin_a, cos_a) = angle.sin_cos();
+      let (ex, ey) = (rx * cos_a, ry * sin_a);
+      let (dx, dy) = (-rx * sin_a, ry * cos_a);
+
+      (
+         (cx + cos * ex - sin * ey, cy + sin * ex + cos * ey),
+         (cos * dx - sin * dy, sin * dx + cos * dy),
+      )
+   };
+
+   for i in 0..segments {
+      let a0 = start + i as f64 * step;
+      let a1 = a0 + step;
+
+      let (p0, d0) = ellipse(a0);
+      let (p1, d1) = ellipse(a1);
+
+      let p1 = if i + 1 == segments { to } else { p1 };
+
+      let (c1x, c1y) = point(p0.0 + k * d0.0, p0.1 + k * d0.1);
+      let (c2x, c2y) = point(p1.0 - k * d1.0, p1.1 - k * d1.1);
+      let (px, py) = point(p1.0, p1.1);
+
+      path.cubic_to(c1x, c1y, c2x, c2y, px, py);
+   }
+}
+
+/// Tokenizer for the path data grammar, where separators are optional
+/// whenever the next token is unambiguous. Number lists in other attributes
+/// follow the same rules.
+pub struct Scanner<'a> {
+   data: &'a [u8],
+   pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+   #[inline]
+   pub fn new(data: &'a str) -> Self {
+      Scanner {
+         data: data.as_bytes(),
+         pos: 0,
+      }
+   }
+
+   #[inline]
+   pub fn at_end(&self) -> bool {
+      self.pos >= self.data.len()
+   }
+
+   #[inline]
+   fn peek(&self) -> Option<u8> {
+      self.data.get(self.pos).cloned()
+   }
+
+   pub fn skip_separators(&mut self) {
+      while let Some(byte) = self.peek() {
+         if byte.is_ascii_whitespace() || byte == b',' {
+            self.pos += 1;
+         } else {
+            break;
+         }
+      }
+   }
+
+   fn command(&mut self) -> Option<char> {
+      match self.peek() {
+         Some(byte) if b"MmLlHhVvCcSsQqTtAaZz".contains(&byte) => {
+            self.pos += 1;
+            Some(byte as char)
+         },
+         _ => None,
+      }
+   }
+
+   pub fn number(&mut self) -> Result<f64, RuntimeError> {
+      self.skip_separators();
+
+      let start = self.pos;
+
+      if let Some(b'+') | Some(b'-') = self.peek() {
+         self.pos += 1;
+      }
+
+      let integer = self.digits();
+
+      let mut fraction = 0;
+      if self.peek() == Some(b'.') {
+         self.pos += 1;
+         fraction = self.digits();
+      }
+
+      if integer == 0 && fraction == 0 {
+         self.pos = start;
+         return Err(self.error());
+      }
+
+      // An exponent needs digits, otherwise the 'e' is left alone
+      if let Some(b'e') | Some(b'E') = self.peek() {
+         let mantissa_end = self.pos;
+         self.pos += 1;
+
+         if let Some(b'+') | Some(b'-') = self.peek() {
+            self.pos += 1;
+         }
+
+         if self.digits() == 0 {
+            self.pos = mantissa_end;
+         }
+      }
+
+      let text = String::from_utf8_lossy(&self.data[start..self.pos]);
+      text.parse().map_err(|_| self.error())
+   }
+
+   /// Arc flag, a single digit that needs no separator after it.
+   pub fn flag(&mut self) -> Result<bool, RuntimeError> {
+      self.skip_separators();
+
+      match self.peek() {
+         Some(b'0') => {
+            self.pos += 1;
+            Ok(false)
+         },
+         Some(b'1') => {
+            self.pos += 1;
+            Ok(true)
+         },
+         _ => Err(self.error()),
+      }
+   }
+
+   fn digits(&mut self) -> usize {
+      let start = self.pos;
+      while let Some(b'0'..=b'9') = self.peek() {
+         self.pos += 1;
+      }
+      self.pos - start
+   }
+
+   pub fn error(&self) -> RuntimeError {
+      RuntimeError::new(
+         ErrorKind::InvalidInput,
+         format!("Invalid path data at offset {}", self.pos)
+      )
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use geom::affine::Affine;
+   use geom::point::Point;
+   use draw::RGBA;
+   use frame::Frame;
+   use raster::{SceneCompiler, Rasterizer};
+   use raster::path::PathEdge;
+
+   use super::{PathCommand, parse_path, path_from_data};
+
+   #[test]
+   fn test_parse_path_data() {
+      let commands = parse_path("M1-2.5.5.5l1e1,0h-1V3c1 1 2 2 3 3s4 4 5 5Q0 0 1 1t2 2a1 1 0 01-2 2z m1 1 2 2").unwrap();
+
+      assert_eq!(commands, vec![
+         PathCommand::MoveTo(1., -2.5),
+         PathCommand::LineTo(0.5, 0.5),
+         PathCommand::LineTo(10.5, 0.5),
+         PathCommand::LineTo(9.5, 0.5),
+         PathCommand::LineTo(9.5, 3.),
+         PathCommand::CubicTo(10.5, 4., 11.5, 5., 12.5, 6.),
+         PathCommand::CubicTo(13.5, 7., 16.5, 10., 17.5, 11.),
+         PathCommand::QuadTo(0., 0., 1., 1.),
+         PathCommand::QuadTo(2., 2., 3., 3.),
+         PathCommand::ArcTo { rx: 1., ry: 1., rotation: 0., large_arc: false, sweep: true, x: 1., y: 5. },
+         PathCommand::Close,
+         PathCommand::MoveTo(2., -1.5),
+         PathCommand::LineTo(4., 0.5),
+      ]);
+
+      assert!(parse_path("L1 1").is_err());
+      assert!(parse_path("M1 1 L2").is_err());
+      assert!(parse_path("M1 1 Z 2 2").is_err());
+   }
+
+   #[test]
+   fn test_path_data_arcs() {
+      // Quarter circle around the origin, anticlockwise in SVG coordinates
+      let path = path_from_data("M10 0A10 10 0 0 1 0 10", &Affine::scale(100., 100.)).unwrap();
+      assert_eq!(path.contours[0].edges[0], PathEdge::Arc {
+         end: Point::new(0, 1000),
+         center: Point::new(0, 0),
+         radius: 1000,
+         clockwise: false,
+      });
+
+      // Flipping y turns it clockwise
+      let path = path_from_data("M10 0A10 10 0 0 1 0 10", &Affine::scale(100., -100.)).unwrap();
+      match path.contours[0].edges[0] {
+         PathEdge::Arc { clockwise, .. } => assert!(clockwise),
+         ref edge => panic!("Unexpected edge {:?}", edge),
+      }
+
+      // Unequal radii give cubic curves, a half ellipse needs two
+      let path = path_from_data("M0 0A20 10 0 0 0 40 0", &Affine::identity()).unwrap();
+      assert_eq!(path.contours[0].edges.len(), 2);
+      match path.contours[0].edges[0] {
+         PathEdge::Cubic { end, .. } => assert_eq!(end, Point::new(20, 10)),
+         ref edge => panic!("Unexpected edge {:?}", edge),
+      }
+   }
+
+   #[test]
+   fn test_path_data_scene() {
+      // Square with a round hole, y flipped into a 10x10 frame
+      let transform = Affine::scale(1000., -1000.).then(&Affine::translate(0., 10000.));
+      let path = path_from_data("M1 1H9V9H1Z M5 3a2 2 0 1 0 0.001 0", &transform).unwrap();
+
+      let mut compiler = SceneCompiler::new();
+      compiler.add_path(&path, RGBA::new(255, 255, 255, 255));
+
+      let mut frame = Frame::new(10, 10);
+      Rasterizer::new().render(&compiler.compile().unwrap(), &mut frame, 1000).unwrap();
+
+      assert_eq!(frame.get_pixel(1, 1).r, 255);
+      assert_eq!(frame.get_pixel(0, 0).r, 0);
+      assert_eq!(frame.get_pixel(4, 4).r, 0);
+   }
+}
@@ -0,0 +1,316 @@
+use error::{RuntimeError, ErrorKind};
+
+
+/// Element of an XML document with its attributes in document order. Text
+/// content is dropped since SVG shapes are described by attributes alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+   pub name: String,
+   pub attributes: Vec<(String, String)>,
+   pub children: Vec<Element>,
+}
+
+impl Element {
+   #[inline]
+   pub fn new(name: &str) -> Self {
+      Element {
+         name: name.to_string(),
+         attributes: Vec::new(),
+         children: Vec::new(),
+      }
+   }
+
+   pub fn attribute(&self, name: &str) -> Option<&str> {
+      self.attributes.iter()
+         .find(|attribute| attribute.0 == name)
+         .map(|attribute| attribute.1.as_str())
+   }
+}
+
+/// Parses the root element of an XML document. The prolog, comments,
+/// processing instructions, CDATA sections and the DOCTYPE are skipped;
+/// namespace prefixes are kept in the names.
+pub fn parse_xml(text: &str) -> Result<Element, RuntimeError> {
+   let mut parser = Parser {
+      text,
+      pos: 0,
+   };
+
+   let mut stack: Vec<Element> = Vec::new();
+
+   loop {
+      parser.skip_text();
+
+      if parser.at_end() {
+         return Err(parser.error("Unexpected end of XML document"));
+      }
+
+      if parser.skip_markup()? {
+         continue;
+      }
+
+      if parser.eat("</") {
+         let name = parser.name()?;
+         parser.skip_whitespace();
+
+         if !parser.eat(">") {
+            return Err(parser.error("Expected '>'"));
+         }
+
+         let element = match stack.pop() {
+            Some(element) if element.name == name => element,
+            _ => return Err(parser.error("Mismatched closing tag")),
+         };
+
+         match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => return Ok(element),
+         }
+
+         continue;
+      }
+
+      parser.pos += 1;
+
+      let (element, empty) = parser.start_tag()?;
+
+      if !empty {
+         stack.push(element);
+         continue;
+      }
+
+      match stack.last_mut() {
+         Some(parent) => parent.children.push(element),
+         None => return Ok(element),
+      }
+   }
+}
+
+struct Parser<'a> {
+   text: &'a str,
+   pos: usize,
+}
+
+impl<'a> Parser<'a> {
+   #[inline]
+   fn at_end(&self) -> bool {
+      self.pos >= self.text.len()
+   }
+
+   #[inline]
+   fn rest(&self) -> &'a str {
+      &self.text[self.pos..]
+   }
+
+   fn eat(&mut self, token: &str) -> bool {
+      if self.rest().starts_with(token) {
+         self.pos += token.len();
+         true
+      } else {
+         false
+      }
+   }
+
+   fn skip_until(&mut self, token: &str) -> Result<(), RuntimeError> {
+      match self.rest().find(token) {
+         Some(index) => {
+            self.pos += index + token.len();
+            Ok(())
+         },
+         None => Err(self.error("Unterminated markup")),
+      }
+   }
+
+   fn skip_text(&mut self) {
+      match self.rest().find('<') {
+         Some(index) => self.pos += index,
+         None => self.pos = self.text.len(),
+      }
+   }
+
+   fn skip_whitespace(&mut self) {
+      let rest = self.rest();
+      self.pos += rest.len() - rest.trim_start().len();
+   }
+
+   /// Skips markup that is not an element, returning whether there was any.
+   fn skip_markup(&mut self) -> Result<bool, RuntimeError> {
+      if self.eat("<!--") {
+         self.skip_until("-->")?;
+      } else if self.eat("<![CDATA[") {
+         self.skip_until("]]>")?;
+      } else if self.eat("<?") {
+         self.skip_until("?>")?;
+      } else if self.eat("<!") {
+         self.skip_doctype()?;
+      } else {
+         return Ok(false);
+      }
+
+      Ok(true)
+   }
+
+   /// Skips a DOCTYPE, which may hold an internal subset in brackets.
+   fn skip_doctype(&mut self) -> Result<(), RuntimeError> {
+      let mut depth = 0;
+
+      for (index, ch) in self.rest().char_indices() {
+         match ch {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '>' if depth <= 0 => {
+               self.pos += index + 1;
+               return Ok(());
+            },
+            _ => {},
+         }
+      }
+
+      Err(self.error("Unterminated DOCTYPE"))
+   }
+
+   fn name(&mut self) -> Result<String, RuntimeError> {
+      let rest = self.rest();
+
+      let len = rest
+         .find(|ch: char| ch.is_whitespace() || "/>=\"'".contains(ch))
+         .unwrap_or(rest.len());
+
+      if len == 0 {
+         return Err(self.error("Expected a name"));
+      }
+
+      self.pos += len;
+
+      Ok(rest[..len].to_string())
+   }
+
+   /// Reads the tag after its '<', returning the element and whether the
+   /// tag was self-closing.
+   fn start_tag(&mut self) -> Result<(Element, bool), RuntimeError> {
+      let mut element = Element::new(&self.name()?);
+
+      loop {
+         self.skip_whitespace();
+
+         if self.eat("/>") {
+            return Ok((element, true));
+         }
+
+         if self.eat(">") {
+            return Ok((element, false));
+         }
+
+         let name = self.name()?;
+         self.skip_whitespace();
+
+         if !self.eat("=") {
+            return Err(self.error("Expected '=' after attribute name"));
+         }
+
+         self.skip_whitespace();
+
+         let quote = match self.rest().chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => return Err(self.error("Expected a quoted attribute value")),
+         };
+
+         self.pos += 1;
+
+         let len = match self.rest().find(quote) {
+            Some(len) => len,
+            None => return Err(self.error("Unterminated attribute value")),
+         };
+
+         let value = unescape(&self.rest()[..len]);
+         self.pos += len + 1;
+
+         element.attributes.push((name, value));
+      }
+   }
+
+   fn error(&self, message: &str) -> RuntimeError {
+      RuntimeError::new(
+         ErrorKind::InvalidInput,
+         format!("{} at offset {}", message, self.pos)
+      )
+   }
+}
+
+/// Replaces the predefined entities and character references. Unknown
+/// entities are left as they are.
+fn unescape(value: &str) -> String {
+   if !value.contains('&') {
+      return value.to_string();
+   }
+
+   let mut result = String::with_capacity(value.len());
+   let mut rest = value;
+
+   while let Some(start) = rest.find('&') {
+      result.push_str(&rest[..start]);
+      rest = &rest[start..];
+
+      let end = match rest.find(';') {
+         Some(end) => end,
+         None => break,
+      };
+
+      let entity = &rest[1..end];
+
+      let ch = match entity {
+         "lt" => Some('<'),
+         "gt" => Some('>'),
+         "amp" => Some('&'),
+         "quot" => Some('"'),
+         "apos" => Some('\''),
+         _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+         _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+         _ => None,
+      };
+
+      match ch {
+         Some(ch) => {
+            result.push(ch);
+            rest = &rest[end + 1..];
+         },
+         None => {
+            result.push('&');
+            rest = &rest[1..];
+         },
+      }
+   }
+
+   result.push_str(rest);
+   result
+}
+
+#[cfg(test)]
+mod tests {
+   use super::{Element, parse_xml};
+
+   #[test]
+   fn test_parse_xml() {
+      let root = parse_xml(r#"<?xml version="1.0"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" [ <!ENTITY x "y"> ]>
+<!-- comment with <tags> -->
+<svg xmlns="http://www.w3.org/2000/svg" width = '10'>
+   <title>Text &amp; <![CDATA[ <ignored/> ]]></title>
+   <g id="a&amp;b&#x41;&#66;&nbsp;"><rect/></g>
+</svg>"#).unwrap();
+
+      assert_eq!(root.name, "svg");
+      assert_eq!(root.attribute("width"), Some("10"));
+      assert_eq!(root.attribute("height"), None);
+      assert_eq!(root.children.len(), 2);
+      assert_eq!(root.children[0].children, vec![]);
+
+      let group = &root.children[1];
+      assert_eq!(group.attribute("id"), Some("a&bAB&nbsp;"));
+      assert_eq!(group.children, vec![Element::new("rect")]);
+
+      assert!(parse_xml("<svg><g></svg>").is_err());
+      assert!(parse_xml("<svg>").is_err());
+      assert!(parse_xml("<svg width=10/>").is_err());
+   }
+}
@@ -297,6 +297,13 @@ impl Buffer {
       };
    }
 
+   #[inline]
+   pub fn map_read_write(&mut self) {
+      self.ptr = unsafe {
+         ffi::glMapBuffer(ffi::GL_PIXEL_UNPACK_BUFFER, ffi::GL_READ_WRITE)
+      };
+   }
+
    #[inline]
    pub fn map_range(&mut self) {
       self.ptr = unsafe {